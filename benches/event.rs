@@ -1,45 +1,107 @@
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use rollo::game::{Event, EventProcessor};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use rollo::game::{Event, EventAction, EventProcessor, WheelEventProcessor};
 use std::time::Duration;
 
-fn event_iter(c: &mut Criterion) {
-    let mut event_processor = EventProcessor::<MyEvent>::new(1000000);
+const START: i64 = 1000000;
+/// One second after the start, the events of the first two seconds are executed.
+const ITER_TIME: i64 = START + 1000;
+/// Every event is executed.
+const PASS_TIME: i64 = 20000000;
+
+fn events() -> EventProcessor<MyEvent> {
+    let mut event_processor = EventProcessor::<MyEvent>::new(START);
     for i in 0..1250 {
         for _ in 0..300 {
             event_processor.add_event(MyEvent, Duration::from_secs(i));
         }
     }
-    c.bench_function("event_iter", |b| {
-        b.iter(|| {
-            event_processor.update(black_box(1000));
-        });
-    });
+    event_processor
 }
 
-fn event_iter_rev(c: &mut Criterion) {
-    let mut event_processor = EventProcessor::<MyEvent>::new(1000000);
-    for i in (0..1250).rev() {
-        for _ in (0..300).rev() {
+fn wheel_events() -> WheelEventProcessor<MyEvent> {
+    let mut event_processor = WheelEventProcessor::<MyEvent>::new(START);
+    for i in 0..1250 {
+        for _ in 0..300 {
             event_processor.add_event(MyEvent, Duration::from_secs(i));
         }
     }
+    event_processor
+}
+
+fn event_iter(c: &mut Criterion) {
+    c.bench_function("event_iter", |b| {
+        b.iter_batched(
+            events,
+            |mut event_processor| event_processor.update(black_box(ITER_TIME), &mut ()),
+            BatchSize::LargeInput,
+        );
+    });
+}
+
+fn event_iter_rev(c: &mut Criterion) {
     c.bench_function("event_iter_rev", |b| {
-        b.iter(|| {
-            event_processor.update(black_box(1000));
-        });
+        b.iter_batched(
+            || {
+                let mut event_processor = EventProcessor::<MyEvent>::new(START);
+                for i in (0..1250).rev() {
+                    for _ in (0..300).rev() {
+                        event_processor.add_event(MyEvent, Duration::from_secs(i));
+                    }
+                }
+                event_processor
+            },
+            |mut event_processor| event_processor.update(black_box(ITER_TIME), &mut ()),
+            BatchSize::LargeInput,
+        );
     });
 }
 
 fn event_pass(c: &mut Criterion) {
-    let mut event_processor = EventProcessor::<MyEvent>::new(1000000);
-    for i in 0..1250 {
-        for _ in 0..300 {
-            event_processor.add_event(MyEvent, Duration::from_secs(i));
-        }
-    }
     c.bench_function("event_pass", |b| {
+        b.iter_batched(
+            events,
+            |mut event_processor| event_processor.update(black_box(PASS_TIME), &mut ()),
+            BatchSize::LargeInput,
+        );
+    });
+}
+
+fn wheel_event_iter(c: &mut Criterion) {
+    c.bench_function("wheel_event_iter", |b| {
+        b.iter_batched(
+            wheel_events,
+            |mut event_processor| event_processor.update(black_box(ITER_TIME), &mut ()),
+            BatchSize::LargeInput,
+        );
+    });
+}
+
+fn wheel_event_pass(c: &mut Criterion) {
+    c.bench_function("wheel_event_pass", |b| {
+        b.iter_batched(
+            wheel_events,
+            |mut event_processor| event_processor.update(black_box(PASS_TIME), &mut ()),
+            BatchSize::LargeInput,
+        );
+    });
+}
+
+fn event_add_cancel(c: &mut Criterion) {
+    let mut event_processor = events();
+    c.bench_function("event_add_cancel", |b| {
         b.iter(|| {
-            event_processor.update(black_box(20000000));
+            let id = event_processor.add_event(MyEvent, black_box(Duration::from_millis(625500)));
+            event_processor.cancel_event(id, false);
+        });
+    });
+}
+
+fn wheel_event_add_cancel(c: &mut Criterion) {
+    let mut event_processor = wheel_events();
+    c.bench_function("wheel_event_add_cancel", |b| {
+        b.iter(|| {
+            let id = event_processor.add_event(MyEvent, black_box(Duration::from_millis(625500)));
+            event_processor.cancel_event(id, false);
         });
     });
 }
@@ -47,12 +109,23 @@ fn event_pass(c: &mut Criterion) {
 struct MyEvent;
 
 impl Event for MyEvent {
-    fn on_execute(&self, _diff: i64) {}
+    fn on_execute(&mut self, _ctx: &mut (), _diff: i64) -> EventAction<Self> {
+        EventAction::Next
+    }
 
     fn is_deletable(&self) -> bool {
         true
     }
 }
 
-criterion_group!(benches, event_iter, event_pass, event_iter_rev);
+criterion_group!(
+    benches,
+    event_iter,
+    event_pass,
+    event_iter_rev,
+    wheel_event_iter,
+    wheel_event_pass,
+    event_add_cancel,
+    wheel_event_add_cancel
+);
 criterion_main!(benches);
@@ -2,7 +2,7 @@ use once_cell::sync::Lazy;
 use parking_lot::Mutex;
 use rollo::{
     error::Error,
-    game::{Event, EventProcessor, GameTime},
+    game::{Event, EventAction, EventProcessor, GameTime},
     packet::Packet,
     server::{ListenerSecurity, SocketTools, World, WorldSession, WorldSocketMgr},
     tokio, AtomicCell,
@@ -39,8 +39,9 @@ async fn main() {
 struct MyEvent;
 
 impl Event for MyEvent {
-    fn on_execute(&self, _diff: i64) {
+    fn on_execute(&mut self, _ctx: &mut (), _diff: i64) -> EventAction<Self> {
         println!("Event executed at {}", WORLD.game_time.load().timestamp);
+        EventAction::Next
     }
 
     fn is_deletable(&self) -> bool {
@@ -56,7 +57,7 @@ struct MyWorld {
 impl World for MyWorld {
     type WorldSessionimplementer = MyWorldSession;
     fn update(&'static self, _diff: i64, game_time: GameTime) {
-        self.events.lock().update(game_time.timestamp);
+        self.events.lock().update(game_time.timestamp, &mut ());
     }
 
     fn game_time(&'static self) -> Option<&'static AtomicCell<GameTime>> {
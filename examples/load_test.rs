@@ -0,0 +1,29 @@
+use rollo::{
+    client::{LoadTest, Scenario},
+    tokio,
+};
+use std::{env, time::Duration};
+
+// cargo run --example load_test -- 127.0.0.1:6666 1000 10 30
+// (address, bots, packets per second, duration in seconds)
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = env::args().collect();
+    let arg = |i: usize, default: &str| args.get(i).cloned().unwrap_or_else(|| default.into());
+
+    let addr = arg(1, "127.0.0.1:6666");
+    let clients = arg(2, "100").parse().expect("amount of bots");
+    let rate = arg(3, "10").parse().expect("packets per second");
+    let duration = arg(4, "10").parse().expect("duration in seconds");
+
+    let scenario = Scenario::new(10, vec![0; 32])
+        .with_rate(rate)
+        .with_duration(Duration::from_secs(duration));
+
+    let report = LoadTest::new(addr, clients, scenario)
+        .with_ramp_up(Duration::from_secs(5))
+        .run()
+        .await;
+
+    println!("{}", report);
+}
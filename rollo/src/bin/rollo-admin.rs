@@ -0,0 +1,103 @@
+//! Client of the admin console (`rollo::server::AdminConsole`).
+//!
+//! ```text
+//! rollo-admin --tcp 127.0.0.1:6670 sessions
+//! rollo-admin --unix /run/game/admin.sock kick 12
+//! rollo-admin --tcp 127.0.0.1:6670
+//! ```
+//! Without command, the commands are read from stdin.
+use std::{
+    env,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::TcpStream,
+    process,
+};
+
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+const USAGE: &str = "usage: rollo-admin (--tcp <addr> | --unix <path>) [command...]";
+
+trait Stream: Read + Write {}
+
+impl<T: Read + Write> Stream for T {}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    if args.len() < 2 {
+        exit(USAGE);
+    }
+
+    let stream: Box<dyn Stream> = match args[0].as_str() {
+        "--tcp" => Box::new(TcpStream::connect(&args[1]).unwrap_or_else(|e| exit(&e.to_string()))),
+        #[cfg(unix)]
+        "--unix" => {
+            Box::new(UnixStream::connect(&args[1]).unwrap_or_else(|e| exit(&e.to_string())))
+        }
+        _ => exit(USAGE),
+    };
+    let mut console = Console {
+        reader: BufReader::new(stream),
+    };
+
+    if args.len() > 2 {
+        match console.execute(&args[2..].join(" ")) {
+            Ok(true) => {}
+            Ok(false) => process::exit(1),
+            Err(error) => exit(&error.to_string()),
+        }
+        return;
+    }
+
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line.unwrap_or_else(|e| exit(&e.to_string()));
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line == "quit" {
+            break;
+        }
+        if let Err(error) = console.execute(line) {
+            exit(&error.to_string());
+        }
+    }
+}
+
+struct Console {
+    reader: BufReader<Box<dyn Stream>>,
+}
+
+impl Console {
+    /// Sends a command and prints the response, returns whether it succeeded.
+    fn execute(&mut self, command: &str) -> io::Result<bool> {
+        let stream = self.reader.get_mut();
+        stream.write_all(command.as_bytes())?;
+        stream.write_all(b"\n")?;
+        stream.flush()?;
+
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            stdout.write_all(line.as_bytes())?;
+
+            let line = line.trim_end();
+            if line == "OK" {
+                return Ok(true);
+            }
+            if line.starts_with("ERR") {
+                return Ok(false);
+            }
+        }
+    }
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(2)
+}
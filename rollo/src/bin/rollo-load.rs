@@ -0,0 +1,82 @@
+//! Load test of a rollo server with scripted bots (`rollo::client::LoadTest`).
+//!
+//! ```text
+//! rollo-load 127.0.0.1:6666 --clients 1000 --cmd 10 --size 32 --rate 20 --duration 60
+//! rollo-load 127.0.0.1:6666 --login 1:2 --reply 10 --ramp-up 5
+//! rollo-load example.com:6666 --tls ca.pem example.com
+//! ```
+//! The report (connections, throughput, RTT percentiles) is printed at the end.
+use rollo::client::{LoadTest, Scenario};
+use std::{env, process, str::FromStr, time::Duration};
+
+const USAGE: &str = "usage: rollo-load <addr> [--clients <n>] [--cmd <cmd>] [--size <bytes>] \
+                     [--rate <packets/s>] [--duration <s>] [--ramp-up <s>] \
+                     [--login <cmd>[:<reply cmd>]] [--reply <cmd>] [--tls <root certificate> <domain>]";
+
+#[tokio::main]
+async fn main() {
+    let mut args = env::args().skip(1);
+    let addr = args.next().unwrap_or_else(|| exit(USAGE));
+
+    let mut clients = 100;
+    let mut cmd = 10;
+    let mut size = 0;
+    let mut rate = 10;
+    let mut duration = 10;
+    let mut ramp_up = 0;
+    let mut login = None;
+    let mut reply = None;
+    let mut tls = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--clients" => clients = parse(args.next()),
+            "--cmd" => cmd = parse(args.next()),
+            "--size" => size = parse(args.next()),
+            "--rate" => rate = parse(args.next()),
+            "--duration" => duration = parse(args.next()),
+            "--ramp-up" => ramp_up = parse(args.next()),
+            "--login" => {
+                let value = args.next().unwrap_or_else(|| exit(USAGE));
+                let mut parts = value.splitn(2, ':');
+                let login_cmd = parse(parts.next().map(str::to_owned));
+                login = Some((login_cmd, parts.next().map(|p| parse(Some(p.to_owned())))));
+            }
+            "--reply" => reply = Some(parse(args.next())),
+            "--tls" => match (args.next(), args.next()) {
+                (Some(root_certificate), Some(domain)) => tls = Some((root_certificate, domain)),
+                _ => exit(USAGE),
+            },
+            _ => exit(USAGE),
+        }
+    }
+
+    let mut scenario = Scenario::new(cmd, vec![0; size])
+        .with_rate(rate)
+        .with_duration(Duration::from_secs(duration));
+    if let Some((login_cmd, login_reply)) = login {
+        scenario = scenario.with_login(login_cmd, Vec::new(), login_reply);
+    }
+    if let Some(reply) = reply {
+        scenario = scenario.with_reply(reply, |_| true);
+    }
+
+    let mut load_test =
+        LoadTest::new(addr, clients, scenario).with_ramp_up(Duration::from_secs(ramp_up));
+    if let Some((root_certificate, domain)) = tls {
+        load_test = load_test.with_tls(root_certificate, domain);
+    }
+
+    println!("{}", load_test.run().await);
+}
+
+fn parse<T: FromStr>(value: Option<String>) -> T {
+    value
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_else(|| exit(USAGE))
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(2)
+}
@@ -0,0 +1,244 @@
+use crate::{
+    error::{Error, Result},
+    io::read::MAX_SIZE,
+    packet::Packet,
+};
+use easy_pool::{PoolObjectContainer, PoolSegQueue};
+use once_cell::sync::Lazy;
+use std::{collections::HashMap, convert::TryInto, mem, sync::Arc};
+
+const HEADER_SIZE: usize = mem::size_of::<u32>() + mem::size_of::<u16>();
+
+const FRAGMENT_HEADER_SIZE: usize = mem::size_of::<u8>() * 2;
+
+// Keeps no buffer, the reassembled buffers can be of any size and are freed when dropped.
+static POOL_VEC_ASSEMBLED: Lazy<Arc<PoolSegQueue<Vec<u8>>>> =
+    Lazy::new(|| Arc::new(PoolSegQueue::new(0)));
+
+/// Reassembles the messages of the channels and the streams sent by the server.
+///
+/// The connection is closed (`Error::StreamSize`) if the data being reassembled exceeds
+/// `max_memory`.
+#[derive(Debug)]
+pub(crate) struct Assembler {
+    max_memory: usize,
+    memory: usize,
+    channels: HashMap<u8, Vec<u8>>,
+    streams: HashMap<u32, (u16, Vec<u8>)>,
+}
+
+impl Assembler {
+    pub(crate) fn new(max_memory: usize) -> Self {
+        Self {
+            max_memory,
+            memory: 0,
+            channels: HashMap::new(),
+            streams: HashMap::new(),
+        }
+    }
+
+    /// Adds a fragment of a channel, returns the packets of the message once the last fragment
+    /// is received.
+    pub(crate) fn push_fragment(&mut self, packet: Packet) -> Result<Vec<Packet>> {
+        let payload = packet.payload.ok_or(Error::PacketPayload)?;
+
+        if payload.len() < FRAGMENT_HEADER_SIZE {
+            return Err(Error::PacketPayload);
+        }
+
+        let (channel_id, last) = (payload[0], payload[1] == 1);
+        let chunk = &payload[FRAGMENT_HEADER_SIZE..];
+        self.reserve(chunk.len())?;
+        self.channels
+            .entry(channel_id)
+            .or_default()
+            .extend_from_slice(chunk);
+
+        if !last {
+            return Ok(Vec::new());
+        }
+
+        let data = self.channels.remove(&channel_id).unwrap_or_default();
+        self.memory -= data.len();
+
+        split_packets(&data)
+    }
+
+    /// Adds a fragment of a stream, returns the Packet once the last fragment is received.
+    pub(crate) fn push_stream(&mut self, packet: Packet) -> Result<Option<Packet>> {
+        let (stream_id, last, packet) = packet.into_stream()?;
+        let chunk = packet.payload.as_deref().map_or(&[][..], |p| p.as_slice());
+        self.reserve(chunk.len())?;
+        self.streams
+            .entry(stream_id)
+            .or_insert_with(|| (packet.cmd, Vec::new()))
+            .1
+            .extend_from_slice(chunk);
+
+        if !last {
+            return Ok(None);
+        }
+
+        let (cmd, data) = self.streams.remove(&stream_id).unwrap_or_default();
+        self.memory -= data.len();
+
+        Ok(Some(Packet::new(cmd, into_payload(data))))
+    }
+
+    fn reserve(&mut self, len: usize) -> Result<()> {
+        self.memory += len;
+
+        if self.memory > self.max_memory {
+            Err(Error::StreamSize)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// The packets ([size][cmd][payload]) of a message sent on a channel.
+fn split_packets(mut data: &[u8]) -> Result<Vec<Packet>> {
+    let mut packets = Vec::new();
+
+    while !data.is_empty() {
+        if data.len() < HEADER_SIZE {
+            return Err(Error::PacketPayload);
+        }
+
+        let size = u32::from_be_bytes(
+            data[..mem::size_of::<u32>()]
+                .try_into()
+                .map_err(|_| Error::PacketPayload)?,
+        ) as usize;
+        let cmd = u16::from_be_bytes(
+            data[mem::size_of::<u32>()..HEADER_SIZE]
+                .try_into()
+                .map_err(|_| Error::PacketPayload)?,
+        );
+
+        if size >= MAX_SIZE {
+            return Err(Error::PacketSize);
+        }
+
+        let end = HEADER_SIZE + size;
+        let payload = data.get(HEADER_SIZE..end).ok_or(Error::PacketPayload)?;
+        packets.push(Packet::new(cmd, into_payload(payload.to_vec())));
+        data = &data[end..];
+    }
+
+    Ok(packets)
+}
+
+fn into_payload(data: Vec<u8>) -> Option<PoolObjectContainer<Vec<u8>>> {
+    if data.is_empty() {
+        None
+    } else {
+        Some(POOL_VEC_ASSEMBLED.create_with(|| data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{to_bytes, FRAGMENT_CMD, STREAM_CMD};
+
+    fn fragment(channel_id: u8, last: bool, chunk: &[u8]) -> Packet {
+        let mut payload = vec![channel_id, last as u8];
+        payload.extend_from_slice(chunk);
+        Packet::new(FRAGMENT_CMD, into_payload(payload))
+    }
+
+    fn stream(stream_id: u32, cmd: u16, last: bool, chunk: &[u8]) -> Packet {
+        let mut payload = stream_id.to_be_bytes().to_vec();
+        payload.extend_from_slice(&cmd.to_be_bytes());
+        payload.push(last as u8);
+        payload.extend_from_slice(chunk);
+        Packet::new(STREAM_CMD, into_payload(payload))
+    }
+
+    #[test]
+    fn test_fragments() {
+        let mut assembler = Assembler::new(100);
+        let first = to_bytes(5, Some(&[1, 2, 3, 4]));
+        let second = to_bytes(6, None);
+
+        assert!(assembler
+            .push_fragment(fragment(1, false, &first[..5]))
+            .unwrap()
+            .is_empty());
+        assert!(assembler
+            .push_fragment(fragment(2, false, &second[..2]))
+            .unwrap()
+            .is_empty());
+        assert_eq!(assembler.memory, 7);
+
+        let packets = assembler
+            .push_fragment(fragment(1, true, &first[5..]))
+            .unwrap();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].cmd, 5);
+        assert_eq!(packets[0].payload.as_deref(), Some(&vec![1, 2, 3, 4]));
+
+        let packets = assembler
+            .push_fragment(fragment(2, true, &second[2..]))
+            .unwrap();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].cmd, 6);
+        assert!(packets[0].payload.is_none());
+        assert_eq!(assembler.memory, 0);
+    }
+
+    #[test]
+    fn test_fragment_packets() {
+        let mut assembler = Assembler::new(100);
+        let mut bytes = to_bytes(5, Some(&[1])).to_vec();
+        bytes.extend_from_slice(&to_bytes(6, Some(&[2])));
+
+        let packets = assembler.push_fragment(fragment(1, true, &bytes)).unwrap();
+        assert_eq!(packets.iter().map(|p| p.cmd).collect::<Vec<_>>(), [5, 6]);
+
+        let packets = assembler.push_fragment(fragment(1, true, &bytes[..8]));
+        assert_eq!(packets.unwrap_err(), Error::PacketPayload);
+        assert_eq!(
+            assembler
+                .push_fragment(Packet::new(FRAGMENT_CMD, None))
+                .unwrap_err(),
+            Error::PacketPayload
+        );
+    }
+
+    #[test]
+    fn test_stream() {
+        let mut assembler = Assembler::new(100);
+
+        assert!(assembler
+            .push_stream(stream(1, 8, false, &[1, 2]))
+            .unwrap()
+            .is_none());
+        let packet = assembler
+            .push_stream(stream(1, 8, true, &[3]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(packet.cmd, 8);
+        assert_eq!(packet.payload.as_deref(), Some(&vec![1, 2, 3]));
+        assert_eq!(assembler.memory, 0);
+
+        let packet = assembler.push_stream(stream(2, 9, true, &[])).unwrap();
+        assert!(packet.unwrap().payload.is_none());
+    }
+
+    #[test]
+    fn test_max_memory() {
+        let mut assembler = Assembler::new(4);
+
+        assert!(assembler
+            .push_stream(stream(1, 8, false, &[1, 2, 3]))
+            .is_ok());
+        assert_eq!(
+            assembler
+                .push_fragment(fragment(1, false, &[1, 2]))
+                .unwrap_err(),
+            Error::StreamSize
+        );
+    }
+}
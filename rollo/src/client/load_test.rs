@@ -0,0 +1,475 @@
+use super::{ClientConfiguration, ClientEvent, ClientSecurity, RolloClient};
+use crate::packet::Packet;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    select,
+    sync::mpsc::UnboundedReceiver,
+    time::{interval, sleep, sleep_until, timeout, MissedTickBehavior},
+};
+
+/// Check of a reply of the server.
+pub type ReplyCheck = fn(&Packet) -> bool;
+
+/// # Scenario
+/// What each bot of a `LoadTest` does: login, packets sent at a rate and replies expected.
+/// ## Usage
+/// ```rust, no_run
+/// use rollo::client::Scenario;
+/// use std::time::Duration;
+///
+/// let scenario = Scenario::new(10, vec![1, 2, 3])
+///     .with_login(1, b"bot".to_vec(), Some(2))
+///     .with_rate(20)
+///     .with_duration(Duration::from_secs(60))
+///     .with_reply(10, |packet| packet.payload.is_some());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    login: Option<(u16, Vec<u8>, Option<u16>)>,
+    cmd: u16,
+    payload: Vec<u8>,
+    rate: u32,
+    duration: Duration,
+    reply: Option<(u16, ReplyCheck)>,
+    reply_timeout: Duration,
+}
+
+impl Scenario {
+    /// Creates the scenario with the packet sent by the bots.
+    pub fn new(cmd: u16, payload: Vec<u8>) -> Self {
+        Self {
+            login: None,
+            cmd,
+            payload,
+            rate: 10,
+            duration: Duration::from_secs(10),
+            reply: None,
+            reply_timeout: Duration::from_secs(5),
+        }
+    }
+
+    /// Packet sent after the connection, the bot waits for the reply (cmd) if any.
+    pub fn with_login(mut self, cmd: u16, payload: Vec<u8>, reply: Option<u16>) -> Self {
+        self.login = Some((cmd, payload, reply));
+        self
+    }
+
+    /// Packets sent per second by each bot (10 by default).
+    pub fn with_rate(mut self, rate: u32) -> Self {
+        self.rate = rate.max(1);
+        self
+    }
+
+    /// Duration of the sending (10 seconds by default).
+    pub fn with_duration(mut self, duration: Duration) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    /// Each packet expects a reply with the cmd, checked by the function.
+    pub fn with_reply(mut self, cmd: u16, check: ReplyCheck) -> Self {
+        self.reply = Some((cmd, check));
+        self
+    }
+
+    /// Delay to wait for the login reply and the last replies (5 seconds by default).
+    pub fn with_reply_timeout(mut self, reply_timeout: Duration) -> Self {
+        self.reply_timeout = reply_timeout;
+        self
+    }
+}
+
+/// # Load Test
+/// Spawns many bots following a `Scenario` against a server.
+/// ## Usage
+/// ```rust, no_run
+/// use rollo::client::{LoadTest, Scenario};
+/// use std::time::Duration;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let report = LoadTest::new("127.0.0.1:6666", 1000, Scenario::new(10, vec![1, 2, 3]))
+///         .with_ramp_up(Duration::from_secs(5))
+///         .run()
+///         .await;
+///
+///     println!("{}", report);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct LoadTest {
+    addr: String,
+    clients: usize,
+    scenario: Scenario,
+    configuration: ClientConfiguration,
+    ramp_up: Duration,
+    tls: Option<(PathBuf, String)>,
+}
+
+impl LoadTest {
+    /// Creates the load test with the amount of bots.
+    pub fn new(addr: impl AsRef<str>, clients: usize, scenario: Scenario) -> Self {
+        Self {
+            addr: addr.as_ref().to_owned(),
+            clients,
+            scenario,
+            configuration: ClientConfiguration::new()
+                .with_ping_interval(Duration::from_secs(1))
+                .with_reconnect(false),
+            ramp_up: Duration::ZERO,
+            tls: None,
+        }
+    }
+
+    /// Configuration of the bots (pings every second and no reconnection by default).
+    pub fn with_configuration(mut self, configuration: ClientConfiguration) -> Self {
+        self.configuration = configuration;
+        self
+    }
+
+    /// The connections of the bots are spread over the duration.
+    pub fn with_ramp_up(mut self, ramp_up: Duration) -> Self {
+        self.ramp_up = ramp_up;
+        self
+    }
+
+    /// Connects with Tls (root certificate, name of the server).
+    pub fn with_tls(mut self, root_certificate: impl AsRef<Path>, domain: impl AsRef<str>) -> Self {
+        self.tls = Some((
+            root_certificate.as_ref().to_owned(),
+            domain.as_ref().to_owned(),
+        ));
+        self
+    }
+
+    /// Runs the bots and waits for the end of the scenario.
+    pub async fn run(&self) -> LoadReport {
+        let start = Instant::now();
+        let test = Arc::new(self.clone());
+
+        let bots: Vec<_> = (0..self.clients)
+            .map(|i| {
+                let delay = self.ramp_up.mul_f64(i as f64 / self.clients as f64);
+                tokio::spawn(Arc::clone(&test).run_bot(delay))
+            })
+            .collect();
+
+        let mut report = LoadReport::new(self.clients);
+        for bot in bots {
+            if let Ok(bot) = bot.await {
+                report.add(bot);
+            }
+        }
+        report.duration = start.elapsed();
+        report.rtt.sort_unstable();
+
+        report
+    }
+
+    async fn run_bot(self: Arc<Self>, delay: Duration) -> BotReport {
+        sleep(delay).await;
+
+        let mut report = BotReport::default();
+        let security = match &self.tls {
+            Some((root_certificate, domain)) => ClientSecurity::Tls(root_certificate, domain),
+            None => ClientSecurity::Tcp,
+        };
+
+        let (client, mut events) =
+            match RolloClient::connect(&self.addr, security, self.configuration).await {
+                Ok(client) => client,
+                Err(_) => return report,
+            };
+        report.connected = true;
+
+        if !self.login(&client, &mut events, &mut report).await {
+            report.login_failed = true;
+            client.close();
+            return report;
+        }
+
+        self.send(&client, &mut events, &mut report).await;
+        client.close();
+
+        report
+    }
+
+    async fn login(
+        &self,
+        client: &RolloClient,
+        events: &mut UnboundedReceiver<ClientEvent>,
+        report: &mut BotReport,
+    ) -> bool {
+        let (cmd, payload, reply) = match &self.scenario.login {
+            Some(login) => login,
+            None => return true,
+        };
+
+        if client.send(*cmd, Some(payload)).is_err() {
+            return false;
+        }
+
+        let reply = match reply {
+            Some(reply) => *reply,
+            None => return true,
+        };
+
+        timeout(self.scenario.reply_timeout, async {
+            while let Some(event) = events.recv().await {
+                match event {
+                    ClientEvent::Packet(packet) if packet.cmd == reply => return true,
+                    ClientEvent::Latency(rtt) => report.rtt.push(rtt),
+                    ClientEvent::Disconnected => return false,
+                    _ => {}
+                }
+            }
+
+            false
+        })
+        .await
+        .unwrap_or(false)
+    }
+
+    async fn send(
+        &self,
+        client: &RolloClient,
+        events: &mut UnboundedReceiver<ClientEvent>,
+        report: &mut BotReport,
+    ) {
+        let scenario = &self.scenario;
+        let mut rate = interval(Duration::from_secs(1) / scenario.rate);
+        rate.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let end = tokio::time::Instant::now() + scenario.duration;
+
+        // Sending
+        loop {
+            select! {
+                _ = rate.tick() => {
+                    if client.send(scenario.cmd, Some(&scenario.payload)).is_err() {
+                        return;
+                    }
+                    report.sent += 1;
+                }
+                event = events.recv() => if !self.on_event(event, report) {
+                    return;
+                },
+                _ = sleep_until(end) => break,
+            }
+        }
+
+        // Last replies
+        if scenario.reply.is_some() {
+            let _ = timeout(scenario.reply_timeout, async {
+                while report.replies + report.invalid_replies < report.sent {
+                    if !self.on_event(events.recv().await, report) {
+                        return;
+                    }
+                }
+            })
+            .await;
+        }
+    }
+
+    // Returns false if the connection is lost.
+    fn on_event(&self, event: Option<ClientEvent>, report: &mut BotReport) -> bool {
+        match event {
+            Some(ClientEvent::Packet(packet)) => {
+                report.received += 1;
+
+                if let Some((cmd, check)) = self.scenario.reply {
+                    if packet.cmd == cmd && check(&packet) {
+                        report.replies += 1;
+                    } else if packet.cmd == cmd {
+                        report.invalid_replies += 1;
+                    }
+                }
+
+                true
+            }
+            Some(ClientEvent::Latency(rtt)) => {
+                report.rtt.push(rtt);
+                true
+            }
+            // The bots don't answer the requests of the server.
+            Some(ClientEvent::Request(..)) | Some(ClientEvent::Reconnected) => true,
+            Some(ClientEvent::Disconnected) | None => {
+                report.disconnected = true;
+                false
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct BotReport {
+    connected: bool,
+    login_failed: bool,
+    disconnected: bool,
+    sent: u64,
+    received: u64,
+    replies: u64,
+    invalid_replies: u64,
+    rtt: Vec<i64>,
+}
+
+/// Result of a `LoadTest`.
+#[derive(Debug, Clone)]
+pub struct LoadReport {
+    /// Amount of bots.
+    pub clients: usize,
+    /// Bots connected.
+    pub connected: usize,
+    /// Bots without login reply.
+    pub login_failures: usize,
+    /// Bots disconnected before the end.
+    pub disconnections: usize,
+    /// Packets sent (login excluded).
+    pub sent: u64,
+    /// Packets received.
+    pub received: u64,
+    /// Valid replies.
+    pub replies: u64,
+    /// Replies rejected by the check of the scenario.
+    pub invalid_replies: u64,
+    /// Duration of the test.
+    pub duration: Duration,
+    rtt: Vec<i64>,
+}
+
+impl LoadReport {
+    fn new(clients: usize) -> Self {
+        Self {
+            clients,
+            connected: 0,
+            login_failures: 0,
+            disconnections: 0,
+            sent: 0,
+            received: 0,
+            replies: 0,
+            invalid_replies: 0,
+            duration: Duration::ZERO,
+            rtt: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, mut bot: BotReport) {
+        self.connected += bot.connected as usize;
+        self.login_failures += bot.login_failed as usize;
+        self.disconnections += bot.disconnected as usize;
+        self.sent += bot.sent;
+        self.received += bot.received;
+        self.replies += bot.replies;
+        self.invalid_replies += bot.invalid_replies;
+        self.rtt.append(&mut bot.rtt);
+    }
+
+    /// Ratio of the bots connected (0.0 to 1.0).
+    pub fn connect_success(&self) -> f64 {
+        if self.clients == 0 {
+            return 0.0;
+        }
+
+        self.connected as f64 / self.clients as f64
+    }
+
+    /// Packets sent per second.
+    pub fn sent_throughput(&self) -> f64 {
+        self.sent as f64 / self.duration.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Packets received per second.
+    pub fn received_throughput(&self) -> f64 {
+        self.received as f64 / self.duration.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Round trip time (milliseconds) of the pings at a percentile (0.0 to 100.0).
+    pub fn rtt_percentile(&self, percentile: f64) -> Option<i64> {
+        if self.rtt.is_empty() {
+            return None;
+        }
+
+        let rank = (percentile.clamp(0.0, 100.0) / 100.0 * (self.rtt.len() - 1) as f64).round();
+
+        self.rtt.get(rank as usize).copied()
+    }
+}
+
+impl fmt::Display for LoadReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "connected: {}/{} ({:.1}%), login failures: {}, disconnections: {}",
+            self.connected,
+            self.clients,
+            self.connect_success() * 100.0,
+            self.login_failures,
+            self.disconnections
+        )?;
+        writeln!(
+            f,
+            "sent: {} ({:.1}/s), received: {} ({:.1}/s), replies: {}, invalid replies: {}",
+            self.sent,
+            self.sent_throughput(),
+            self.received,
+            self.received_throughput(),
+            self.replies,
+            self.invalid_replies
+        )?;
+
+        match (
+            self.rtt_percentile(50.0),
+            self.rtt_percentile(90.0),
+            self.rtt_percentile(99.0),
+        ) {
+            (Some(p50), Some(p90), Some(p99)) => write!(
+                f,
+                "rtt: p50 {}ms, p90 {}ms, p99 {}ms ({} pings)",
+                p50,
+                p90,
+                p99,
+                self.rtt.len()
+            ),
+            _ => write!(f, "rtt: no ping"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut report = LoadReport::new(4);
+        report.add(BotReport {
+            connected: true,
+            sent: 10,
+            replies: 9,
+            invalid_replies: 1,
+            rtt: vec![5, 1, 3],
+            ..BotReport::default()
+        });
+        report.add(BotReport {
+            connected: true,
+            login_failed: true,
+            rtt: vec![2, 4],
+            ..BotReport::default()
+        });
+        report.duration = Duration::from_secs(2);
+        report.rtt.sort_unstable();
+
+        assert_eq!(report.connected, 2);
+        assert_eq!(report.login_failures, 1);
+        assert_eq!(report.connect_success(), 0.5);
+        assert_eq!(report.sent_throughput(), 5.0);
+        assert_eq!(report.rtt_percentile(0.0), Some(1));
+        assert_eq!(report.rtt_percentile(50.0), Some(3));
+        assert_eq!(report.rtt_percentile(100.0), Some(5));
+        assert_eq!(LoadReport::new(1).rtt_percentile(50.0), None);
+    }
+}
@@ -0,0 +1,9 @@
+mod assembler;
+
+mod load_test;
+pub use load_test::{LoadReport, LoadTest, ReplyCheck, Scenario};
+
+mod rollo_client;
+pub use rollo_client::{ClientConfiguration, ClientEvent, ClientSecurity, RolloClient};
+
+mod tls;
@@ -0,0 +1,597 @@
+use super::{assembler::Assembler, tls::load_config};
+use crate::{
+    error::{Error, Result},
+    io::read::{Reader, MAX_SIZE},
+    packet::{
+        is_reserved, to_bytes, to_rpc_bytes, Packet, FRAGMENT_CMD, RPC_REQUEST_CMD,
+        RPC_RESPONSE_CMD, STREAM_CMD,
+    },
+};
+use easy_pool::PoolObjectContainer;
+use std::{
+    convert::{TryFrom, TryInto},
+    mem,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicI64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, ReadHalf, WriteHalf},
+    net::TcpStream,
+    select,
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    time::{interval, sleep, timeout, MissedTickBehavior},
+};
+use tokio_rustls::{client::TlsStream, rustls::ServerName, TlsConnector};
+
+/// Tcp or Tcp/Tls (root certificate, name of the server)
+#[derive(Debug)]
+pub enum ClientSecurity<'a> {
+    Tcp,
+    Tls(&'a Path, &'a str),
+}
+
+/// Events of a `RolloClient`.
+#[derive(Debug)]
+pub enum ClientEvent {
+    /// A packet received from the server (the pings are handled by the client).
+    ///
+    /// The messages of the channels and the streams are received once reassembled.
+    Packet(Packet),
+    /// A request (RPC) of the server with its request id, answered with `RolloClient::respond`.
+    Request(u32, Packet),
+    /// The round trip time (milliseconds) of a ping.
+    Latency(i64),
+    /// The connection is lost, the client reconnects if enabled.
+    Disconnected,
+    /// The connection is back.
+    Reconnected,
+}
+
+/// Configuration of a `RolloClient`.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientConfiguration {
+    no_delay: bool,
+    timeout: Duration,
+    ping_interval: Duration,
+    reconnect: bool,
+    min_backoff: Duration,
+    max_backoff: Duration,
+    max_stream_memory: usize,
+}
+
+impl ClientConfiguration {
+    pub const fn new() -> Self {
+        Self {
+            no_delay: true,
+            timeout: Duration::from_secs(10),
+            ping_interval: Duration::from_secs(5),
+            reconnect: true,
+            min_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            max_stream_memory: Self::MAX_STREAM_MEMORY,
+        }
+    }
+
+    const MAX_STREAM_MEMORY: usize = 16 * 1024 * 1024;
+
+    /// Delay to connect (and to complete the Tls handshake).
+    ///```rust, no_run
+    /// use rollo::client::ClientConfiguration;
+    /// use std::time::Duration;
+    ///
+    /// let conf = ClientConfiguration::new().with_timeout(Duration::from_secs(5));
+    /// ```
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Interval of the pings (must be lower than the timeout of the server).
+    ///```rust, no_run
+    /// use rollo::client::ClientConfiguration;
+    /// use std::time::Duration;
+    ///
+    /// let conf = ClientConfiguration::new().with_ping_interval(Duration::from_secs(1));
+    /// ```
+    pub const fn with_ping_interval(mut self, ping_interval: Duration) -> Self {
+        self.ping_interval = ping_interval;
+        self
+    }
+
+    /// Reconnects when the connection is lost (true by default).
+    ///```rust, no_run
+    /// use rollo::client::ClientConfiguration;
+    ///
+    /// let conf = ClientConfiguration::new().with_reconnect(false);
+    /// ```
+    pub const fn with_reconnect(mut self, reconnect: bool) -> Self {
+        self.reconnect = reconnect;
+        self
+    }
+
+    /// Delay between the attempts to reconnect, doubled after each failure up to the maximum.
+    ///```rust, no_run
+    /// use rollo::client::ClientConfiguration;
+    /// use std::time::Duration;
+    ///
+    /// let conf = ClientConfiguration::new()
+    ///     .with_backoff(Duration::from_millis(100), Duration::from_secs(5));
+    /// ```
+    pub const fn with_backoff(mut self, min_backoff: Duration, max_backoff: Duration) -> Self {
+        self.min_backoff = min_backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Maximum memory used to reassemble the messages of the channels and the streams,
+    /// the connection is closed if it is exceeded.
+    ///```rust, no_run
+    /// use rollo::client::ClientConfiguration;
+    ///
+    /// let conf = ClientConfiguration::new().with_max_stream_memory(4 * 1024 * 1024);
+    /// ```
+    pub const fn with_max_stream_memory(mut self, max_stream_memory: usize) -> Self {
+        self.max_stream_memory = max_stream_memory;
+        self
+    }
+
+    /// Sets TCP_NODELAY (true by default).
+    pub const fn with_no_delay(mut self, no_delay: bool) -> Self {
+        self.no_delay = no_delay;
+        self
+    }
+}
+
+impl Default for ClientConfiguration {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// # Rollo Client
+/// Connects to a rollo server, sends the pings and reconnects with a backoff.
+///
+/// The packets sent while the client is reconnecting are sent once the connection is back.
+/// ## Usage
+/// ```rust, no_run
+/// use rollo::client::{ClientConfiguration, ClientEvent, ClientSecurity, RolloClient};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (client, mut events) = RolloClient::connect(
+///         "127.0.0.1:6666",
+///         ClientSecurity::Tcp,
+///         ClientConfiguration::new(),
+///     )
+///     .await
+///     .unwrap();
+///
+///     client.send(10, Some(&[1, 2, 3])).unwrap();
+///
+///     while let Some(event) = events.recv().await {
+///         match event {
+///             ClientEvent::Packet(packet) => {
+///                 println!("cmd {} latency {}", packet.cmd, client.latency());
+///             }
+///             ClientEvent::Request(request_id, packet) => {
+///                 client.respond(request_id, packet.cmd, Some(&[4, 5])).unwrap();
+///             }
+///             _ => {}
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct RolloClient {
+    tx: UnboundedSender<Command>,
+    state: Arc<ClientState>,
+}
+
+impl RolloClient {
+    /// Connects to the server, the events are received with the receiver.
+    pub async fn connect(
+        addr: impl AsRef<str>,
+        security: ClientSecurity<'_>,
+        configuration: ClientConfiguration,
+    ) -> Result<(Self, UnboundedReceiver<ClientEvent>)> {
+        let (tx, rx) = unbounded_channel();
+        let client = Self::connect_with_callback(addr, security, configuration, move |event| {
+            // The receiver can be dropped by the user.
+            let _ = tx.send(event);
+        })
+        .await?;
+
+        Ok((client, rx))
+    }
+
+    /// Connects to the server, the events are given to the callback.
+    pub async fn connect_with_callback<F>(
+        addr: impl AsRef<str>,
+        security: ClientSecurity<'_>,
+        configuration: ClientConfiguration,
+        callback: F,
+    ) -> Result<Self>
+    where
+        F: FnMut(ClientEvent) + Send + 'static,
+    {
+        let connector = Connector::new(addr.as_ref(), security, configuration)?;
+        let transport = connector.connect().await?;
+
+        let (tx, rx) = unbounded_channel();
+        let state = Arc::new(ClientState {
+            latency: AtomicI64::new(0),
+            connected: AtomicBool::new(true),
+            closed: AtomicBool::new(false),
+        });
+
+        tokio::spawn(run(connector, transport, rx, Arc::clone(&state), callback));
+
+        Ok(Self { tx, state })
+    }
+
+    /// Sends a packet, returns an error if the client is closed or if the command is reserved.
+    pub fn send(&self, cmd: u16, payload: Option<&[u8]>) -> Result<()> {
+        if is_reserved(cmd) {
+            return Err(Error::ReservedCmd);
+        }
+
+        self.tx
+            .send(Command::Send(to_bytes(cmd, payload)))
+            .map_err(|_| Error::Channel)
+    }
+
+    /// Sends the reply of a request (RPC) received with `ClientEvent::Request`.
+    pub fn respond(&self, request_id: u32, cmd: u16, payload: Option<&[u8]>) -> Result<()> {
+        self.tx
+            .send(Command::Send(to_rpc_bytes(
+                RPC_RESPONSE_CMD,
+                request_id,
+                cmd,
+                payload,
+            )))
+            .map_err(|_| Error::Channel)
+    }
+
+    /// Round trip time (milliseconds) of the last ping.
+    pub fn latency(&self) -> i64 {
+        self.state.latency.load(Ordering::Acquire)
+    }
+
+    /// Returns true if the client is connected.
+    pub fn is_connected(&self) -> bool {
+        self.state.connected.load(Ordering::Acquire)
+    }
+
+    /// Closes the connection, the client does not reconnect.
+    pub fn close(&self) {
+        self.state.closed.store(true, Ordering::Release);
+        // The receiver is dropped if the client is already stopped.
+        let _ = self.tx.send(Command::Close);
+    }
+}
+
+impl Drop for RolloClient {
+    fn drop(&mut self) {
+        self.state.closed.store(true, Ordering::Release);
+    }
+}
+
+#[derive(Debug)]
+enum Command {
+    Send(PoolObjectContainer<Vec<u8>>),
+    Close,
+}
+
+#[derive(Debug)]
+struct ClientState {
+    latency: AtomicI64,
+    connected: AtomicBool,
+    closed: AtomicBool,
+}
+
+enum Transport {
+    Tcp(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+struct Connector {
+    addr: String,
+    tls: Option<(TlsConnector, ServerName)>,
+    configuration: ClientConfiguration,
+}
+
+impl Connector {
+    fn new(
+        addr: &str,
+        security: ClientSecurity<'_>,
+        configuration: ClientConfiguration,
+    ) -> Result<Self> {
+        let tls = match security {
+            ClientSecurity::Tcp => None,
+            ClientSecurity::Tls(root_certificate, domain) => {
+                let config = load_config(root_certificate).map_err(|_| Error::TlsConnect)?;
+                let domain = ServerName::try_from(domain).map_err(|_| Error::TlsConnect)?;
+                Some((TlsConnector::from(Arc::new(config)), domain))
+            }
+        };
+
+        Ok(Self {
+            addr: addr.to_owned(),
+            tls,
+            configuration,
+        })
+    }
+
+    async fn connect(&self) -> Result<Transport> {
+        let socket = timeout(self.configuration.timeout, TcpStream::connect(&self.addr))
+            .await
+            .map_err(|_| Error::Connect)?
+            .map_err(|_| Error::Connect)?;
+
+        if self.configuration.no_delay {
+            socket.set_nodelay(true).map_err(|_| Error::NoDelayError)?;
+        }
+
+        match &self.tls {
+            Some((connector, domain)) => {
+                let socket = timeout(
+                    self.configuration.timeout,
+                    connector.connect(domain.clone(), socket),
+                )
+                .await
+                .map_err(|_| Error::TlsConnect)?
+                .map_err(|_| Error::TlsConnect)?;
+
+                Ok(Transport::Tls(Box::new(socket)))
+            }
+            None => Ok(Transport::Tcp(socket)),
+        }
+    }
+
+    async fn reconnect(&self, state: &ClientState) -> Option<Transport> {
+        let mut delay = self.configuration.min_backoff;
+
+        loop {
+            sleep(delay).await;
+
+            if state.closed.load(Ordering::Acquire) {
+                return None;
+            }
+
+            if let Ok(transport) = self.connect().await {
+                return Some(transport);
+            }
+
+            delay = (delay * 2).min(self.configuration.max_backoff);
+        }
+    }
+}
+
+async fn run<F>(
+    connector: Connector,
+    mut transport: Transport,
+    mut rx: UnboundedReceiver<Command>,
+    state: Arc<ClientState>,
+    mut callback: F,
+) where
+    F: FnMut(ClientEvent) + Send + 'static,
+{
+    let configuration = connector.configuration;
+
+    loop {
+        let closed = match transport {
+            Transport::Tcp(socket) => {
+                handle(socket, &mut rx, &state, &mut callback, configuration).await
+            }
+            Transport::Tls(socket) => {
+                handle(*socket, &mut rx, &state, &mut callback, configuration).await
+            }
+        };
+
+        state.connected.store(false, Ordering::Release);
+
+        if closed || state.closed.load(Ordering::Acquire) {
+            return;
+        }
+
+        callback(ClientEvent::Disconnected);
+
+        if !connector.configuration.reconnect {
+            return;
+        }
+
+        match connector.reconnect(&state).await {
+            Some(new_transport) => transport = new_transport,
+            None => return,
+        }
+
+        state.connected.store(true, Ordering::Release);
+        callback(ClientEvent::Reconnected);
+    }
+}
+
+// Returns true if the connection is closed by the client.
+async fn handle<S, F>(
+    socket: S,
+    rx: &mut UnboundedReceiver<Command>,
+    state: &ClientState,
+    callback: &mut F,
+    configuration: ClientConfiguration,
+) -> bool
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: FnMut(ClientEvent),
+{
+    let (reader, mut writer) = tokio::io::split(socket);
+    let mut reader = BufReader::new(reader);
+    // The messages being reassembled are lost with the connection.
+    let mut assembler = Assembler::new(configuration.max_stream_memory);
+
+    select! {
+        _ = read(&mut reader, state, &mut assembler, callback) => false,
+        closed = write(&mut writer, rx, state, configuration.ping_interval) => closed,
+    }
+}
+
+async fn read<S, F>(
+    buffer: &mut BufReader<ReadHalf<S>>,
+    state: &ClientState,
+    assembler: &mut Assembler,
+    callback: &mut F,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: FnMut(ClientEvent),
+{
+    let mut reader = Reader::new(buffer);
+
+    loop {
+        let size = reader.read_size().await?;
+        let cmd = reader.read_cmd().await?;
+
+        if size >= MAX_SIZE {
+            return Err(Error::PacketSize);
+        }
+
+        let payload = if size == 0 {
+            None
+        } else {
+            reader.read_payload(size).await?
+        };
+
+        match cmd {
+            0 => {
+                let latency = parse_pong(payload.as_deref(), now())?;
+                state.latency.store(latency, Ordering::Release);
+                callback(ClientEvent::Latency(latency));
+            }
+            FRAGMENT_CMD => {
+                for packet in assembler.push_fragment(Packet::new(cmd, payload))? {
+                    dispatch(packet, assembler, callback)?;
+                }
+            }
+            _ => dispatch(Packet::new(cmd, payload), assembler, callback)?,
+        }
+    }
+}
+
+// Gives a packet (whole or reassembled from the fragments of a channel) to the callback.
+fn dispatch<F>(packet: Packet, assembler: &mut Assembler, callback: &mut F) -> Result<()>
+where
+    F: FnMut(ClientEvent),
+{
+    match packet.cmd {
+        STREAM_CMD => {
+            if let Some(packet) = assembler.push_stream(packet)? {
+                callback(ClientEvent::Packet(packet));
+            }
+        }
+        RPC_REQUEST_CMD => {
+            let (request_id, packet) = packet.into_rpc()?;
+            callback(ClientEvent::Request(request_id, packet));
+        }
+        cmd if is_reserved(cmd) => return Err(Error::ReservedCmd),
+        _ => callback(ClientEvent::Packet(packet)),
+    }
+
+    Ok(())
+}
+
+async fn write<S>(
+    writer: &mut WriteHalf<S>,
+    rx: &mut UnboundedReceiver<Command>,
+    state: &ClientState,
+    ping_interval: Duration,
+) -> bool
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut ping = interval(ping_interval);
+    ping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        let bytes = select! {
+            command = rx.recv() => match command {
+                Some(Command::Send(bytes)) => bytes,
+                _ => {
+                    let _ = writer.shutdown().await;
+                    return true;
+                }
+            },
+            _ = ping.tick() => to_ping_bytes(now(), state.latency.load(Ordering::Acquire)),
+        };
+
+        if writer.write_all(&bytes).await.is_err() {
+            return false;
+        }
+    }
+}
+
+const PING_SIZE: usize = mem::size_of::<u64>() + mem::size_of::<i64>();
+
+// Date of the ping and the latency known by the client, sent back by the server.
+fn to_ping_bytes(date: u64, latency: i64) -> PoolObjectContainer<Vec<u8>> {
+    let mut payload = [0; PING_SIZE];
+    payload[..mem::size_of::<u64>()].copy_from_slice(&date.to_be_bytes());
+    payload[mem::size_of::<u64>()..].copy_from_slice(&latency.to_be_bytes());
+
+    to_bytes(0, Some(&payload))
+}
+
+fn parse_pong(content: Option<&Vec<u8>>, now: u64) -> Result<i64> {
+    match content {
+        Some(content) if content.len() == PING_SIZE => {
+            let date = u64::from_be_bytes(
+                content[..mem::size_of::<u64>()]
+                    .try_into()
+                    .map_err(|_| Error::PacketPayload)?,
+            );
+
+            Ok(now.saturating_sub(date) as i64)
+        }
+        _ => Err(Error::PacketPayload),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ping() {
+        let bytes = to_ping_bytes(1625663748, 26);
+        assert_eq!(bytes.len(), 6 + PING_SIZE);
+        assert_eq!(bytes[..6], [0, 0, 0, 16, 0, 0]);
+
+        let content = bytes[6..].to_vec();
+        assert_eq!(parse_pong(Some(&content), 1625663800), Ok(52));
+        assert_eq!(parse_pong(Some(&vec![0; 8]), 0), Err(Error::PacketPayload));
+        assert_eq!(parse_pong(None, 0), Err(Error::PacketPayload));
+    }
+
+    #[tokio::test]
+    async fn test_connect_error() {
+        let configuration = ClientConfiguration::new().with_timeout(Duration::from_secs(1));
+        let result = RolloClient::connect("127.0.0.1:1", ClientSecurity::Tcp, configuration).await;
+        assert_eq!(result.err(), Some(Error::Connect));
+
+        let result = RolloClient::connect(
+            "127.0.0.1:1",
+            ClientSecurity::Tls(Path::new("missing.pem"), "example.com"),
+            configuration,
+        )
+        .await;
+        assert_eq!(result.err(), Some(Error::TlsConnect));
+    }
+}
@@ -0,0 +1,27 @@
+use rustls_pemfile::certs;
+use std::{
+    fs::File,
+    io::{self, BufReader},
+    path::Path,
+};
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+
+pub(crate) fn load_config(root_certificate: &Path) -> io::Result<ClientConfig> {
+    let certs = certs(&mut BufReader::new(File::open(root_certificate)?))
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid cert"))?;
+
+    let mut root_cert_store = RootCertStore::empty();
+    let (added, _) = root_cert_store.add_parsable_certificates(&certs);
+
+    if added == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no root certificate",
+        ));
+    }
+
+    Ok(ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(root_cert_store)
+        .with_no_client_auth())
+}
@@ -0,0 +1,534 @@
+//! # Server Configuration
+//! Configuration of a `WorldSocketMgr` read from a TOML file and the environment variables.
+//!
+//! The environment variables `ROLLO_<SECTION>__<KEY>` replace the values of the file
+//! (`ROLLO_NETWORK__TIMEOUT=30`, `ROLLO_DOS__GLOBAL='{ amount = 100, size = 10240 }'`).
+//!
+//! `WorldSocketMgr::watch_config` applies the values which are safe to change while running:
+//! timeouts, framing and `no_delay` (new sessions), DoS limits and the interval of the GameLoop.
+//! The bind address, the TLS files and the pool sizes need a restart.
+//! ## Usage
+//! ```rust, no_run
+//! use rollo::{config::ServerConfig, server::{World, WorldSocketMgr}};
+//! use std::time::Duration;
+//!
+//! # async fn test<W: World + 'static>(world: &'static W) {
+//! let config = ServerConfig::load("server.toml").unwrap();
+//! let mut server = WorldSocketMgr::from_config(world, &config);
+//!
+//! server.watch_config("server.toml", Duration::from_secs(5));
+//! server.start_with_config(&config).await.unwrap();
+//! # }
+//! ```
+//! ```toml
+//! [network]
+//! bind = "0.0.0.0:6666"
+//! no_delay = true
+//! timeout = 20
+//! tls = { certificate = "cert.pem", private_key = "key.pem" }
+//!
+//! [framing]
+//! fragment_size = 1024
+//! max_stream_memory = 16777216
+//! max_streams = 16
+//! flush = "delayed" # "immediate", "tick"
+//! flush_delay = 5
+//!
+//! [pools]
+//! packets = 4096
+//! streams = 64
+//!
+//! [dos]
+//! global = { amount = 100, size = 10240 }
+//! packets = [{ cmd = 10, amount = 30, size = 1024, policy = "close" }]
+//!
+//! [game_loop]
+//! interval = 25
+//! ```
+use crate::{
+    error::Result,
+    game::GameLoopControl,
+    packet::POOL_SIZE,
+    server::{
+        stream::STREAM_POOL_SIZE, DosLimits, DosPolicy, FlushPolicy, ListenerSecurity, World,
+        WorldSocketConfiguration, WorldSocketMgr,
+    },
+};
+use crossbeam::atomic::AtomicCell;
+use serde::Deserialize;
+use std::{
+    env, fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc},
+    time::Duration,
+};
+use tokio::{task::JoinHandle, time::interval};
+use toml::{value::Table, Value};
+
+/// Prefix of the environment variables.
+pub const ENV_PREFIX: &str = "ROLLO_";
+
+/// Configuration of a server.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub network: NetworkConfig,
+    pub framing: FramingConfig,
+    pub pools: PoolConfig,
+    pub dos: DosConfig,
+    pub game_loop: GameLoopConfig,
+}
+
+/// Listener and sockets.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// Needs a restart.
+    pub bind: String,
+    /// Needs a restart.
+    pub tls: Option<TlsConfig>,
+    pub no_delay: bool,
+    /// Timeout of the sessions (seconds).
+    pub timeout: u64,
+}
+
+/// Certificate and private key (PEM).
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    pub certificate: PathBuf,
+    pub private_key: PathBuf,
+}
+
+/// Fragments, streams and flush of the sessions.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FramingConfig {
+    pub fragment_size: usize,
+    pub max_stream_memory: usize,
+    pub max_streams: usize,
+    pub flush: FlushConfig,
+    /// Delay of `FlushConfig::Delayed` (milliseconds).
+    pub flush_delay: u64,
+}
+
+/// `FlushPolicy` of the sessions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlushConfig {
+    Immediate,
+    Delayed,
+    Tick,
+}
+
+/// Maximum amount of buffers kept by the pools, needs a restart.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PoolConfig {
+    pub packets: usize,
+    pub streams: usize,
+}
+
+/// Limits of the DoS protection, they replace the limits of the `World`.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DosConfig {
+    pub global: Option<GlobalLimit>,
+    pub packets: Vec<PacketLimit>,
+}
+
+/// Amount and size of the packets per second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GlobalLimit {
+    pub amount: u16,
+    pub size: u32,
+}
+
+/// Amount and size of the packets of a command per second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PacketLimit {
+    pub cmd: u16,
+    pub amount: u16,
+    pub size: u32,
+    pub policy: DosPolicy,
+}
+
+/// GameLoop
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameLoopConfig {
+    /// Interval of the ticks (milliseconds).
+    pub interval: u64,
+}
+
+impl ServerConfig {
+    /// Reads a TOML file, the environment variables replace its values.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut table = parse(&fs::read_to_string(path)?)?;
+        merge_env(&mut table, env::vars())?;
+        Self::from_table(table)
+    }
+
+    /// Default configuration, the environment variables replace its values.
+    pub fn from_env() -> io::Result<Self> {
+        let mut table = Table::new();
+        merge_env(&mut table, env::vars())?;
+        Self::from_table(table)
+    }
+
+    /// Parses a TOML configuration.
+    pub fn from_toml(text: &str) -> io::Result<Self> {
+        Self::from_table(parse(text)?)
+    }
+
+    fn from_table(table: Table) -> io::Result<Self> {
+        Value::Table(table)
+            .try_into()
+            .map_err(|error| io::Error::new(ErrorKind::InvalidData, error))
+    }
+
+    /// Configuration of the sessions.
+    pub fn configuration(&self) -> WorldSocketConfiguration {
+        let flush_policy = match self.framing.flush {
+            FlushConfig::Immediate => FlushPolicy::Immediate,
+            FlushConfig::Tick => FlushPolicy::Tick,
+            FlushConfig::Delayed => {
+                FlushPolicy::Delayed(Duration::from_millis(self.framing.flush_delay))
+            }
+        };
+
+        WorldSocketConfiguration::with_custom_configuration(
+            self.network.no_delay,
+            self.network.timeout,
+        )
+        .with_fragment_size(self.framing.fragment_size)
+        .with_max_stream_memory(self.framing.max_stream_memory)
+        .with_max_streams(self.framing.max_streams)
+        .with_flush_policy(flush_policy)
+    }
+
+    /// Security of the listener.
+    pub fn security(&self) -> ListenerSecurity<'_> {
+        match &self.network.tls {
+            Some(tls) => ListenerSecurity::Tls(&tls.certificate, &tls.private_key),
+            None => ListenerSecurity::Tcp,
+        }
+    }
+
+    /// Interval of the GameLoop.
+    pub fn game_loop_interval(&self) -> Duration {
+        Duration::from_millis(self.game_loop.interval)
+    }
+
+    /// The values of `other` which are different and need a restart.
+    fn restart_changes(&self, other: &Self) -> Vec<&'static str> {
+        let mut changes = Vec::new();
+        if self.network.bind != other.network.bind {
+            changes.push("network.bind");
+        }
+        if self.network.tls != other.network.tls {
+            changes.push("network.tls");
+        }
+        if self.pools != other.pools {
+            changes.push("pools");
+        }
+        changes
+    }
+}
+
+impl DosConfig {
+    fn apply(&self, limits: &DosLimits) {
+        limits.reset();
+        if let Some(global) = self.global {
+            limits.set_global(global.amount, global.size);
+        }
+        for packet in self.packets.iter() {
+            limits.set_packet(packet.cmd, packet.amount, packet.size, packet.policy);
+        }
+    }
+}
+
+impl PoolConfig {
+    fn apply(&self) {
+        POOL_SIZE.store(self.packets, Ordering::Release);
+        STREAM_POOL_SIZE.store(self.streams, Ordering::Release);
+    }
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        let configuration = WorldSocketConfiguration::new();
+        Self {
+            bind: "0.0.0.0:6666".to_string(),
+            tls: None,
+            no_delay: configuration.no_delay,
+            timeout: configuration.timeout,
+        }
+    }
+}
+
+impl Default for FramingConfig {
+    fn default() -> Self {
+        let configuration = WorldSocketConfiguration::new();
+        Self {
+            fragment_size: configuration.fragment_size,
+            max_stream_memory: configuration.max_stream_memory,
+            max_streams: configuration.max_streams,
+            flush: FlushConfig::Immediate,
+            flush_delay: 5,
+        }
+    }
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            packets: POOL_SIZE.load(Ordering::Acquire),
+            streams: STREAM_POOL_SIZE.load(Ordering::Acquire),
+        }
+    }
+}
+
+impl Default for GameLoopConfig {
+    fn default() -> Self {
+        Self { interval: 25 }
+    }
+}
+
+/// Values of a `WorldSocketMgr` changed by a reload.
+struct Reload {
+    configuration: Arc<AtomicCell<WorldSocketConfiguration>>,
+    limits: Arc<DosLimits>,
+    control: GameLoopControl,
+}
+
+impl Reload {
+    fn apply(&self, config: &ServerConfig) {
+        self.configuration.store(config.configuration());
+        config.dos.apply(&self.limits);
+        self.control.set_interval(config.game_loop_interval());
+    }
+}
+
+impl<W> WorldSocketMgr<W>
+where
+    W: Send + Sync + 'static + World,
+{
+    /// Create WorldSocketMgr with a configuration.
+    ///
+    /// The pool sizes are only applied if the pools are not used yet.
+    pub fn from_config(world: &'static W, config: &ServerConfig) -> Self {
+        config.pools.apply();
+        let server = Self::with_configuration(world, config.configuration());
+        config.dos.apply(&server.dos_limits());
+
+        server
+    }
+
+    /// Applies the values which are safe to change while running.
+    ///
+    /// The DoS limits of the configuration replace the limits set at runtime.
+    pub fn apply_config(&self, config: &ServerConfig) {
+        self.reload().apply(config);
+    }
+
+    /// Reads the file every period, applies the values which are safe to change if it changed.
+    ///
+    /// An invalid file is ignored, the changes which need a restart are logged.
+    pub fn watch_config(&self, path: impl Into<PathBuf>, period: Duration) -> JoinHandle<()> {
+        let path = path.into();
+        let reload = self.reload();
+        let mut text = fs::read_to_string(&path).ok();
+        let mut current = ServerConfig::load(&path).ok();
+
+        tokio::spawn(async move {
+            let mut interval = interval(period);
+
+            loop {
+                interval.tick().await;
+
+                let new_text = fs::read_to_string(&path).ok();
+                if new_text.is_none() || new_text == text {
+                    continue;
+                }
+                text = new_text;
+
+                match ServerConfig::load(&path) {
+                    Ok(config) => {
+                        if let Some(current) = &current {
+                            for change in current.restart_changes(&config) {
+                                log_info!("Configuration: {} needs a restart.", change);
+                            }
+                        }
+                        reload.apply(&config);
+                        log_info!("Configuration reloaded from {}.", path.display());
+                        current = Some(config);
+                    }
+                    Err(error) => {
+                        log_error!("Invalid configuration {}: {}.", path.display(), error)
+                    }
+                }
+            }
+        })
+    }
+
+    /// Starts the GameLoop and the network with the configuration.
+    pub async fn start_with_config(&mut self, config: &ServerConfig) -> Result<()> {
+        self.start_game_loop(config.game_loop_interval())
+            .start_network(&config.network.bind, config.security())
+            .await
+    }
+
+    fn reload(&self) -> Reload {
+        Reload {
+            configuration: self.configuration_handle(),
+            limits: self.dos_limits(),
+            control: self.game_loop_control(),
+        }
+    }
+}
+
+fn parse(text: &str) -> io::Result<Table> {
+    toml::from_str(text).map_err(|error| io::Error::new(ErrorKind::InvalidData, error))
+}
+
+/// Adds the variables `ROLLO_<SECTION>__<KEY>` to the table, the values are parsed as TOML
+/// or kept as strings.
+fn merge_env(table: &mut Table, vars: impl Iterator<Item = (String, String)>) -> io::Result<()> {
+    for (name, raw) in vars {
+        let path = match name.strip_prefix(ENV_PREFIX) {
+            Some(path) if path.contains("__") => path.to_lowercase(),
+            _ => continue,
+        };
+
+        let value = parse(&format!("value = {}", raw))
+            .ok()
+            .and_then(|mut value| value.remove("value"))
+            .unwrap_or(Value::String(raw));
+
+        let mut keys = path.split("__").collect::<Vec<_>>();
+        let last = keys.pop().unwrap();
+        let mut current = &mut *table;
+        for key in keys {
+            current = current
+                .entry(key.to_string())
+                .or_insert_with(|| Value::Table(Table::new()))
+                .as_table_mut()
+                .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, name.clone()))?;
+        }
+        current.insert(last.to_string(), value);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_toml() {
+        let config = ServerConfig::from_toml(
+            r#"
+            [network]
+            bind = "127.0.0.1:7000"
+            timeout = 5
+            tls = { certificate = "cert.pem", private_key = "key.pem" }
+
+            [framing]
+            flush = "delayed"
+            flush_delay = 5
+
+            [dos]
+            global = { amount = 100, size = 10240 }
+            packets = [{ cmd = 10, amount = 30, size = 1024, policy = "close" }]
+
+            [game_loop]
+            interval = 50
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.network.bind, "127.0.0.1:7000");
+        assert_eq!(config.network.timeout, 5);
+        assert!(config.network.no_delay);
+        assert!(matches!(config.security(), ListenerSecurity::Tls(_, _)));
+        assert_eq!(
+            config.configuration().flush_policy,
+            FlushPolicy::Delayed(Duration::from_millis(5))
+        );
+        assert_eq!(config.framing.fragment_size, 1024);
+        assert_eq!(
+            config.dos.packets,
+            [PacketLimit {
+                cmd: 10,
+                amount: 30,
+                size: 1024,
+                policy: DosPolicy::Close
+            }]
+        );
+        assert_eq!(config.game_loop_interval(), Duration::from_millis(50));
+
+        assert_eq!(
+            ServerConfig::from_toml("").unwrap(),
+            ServerConfig::default()
+        );
+        assert_eq!(
+            ServerConfig::from_toml("[network]\nport = 1")
+                .unwrap_err()
+                .kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_merge_env() {
+        let mut table = parse("[network]\nbind = \"127.0.0.1:7000\"\ntimeout = 5").unwrap();
+        let vars = vec![
+            ("ROLLO_NETWORK__TIMEOUT", "30"),
+            ("ROLLO_NETWORK__BIND", "0.0.0.0:7001"),
+            ("ROLLO_DOS__GLOBAL", "{ amount = 10, size = 100 }"),
+            ("ROLLO_GAME_LOOP__INTERVAL", "40"),
+            ("ROLLO_LOG", "debug"),
+            ("PATH", "/bin"),
+        ];
+        merge_env(
+            &mut table,
+            vars.into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string())),
+        )
+        .unwrap();
+
+        let config = ServerConfig::from_table(table).unwrap();
+        assert_eq!(config.network.timeout, 30);
+        assert_eq!(config.network.bind, "0.0.0.0:7001");
+        assert_eq!(
+            config.dos.global,
+            Some(GlobalLimit {
+                amount: 10,
+                size: 100
+            })
+        );
+        assert_eq!(config.game_loop.interval, 40);
+
+        let mut table = parse("[network]\nbind = \"127.0.0.1:7000\"").unwrap();
+        let vars = vec![("ROLLO_NETWORK__BIND__PORT".to_string(), "1".to_string())];
+        assert!(merge_env(&mut table, vars.into_iter()).is_err());
+    }
+
+    #[test]
+    fn test_restart_changes() {
+        let config = ServerConfig::default();
+        let mut other = config.clone();
+        other.network.timeout = 1;
+        other.dos.global = Some(GlobalLimit { amount: 1, size: 1 });
+        assert!(config.restart_changes(&other).is_empty());
+
+        other.network.bind = "127.0.0.1:1".to_string();
+        other.pools.packets = 1;
+        assert_eq!(config.restart_changes(&other), ["network.bind", "pools"]);
+    }
+}
@@ -14,4 +14,13 @@ pub enum Error {
     TlsAcceptTimeout,
     NoDelayError,
     TlsAccept,
+    RequestTimeout,
+    StreamSize,
+    Connect,
+    TlsConnect,
+    Bind,
+    TlsConfig,
+    ClusterAuth,
+    NodeUnavailable,
+    ReservedCmd,
 }
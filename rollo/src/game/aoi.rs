@@ -0,0 +1,502 @@
+use crate::server::{world_session::SocketTools, world_socket::ContainerBytes};
+use easy_pool::PoolObjectContainer;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+type Cell = (i32, i32);
+
+/// # Area of interest
+/// Grid of entities, an entity sees the entities of the cells around its own cell.
+/// ## Usage
+/// ```rust, no_run
+/// use rollo::game::{AoiEvent, AoiGrid};
+/// use rollo::packet::to_bytes;
+/// use rollo::Mutex;
+/// use std::sync::Arc;
+///
+/// // Returned by World::aoi_grid, the game loop removes the closed sessions before each update.
+/// let grid = Mutex::new(AoiGrid::new(50.0, 1));
+///
+/// // In World::update
+/// let mut grid = grid.lock();
+/// for event in grid.drain_events() {
+///     match event {
+///         AoiEvent::Enter { observer, entity } => {}
+///         AoiEvent::Leave { observer, entity } => {}
+///         AoiEvent::Move { observer, entity } => {}
+///     }
+/// }
+///
+/// // Send the same buffer to every session that sees the entity 1.
+/// grid.broadcast(1, &Arc::new(to_bytes(10, Some(&[1]))));
+/// ```
+#[derive(Debug)]
+pub struct AoiGrid {
+    cell_size: f32,
+    radius: i32,
+    entities: HashMap<u64, AoiEntity>,
+    cells: HashMap<Cell, HashSet<u64>>,
+    events: Vec<AoiEvent>,
+}
+
+#[derive(Debug)]
+struct AoiEntity {
+    x: f32,
+    y: f32,
+    cell: Cell,
+    observer: Option<SocketTools>,
+}
+
+/// Events between an observer (entity with a session) and an entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AoiEvent {
+    /// The entity is now visible by the observer.
+    Enter { observer: u64, entity: u64 },
+    /// The entity is no longer visible by the observer.
+    Leave { observer: u64, entity: u64 },
+    /// The entity moved and is still visible by the observer.
+    Move { observer: u64, entity: u64 },
+}
+
+impl AoiGrid {
+    /// ## Create the grid
+    /// The radius is the amount of cells visible around the cell of an entity.
+    /// ### Examples
+    /// ```rust, no_run
+    /// use rollo::game::AoiGrid;
+    ///
+    /// let grid = AoiGrid::new(50.0, 1);
+    /// ```
+    pub fn new(cell_size: f32, radius: u32) -> Self {
+        debug_assert!(cell_size > 0.0);
+
+        Self {
+            cell_size,
+            radius: radius.min(i32::MAX as u32) as i32,
+            entities: HashMap::new(),
+            cells: HashMap::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Adds an entity, with the session of the player if it observes the others.
+    ///
+    /// Returns false if the position is not finite (the entity is not added).
+    pub fn insert(&mut self, id: u64, x: f32, y: f32, observer: Option<SocketTools>) -> bool {
+        if !x.is_finite() || !y.is_finite() {
+            return false;
+        }
+
+        if self.entities.contains_key(&id) {
+            self.remove(id);
+        }
+
+        let cell = self.cell(x, y);
+        let is_observer = observer.is_some();
+
+        for other in self.neighbors(cell) {
+            if self.is_observer(other) {
+                self.events.push(AoiEvent::Enter {
+                    observer: other,
+                    entity: id,
+                });
+            }
+
+            if is_observer {
+                self.events.push(AoiEvent::Enter {
+                    observer: id,
+                    entity: other,
+                });
+            }
+        }
+
+        self.cells.entry(cell).or_default().insert(id);
+        self.entities.insert(
+            id,
+            AoiEntity {
+                x,
+                y,
+                cell,
+                observer,
+            },
+        );
+
+        true
+    }
+
+    /// Moves an entity, returns false if the entity is unknown or if the position is not finite.
+    pub fn update_position(&mut self, id: u64, x: f32, y: f32) -> bool {
+        if !x.is_finite() || !y.is_finite() {
+            return false;
+        }
+
+        let new_cell = self.cell(x, y);
+
+        let (old_cell, is_observer) = if let Some(entity) = self.entities.get_mut(&id) {
+            entity.x = x;
+            entity.y = y;
+            let old_cell = entity.cell;
+            entity.cell = new_cell;
+            (old_cell, entity.observer.is_some())
+        } else {
+            return false;
+        };
+
+        if let Some(entities) = self.cells.get_mut(&old_cell) {
+            entities.remove(&id);
+            if entities.is_empty() {
+                self.cells.remove(&old_cell);
+            }
+        }
+
+        let old_neighbors: HashSet<u64> = self.neighbors(old_cell).into_iter().collect();
+        let new_neighbors: HashSet<u64> = self.neighbors(new_cell).into_iter().collect();
+
+        self.cells.entry(new_cell).or_default().insert(id);
+
+        for other in old_neighbors.difference(&new_neighbors) {
+            self.push_pair(id, *other, is_observer, |observer, entity| {
+                AoiEvent::Leave { observer, entity }
+            });
+        }
+
+        for other in new_neighbors.difference(&old_neighbors) {
+            self.push_pair(id, *other, is_observer, |observer, entity| {
+                AoiEvent::Enter { observer, entity }
+            });
+        }
+
+        for other in old_neighbors.intersection(&new_neighbors) {
+            if self.is_observer(*other) {
+                self.events.push(AoiEvent::Move {
+                    observer: *other,
+                    entity: id,
+                });
+            }
+        }
+
+        true
+    }
+
+    /// Removes an entity, returns its session if it was an observer.
+    pub fn remove(&mut self, id: u64) -> Option<SocketTools> {
+        let entity = self.entities.remove(&id)?;
+
+        if let Some(entities) = self.cells.get_mut(&entity.cell) {
+            entities.remove(&id);
+            if entities.is_empty() {
+                self.cells.remove(&entity.cell);
+            }
+        }
+
+        let is_observer = entity.observer.is_some();
+        for other in self.neighbors(entity.cell) {
+            self.push_pair(id, other, is_observer, |observer, entity| AoiEvent::Leave {
+                observer,
+                entity,
+            });
+        }
+
+        entity.observer
+    }
+
+    /// Removes the observers whose session is closed, returns their ids.
+    ///
+    /// Called by the game loop before each update if the grid is returned by `World::aoi_grid`,
+    /// otherwise call it once per tick or `remove` the entity in `WorldSession::on_close`.
+    pub fn remove_closed(&mut self) -> Vec<u64> {
+        let closed = self
+            .entities
+            .iter()
+            .filter(|(_, entity)| entity.observer.as_ref().is_some_and(SocketTools::is_closed))
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+
+        closed.iter().for_each(|id| {
+            self.remove(*id);
+        });
+
+        closed
+    }
+
+    /// Returns the position of an entity.
+    pub fn position(&self, id: u64) -> Option<(f32, f32)> {
+        self.entities.get(&id).map(|entity| (entity.x, entity.y))
+    }
+
+    /// Returns the entities visible by an entity.
+    pub fn visible_entities(&self, id: u64) -> Vec<u64> {
+        self.entities
+            .get(&id)
+            .map(|entity| {
+                let mut entities = self.neighbors(entity.cell);
+                entities.retain(|other| *other != id);
+                entities
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the sessions to notify about an entity (the observers that see it).
+    pub fn visible_sessions(&self, id: u64) -> Vec<&SocketTools> {
+        self.visible_entities(id)
+            .into_iter()
+            .filter_map(|other| {
+                self.entities
+                    .get(&other)
+                    .and_then(|entity| entity.observer.as_ref())
+            })
+            .collect()
+    }
+
+    /// Sends the same buffer to the sessions that see an entity.
+    pub fn broadcast(&self, id: u64, bytes: &Arc<PoolObjectContainer<Vec<u8>>>) {
+        self.visible_sessions(id)
+            .into_iter()
+            .for_each(|socket_tools| {
+                socket_tools.send_data(ContainerBytes::Arc(Arc::clone(bytes)))
+            });
+    }
+
+    /// Returns the events since the last call.
+    pub fn drain_events(&mut self) -> std::vec::Drain<'_, AoiEvent> {
+        self.events.drain(..)
+    }
+
+    /// Returns the amount of entities.
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Returns true if there is no entity.
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    fn push_pair(
+        &mut self,
+        id: u64,
+        other: u64,
+        is_observer: bool,
+        event: impl Fn(u64, u64) -> AoiEvent,
+    ) {
+        if self.is_observer(other) {
+            self.events.push(event(other, id));
+        }
+
+        if is_observer {
+            self.events.push(event(id, other));
+        }
+    }
+
+    fn is_observer(&self, id: u64) -> bool {
+        self.entities
+            .get(&id)
+            .is_some_and(|entity| entity.observer.is_some())
+    }
+
+    fn cell(&self, x: f32, y: f32) -> Cell {
+        (
+            (x / self.cell_size).floor() as i32,
+            (y / self.cell_size).floor() as i32,
+        )
+    }
+
+    fn neighbors(&self, cell: Cell) -> Vec<u64> {
+        let mut entities = Vec::new();
+
+        // The positions far from the origin are in the cells at the bounds of the grid.
+        let (min_x, max_x) = (
+            cell.0.saturating_sub(self.radius),
+            cell.0.saturating_add(self.radius),
+        );
+        let (min_y, max_y) = (
+            cell.1.saturating_sub(self.radius),
+            cell.1.saturating_add(self.radius),
+        );
+
+        for cell_x in min_x..=max_x {
+            for cell_y in min_y..=max_y {
+                if let Some(ids) = self.cells.get(&(cell_x, cell_y)) {
+                    entities.extend(ids.iter().copied());
+                }
+            }
+        }
+
+        entities
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::to_bytes;
+    use crate::server::world_socket::WriterMessage;
+
+    fn events(grid: &mut AoiGrid) -> HashSet<AoiEvent> {
+        grid.drain_events().collect()
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut grid = AoiGrid::new(10.0, 1);
+        let (tools, _rx) = SocketTools::for_test(1);
+
+        grid.insert(1, 5.0, 5.0, Some(tools));
+        grid.insert(2, 15.0, 15.0, None);
+        grid.insert(3, 25.0, 5.0, None);
+
+        assert_eq!(
+            events(&mut grid),
+            HashSet::from([AoiEvent::Enter {
+                observer: 1,
+                entity: 2
+            }])
+        );
+        assert_eq!(grid.len(), 3);
+        assert_eq!(grid.visible_entities(2).len(), 2);
+        assert_eq!(grid.visible_entities(1), [2]);
+    }
+
+    #[test]
+    fn test_update_position() {
+        let mut grid = AoiGrid::new(10.0, 1);
+        let (first, _rx) = SocketTools::for_test(1);
+        let (second, _rx_second) = SocketTools::for_test(2);
+
+        grid.insert(1, 5.0, 5.0, Some(first));
+        grid.insert(2, 15.0, 5.0, Some(second));
+        grid.insert(3, 35.0, 5.0, None);
+        grid.drain_events();
+
+        // Same cells around.
+        assert!(grid.update_position(2, 16.0, 5.0));
+        assert_eq!(
+            events(&mut grid),
+            HashSet::from([AoiEvent::Move {
+                observer: 1,
+                entity: 2
+            }])
+        );
+
+        // Leaves 1, enters 3.
+        assert!(grid.update_position(2, 25.0, 5.0));
+        assert_eq!(
+            events(&mut grid),
+            HashSet::from([
+                AoiEvent::Leave {
+                    observer: 1,
+                    entity: 2
+                },
+                AoiEvent::Leave {
+                    observer: 2,
+                    entity: 1
+                },
+                AoiEvent::Enter {
+                    observer: 2,
+                    entity: 3
+                },
+            ])
+        );
+        assert_eq!(grid.position(2), Some((25.0, 5.0)));
+        assert!(!grid.update_position(10, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_remove_closed() {
+        let mut grid = AoiGrid::new(10.0, 1);
+        let (first, rx) = SocketTools::for_test(1);
+        let (second, _rx_second) = SocketTools::for_test(2);
+
+        grid.insert(1, 5.0, 5.0, Some(first));
+        grid.insert(2, 6.0, 6.0, Some(second));
+        grid.insert(3, 7.0, 7.0, None);
+        grid.drain_events();
+        assert!(grid.remove_closed().is_empty());
+
+        // The writer of the session 1 is gone (session closed).
+        drop(rx);
+
+        assert_eq!(grid.remove_closed(), [1]);
+        assert_eq!(
+            events(&mut grid),
+            HashSet::from([
+                AoiEvent::Leave {
+                    observer: 2,
+                    entity: 1
+                },
+                AoiEvent::Leave {
+                    observer: 1,
+                    entity: 2
+                },
+                AoiEvent::Leave {
+                    observer: 1,
+                    entity: 3
+                },
+            ])
+        );
+        assert_eq!(grid.len(), 2);
+        assert_eq!(grid.visible_entities(2), [3]);
+    }
+
+    #[test]
+    fn test_invalid_position() {
+        let mut grid = AoiGrid::new(10.0, 1);
+        let (first, _rx) = SocketTools::for_test(1);
+
+        assert!(!grid.insert(1, f32::NAN, 5.0, None));
+        assert!(grid.is_empty());
+
+        // The positions beyond the range of the cells are at the bounds of the grid.
+        assert!(grid.insert(1, f32::MAX, f32::MIN, Some(first)));
+        assert!(grid.insert(2, f32::MAX, f32::MIN, None));
+        assert_eq!(grid.visible_entities(1), [2]);
+
+        assert!(!grid.update_position(2, f32::INFINITY, 5.0));
+        assert!(!grid.update_position(2, 5.0, f32::NAN));
+        assert_eq!(grid.position(2), Some((f32::MAX, f32::MIN)));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut grid = AoiGrid::new(10.0, 1);
+        let (first, _rx) = SocketTools::for_test(1);
+
+        grid.insert(1, 5.0, 5.0, Some(first));
+        grid.insert(2, 6.0, 6.0, None);
+        grid.drain_events();
+
+        assert!(grid.remove(2).is_none());
+        assert_eq!(
+            events(&mut grid),
+            HashSet::from([AoiEvent::Leave {
+                observer: 1,
+                entity: 2
+            }])
+        );
+        assert!(grid.remove(1).is_some());
+        assert!(grid.is_empty());
+        assert!(grid.cells.is_empty());
+    }
+
+    #[test]
+    fn test_broadcast() {
+        let mut grid = AoiGrid::new(10.0, 1);
+        let (first, mut rx) = SocketTools::for_test(1);
+        let (second, mut rx_second) = SocketTools::for_test(2);
+
+        grid.insert(1, 5.0, 5.0, Some(first));
+        grid.insert(2, 50.0, 50.0, Some(second));
+        grid.insert(3, 6.0, 6.0, None);
+
+        assert_eq!(grid.visible_sessions(3).len(), 1);
+        assert_eq!(grid.visible_sessions(3)[0].id, 1);
+
+        grid.broadcast(3, &Arc::new(to_bytes(5, None)));
+
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(WriterMessage::Send(ContainerBytes::Arc(_), true))
+        ));
+        assert!(rx_second.try_recv().is_err());
+    }
+}
@@ -0,0 +1,157 @@
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+use spin_sleep::SpinSleeper;
+use std::{
+    fmt::Debug,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::sync::watch::{self, Sender};
+
+static ORIGIN: Lazy<Instant> = Lazy::new(Instant::now);
+
+/// # Clock
+/// Source of time of the `GameLoop`, the `GameTime` and the `Scheduler`.
+///
+/// `SystemClock` is the real clock, `ManualClock` is a virtual clock for the tests.
+#[async_trait]
+pub trait Clock: Send + Sync + Debug {
+    /// Time since UNIX_EPOCH.
+    fn now(&self) -> Duration;
+    /// Monotonic time, only the difference between two values is meaningful.
+    fn monotonic(&self) -> Duration;
+    /// Waits until `now` reaches the deadline (time since UNIX_EPOCH).
+    async fn sleep_until(&self, deadline: Duration);
+}
+
+/// The real clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap()
+    }
+
+    fn monotonic(&self) -> Duration {
+        ORIGIN.elapsed()
+    }
+
+    async fn sleep_until(&self, deadline: Duration) {
+        if let Some(duration) = deadline.checked_sub(self.now()) {
+            SpinSleeper::default().sleep(duration);
+        }
+    }
+}
+
+/// # Manual Clock
+/// A virtual clock, the time only moves with `advance`.
+/// ## Usage
+/// ```rust, no_run
+/// use rollo::game::{GameLoop, ManualClock};
+/// use std::{sync::Arc, time::Duration};
+///
+/// let clock = Arc::new(ManualClock::new());
+/// let game_loop = GameLoop::new(Duration::from_millis(50)).with_clock(clock.clone());
+///
+/// clock.advance(Duration::from_millis(50));
+/// ```
+#[derive(Debug)]
+pub struct ManualClock {
+    start: Duration,
+    elapsed: Sender<Duration>,
+}
+
+impl ManualClock {
+    /// Creates the clock at the current system time.
+    pub fn new() -> Self {
+        Self::starting_at(SystemClock.now())
+    }
+
+    /// Creates the clock at a time since UNIX_EPOCH.
+    pub fn starting_at(start: Duration) -> Self {
+        Self {
+            start,
+            elapsed: watch::channel(Duration::ZERO).0,
+        }
+    }
+
+    /// Moves the time forward and wakes up the sleepers.
+    pub fn advance(&self, duration: Duration) {
+        self.elapsed.send_modify(|elapsed| *elapsed += duration);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.start + self.monotonic()
+    }
+
+    fn monotonic(&self) -> Duration {
+        *self.elapsed.borrow()
+    }
+
+    async fn sleep_until(&self, deadline: Duration) {
+        let target = deadline.saturating_sub(self.start);
+        let mut elapsed = self.elapsed.subscribe();
+
+        while *elapsed.borrow_and_update() < target {
+            // The sender is owned by the clock.
+            if elapsed.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_advance() {
+        let clock = ManualClock::starting_at(Duration::from_secs(100));
+        assert_eq!(clock.now(), Duration::from_secs(100));
+        assert_eq!(clock.monotonic(), Duration::ZERO);
+
+        clock.advance(Duration::from_millis(50));
+        assert_eq!(clock.now(), Duration::from_millis(100_050));
+        assert_eq!(clock.monotonic(), Duration::from_millis(50));
+    }
+
+    #[tokio::test]
+    async fn test_sleep() {
+        let clock = Arc::new(ManualClock::new());
+
+        let clone = Arc::clone(&clock);
+        let deadline = clock.now() + Duration::from_millis(100);
+        let sleeper = tokio::spawn(async move { clone.sleep_until(deadline).await });
+
+        clock.advance(Duration::from_millis(60));
+        tokio::task::yield_now().await;
+        assert!(!sleeper.is_finished());
+
+        clock.advance(Duration::from_millis(40));
+        tokio::time::timeout(Duration::from_secs(1), sleeper)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_system_clock() {
+        let clock = SystemClock;
+        let monotonic = clock.monotonic();
+        std::thread::sleep(Duration::from_millis(2));
+        assert!(clock.monotonic() > monotonic);
+        assert!(clock.now() > Duration::ZERO);
+    }
+}
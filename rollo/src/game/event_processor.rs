@@ -1,94 +1,202 @@
 use indexmap::IndexMap;
-use std::{collections::VecDeque, time::Duration};
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryFrom,
+    marker::PhantomData,
+    time::Duration,
+};
 
 /// # Event Processor
 /// ## Usage
 /// ```rust, no_run
-/// use rollo::game::{EventProcessor, Event};
+/// use rollo::game::{EventProcessor, Event, EventAction};
 /// use std::time::Duration;
 ///
 /// let mut event_processor = EventProcessor::<MyEvent>::new(1000000);
 /// let event = MyEvent;
 /// // The duration is the delay before the execution.
-/// event_processor.add_event(event, Duration::from_secs(5));
-/// event_processor.update(1005000);
+/// let id = event_processor.add_event(event, Duration::from_secs(5));
+/// event_processor.update(1005000, &mut ());
 ///
 /// struct MyEvent;
 ///
 /// impl Event for MyEvent {
-///     fn on_execute(&self, _diff: i64){}
+///     fn on_execute(&mut self, _ctx: &mut (), _diff: i64) -> EventAction<Self> {
+///         EventAction::Next
+///     }
 /// }
 /// ```
 #[derive(Default, Debug)]
-pub struct EventProcessor<T>
+pub struct EventProcessor<T, Ctx = ()>
 where
-    T: Event,
+    T: Event<Ctx>,
 {
-    events: IndexMap<i64, VecDeque<(i64, T)>>,
+    events: IndexMap<i64, VecDeque<ScheduledEvent<T>>>,
+    ids: HashMap<EventId, i64>,
+    next_id: u64,
     m_time: i64,
+    _ctx: PhantomData<fn(&mut Ctx)>,
 }
 
-impl<T> EventProcessor<T>
+/// Identifier of an event added to an `EventProcessor`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EventId(pub(super) u64);
+
+/// How a recurring event is rescheduled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recurrence {
+    /// The next execution is one period after the target time of the previous one (no drift).
+    ///
+    /// A late execution is caught up at the next updates.
+    FixedRate(Duration),
+    /// The next execution is one period after the previous execution.
+    FixedDelay(Duration),
+}
+
+/// What to do after the execution of an event.
+#[derive(Debug)]
+pub enum EventAction<T> {
+    /// Deleted, or executed again if the event is recurring (or not deletable).
+    Next,
+    /// Executed again after the delay.
+    Reschedule(Duration),
+    /// Adds the new event with its delay, then the event continues as with `Next`.
+    Chain(T, Duration),
+    /// Deleted, even if the event is recurring.
+    Finish,
+}
+
+#[derive(Debug)]
+pub(super) struct ScheduledEvent<T> {
+    pub(super) id: EventId,
+    pub(super) delay: i64,
+    pub(super) recurrence: Option<Recurrence>,
+    pub(super) remaining: Option<u32>,
+    pub(super) event: T,
+}
+
+/// Converts a delay to milliseconds, the delays too big for an `i64` are saturated.
+pub(super) fn millis(duration: Duration) -> i64 {
+    i64::try_from(duration.as_millis()).unwrap_or(i64::MAX)
+}
+
+impl<T> ScheduledEvent<T> {
+    /// Executes (or aborts) the event planned at the time, returns the time of the next execution
+    /// and the chained event.
+    pub(super) fn run<Ctx>(
+        &mut self,
+        ctx: &mut Ctx,
+        time: i64,
+        m_time: i64,
+    ) -> (Option<i64>, Option<(T, Duration)>)
+    where
+        T: Event<Ctx>,
+    {
+        if self.event.to_abort() {
+            self.event.on_abort();
+            return (None, None);
+        }
+
+        let diff = (m_time - time).saturating_add(self.delay);
+        let (action, chained) = match self.event.on_execute(ctx, diff) {
+            EventAction::Chain(event, delay) => (EventAction::Next, Some((event, delay))),
+            action => (action, None),
+        };
+
+        let new_time = match (action, self.recurrence) {
+            (EventAction::Finish, _) => None,
+            (EventAction::Reschedule(delay), _) => Some(m_time.saturating_add(millis(delay))),
+            (_, Some(recurrence)) => {
+                self.remaining = self.remaining.map(|r| r.saturating_sub(1));
+
+                match (self.remaining, recurrence) {
+                    (Some(0), _) => None,
+                    (_, Recurrence::FixedRate(period)) => Some(time.saturating_add(millis(period))),
+                    (_, Recurrence::FixedDelay(period)) => {
+                        Some(m_time.saturating_add(millis(period)))
+                    }
+                }
+            }
+            (_, None) if !self.event.is_deletable() => Some(m_time.saturating_add(self.delay)),
+            (_, None) => None,
+        };
+
+        (new_time, chained)
+    }
+}
+
+impl<T, Ctx> EventProcessor<T, Ctx>
 where
-    T: Event,
+    T: Event<Ctx>,
 {
     /// ## Create an event processor
     /// Time (current time in milliseconds)
     /// ### Examples
     /// ```rust, no_run
-    /// use rollo::game::{EventProcessor, Event};
+    /// use rollo::game::{EventProcessor, Event, EventAction};
     ///
     /// let event_processor = EventProcessor::<MyEvent>::new(1000000);
     ///
     /// struct MyEvent;
     ///
     /// impl Event for MyEvent {
-    ///     fn on_execute(&self, _diff: i64){}
+    ///     fn on_execute(&mut self, _ctx: &mut (), _diff: i64) -> EventAction<Self> {
+    ///         EventAction::Next
+    ///     }
     /// }
     /// ```
     pub fn new(time: i64) -> Self {
         Self {
             m_time: time,
             events: IndexMap::new(),
+            ids: HashMap::new(),
+            next_id: 0,
+            _ctx: PhantomData,
         }
     }
 
     /// ## Update events
-    /// Time (current time in milliseconds)
+    /// Time (current time in milliseconds), the context is given to the executed events.
     /// ### Examples
     /// ```rust, no_run
-    /// use rollo::game::{EventProcessor, Event};
+    /// use rollo::game::{EventProcessor, Event, EventAction};
     ///
     /// let mut event_processor = EventProcessor::<MyEvent>::new(1000000);
     /// // 1000000 is the time.
-    /// event_processor.update(1000000);
+    /// event_processor.update(1000000, &mut ());
     ///
     /// struct MyEvent;
     ///
     /// impl Event for MyEvent {
-    ///     fn on_execute(&self, _diff: i64){}
+    ///     fn on_execute(&mut self, _ctx: &mut (), _diff: i64) -> EventAction<Self> {
+    ///         EventAction::Next
+    ///     }
     /// }
     /// ```
-    pub fn update(&mut self, time: i64) {
+    pub fn update(&mut self, time: i64, ctx: &mut Ctx) {
         self.m_time = time;
         let m_time = self.m_time;
 
         let mut keys_to_remove = IndexMap::new();
+        let mut chained = Vec::new();
+        let ids = &mut self.ids;
 
         self.events.retain(|time, events| {
             if m_time >= *time {
-                while let Some(event) = events.pop_front() {
-                    if event.1.to_abort() {
-                        event.1.on_abort();
-                    } else {
-                        let diff = (m_time - time) + event.0;
-                        event.1.on_execute(diff);
+                while let Some(mut scheduled) = events.pop_front() {
+                    let (new_time, event) = scheduled.run(ctx, *time, m_time);
+                    chained.extend(event);
 
-                        if !event.1.is_deletable() {
+                    match new_time {
+                        Some(new_time) => {
+                            ids.insert(scheduled.id, new_time);
                             keys_to_remove
-                                .entry(*time)
-                                .or_insert(VecDeque::with_capacity(1))
-                                .push_back((event.0, event.1));
+                                .entry(new_time)
+                                .or_insert_with(|| VecDeque::with_capacity(1))
+                                .push_back(scheduled);
+                        }
+                        None => {
+                            ids.remove(&scheduled.id);
                         }
                     }
                 }
@@ -99,51 +207,175 @@ where
             }
         });
 
-        keys_to_remove.into_iter().for_each(|(_, events)| {
-            events.into_iter().for_each(|(t, event)| {
-                debug_assert!(!event.is_deletable());
-                let new_time = m_time + t;
-                self.events
-                    .entry(new_time)
-                    .or_default()
-                    .push_back((t, event))
-            });
+        keys_to_remove.into_iter().for_each(|(new_time, events)| {
+            self.events.entry(new_time).or_default().extend(events);
+        });
+
+        chained.into_iter().for_each(|(event, delay)| {
+            self.add_event(event, delay);
         });
     }
 
     /// ## Add an event
     /// ### Examples
     /// ```rust, no_run
-    /// use rollo::game::{EventProcessor, Event};
+    /// use rollo::game::{EventProcessor, Event, EventAction};
     /// use std::time::Duration;
     ///
     /// let mut event_processor = EventProcessor::<MyEvent>::new(1000000);
     /// let event = MyEvent;
     /// // The duration is the delay before the execution.
-    /// event_processor.add_event(event, Duration::from_secs(5));
+    /// let id = event_processor.add_event(event, Duration::from_secs(5));
+    /// event_processor.cancel_event(id, true);
     ///
     /// struct MyEvent;
     ///
     /// impl Event for MyEvent {
-    ///     fn on_execute(&self, _diff: i64){}
+    ///     fn on_execute(&mut self, _ctx: &mut (), _diff: i64) -> EventAction<Self> {
+    ///         EventAction::Next
+    ///     }
     /// }
     /// ```
-    pub fn add_event(&mut self, event: T, add_time: Duration) {
-        let target_time = self.calcul_target_time(add_time.as_millis() as i64);
-        if let Some(events) = self.events.get_mut(&target_time) {
-            events.push_back((add_time.as_millis() as i64, event));
-        } else {
-            self.events.insert(
-                target_time,
-                VecDeque::from([(add_time.as_millis() as i64, event)]),
-            );
+    pub fn add_event(&mut self, event: T, add_time: Duration) -> EventId {
+        self.schedule(event, add_time, None, None)
+    }
+
+    /// ## Add a recurring event
+    /// The event is executed after the delay, then every period until it is cancelled
+    /// or executed `repeat` times (None for no limit).
+    ///
+    /// The recurring events don't use `Event::is_deletable`.
+    /// ### Examples
+    /// ```rust, no_run
+    /// use rollo::game::{EventProcessor, Event, EventAction, Recurrence};
+    /// use std::time::Duration;
+    ///
+    /// let mut event_processor = EventProcessor::<MyEvent>::new(1000000);
+    /// // 3 executions, every second.
+    /// event_processor.add_recurring_event(
+    ///     MyEvent,
+    ///     Duration::from_secs(1),
+    ///     Recurrence::FixedRate(Duration::from_secs(1)),
+    ///     Some(3),
+    /// );
+    ///
+    /// struct MyEvent;
+    ///
+    /// impl Event for MyEvent {
+    ///     fn on_execute(&mut self, _ctx: &mut (), _diff: i64) -> EventAction<Self> {
+    ///         EventAction::Next
+    ///     }
+    /// }
+    /// ```
+    pub fn add_recurring_event(
+        &mut self,
+        event: T,
+        delay: Duration,
+        recurrence: Recurrence,
+        repeat: Option<u32>,
+    ) -> EventId {
+        self.schedule(event, delay, Some(recurrence), repeat)
+    }
+
+    /// ## Cancel an event
+    /// Returns false if the event is unknown (executed or cancelled).
+    /// ### Examples
+    /// ```rust, no_run
+    /// use rollo::game::{EventProcessor, Event, EventAction};
+    /// use std::time::Duration;
+    ///
+    /// let mut event_processor = EventProcessor::<MyEvent>::new(1000000);
+    /// let id = event_processor.add_event(MyEvent, Duration::from_secs(5));
+    /// // Cancel the event without on_abort().
+    /// assert!(event_processor.cancel_event(id, false));
+    ///
+    /// struct MyEvent;
+    ///
+    /// impl Event for MyEvent {
+    ///     fn on_execute(&mut self, _ctx: &mut (), _diff: i64) -> EventAction<Self> {
+    ///         EventAction::Next
+    ///     }
+    /// }
+    /// ```
+    pub fn cancel_event(&mut self, id: EventId, abort: bool) -> bool {
+        match self.take(id) {
+            Some(mut scheduled) => {
+                if abort {
+                    scheduled.event.on_abort();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// ## Reschedule an event
+    /// The event is executed after the new delay, returns false if the event is unknown.
+    /// ### Examples
+    /// ```rust, no_run
+    /// use rollo::game::{EventProcessor, Event, EventAction};
+    /// use std::time::Duration;
+    ///
+    /// let mut event_processor = EventProcessor::<MyEvent>::new(1000000);
+    /// let id = event_processor.add_event(MyEvent, Duration::from_secs(5));
+    /// event_processor.reschedule_event(id, Duration::from_secs(10));
+    ///
+    /// struct MyEvent;
+    ///
+    /// impl Event for MyEvent {
+    ///     fn on_execute(&mut self, _ctx: &mut (), _diff: i64) -> EventAction<Self> {
+    ///         EventAction::Next
+    ///     }
+    /// }
+    /// ```
+    pub fn reschedule_event(&mut self, id: EventId, delay: Duration) -> bool {
+        match self.take(id) {
+            Some(mut scheduled) => {
+                let delay = millis(delay);
+                if scheduled.recurrence.is_none() {
+                    scheduled.delay = delay;
+                }
+                self.insert(self.calcul_target_time(delay), scheduled);
+                true
+            }
+            None => false,
         }
     }
 
+    /// ## Remaining time
+    /// Returns the time before the next execution of an event, None if the event is unknown.
+    /// ### Examples
+    /// ```rust, no_run
+    /// use rollo::game::{EventProcessor, Event, EventAction};
+    /// use std::time::Duration;
+    ///
+    /// let mut event_processor = EventProcessor::<MyEvent>::new(1000000);
+    /// let id = event_processor.add_event(MyEvent, Duration::from_secs(5));
+    /// assert_eq!(event_processor.remaining_time(id), Some(Duration::from_secs(5)));
+    ///
+    /// struct MyEvent;
+    ///
+    /// impl Event for MyEvent {
+    ///     fn on_execute(&mut self, _ctx: &mut (), _diff: i64) -> EventAction<Self> {
+    ///         EventAction::Next
+    ///     }
+    /// }
+    /// ```
+    pub fn remaining_time(&self, id: EventId) -> Option<Duration> {
+        self.ids
+            .get(&id)
+            .map(|time| Duration::from_millis((time - self.m_time).max(0) as u64))
+    }
+
+    /// Returns true if the event is waiting for its execution.
+    pub fn contains_event(&self, id: EventId) -> bool {
+        self.ids.contains_key(&id)
+    }
+
     /// ## Remove Events
     /// ### Examples
     /// ```rust, no_run
-    /// use rollo::game::{EventProcessor, Event};
+    /// use rollo::game::{EventProcessor, Event, EventAction};
     ///
     /// let mut event_processor = EventProcessor::<MyEvent>::new(1000000);
     /// // Remove all events and abort them (on_abort()).
@@ -152,27 +384,78 @@ where
     /// struct MyEvent;
     ///
     /// impl Event for MyEvent {
-    ///     fn on_execute(&self, _diff: i64){}
-    ///     fn on_abort(&self) {}
+    ///     fn on_execute(&mut self, _ctx: &mut (), _diff: i64) -> EventAction<Self> {
+    ///         EventAction::Next
+    ///     }
+    ///     fn on_abort(&mut self) {}
     /// }
     /// ```
     pub fn remove_events(&mut self, abort: bool) {
         if abort {
-            self.events
-                .iter()
-                .for_each(|events| events.1.iter().for_each(|event| event.1.on_abort()));
+            self.events.values_mut().for_each(|events| {
+                events
+                    .iter_mut()
+                    .for_each(|scheduled| scheduled.event.on_abort())
+            });
         }
 
         self.events.clear();
+        self.ids.clear();
+    }
+
+    fn schedule(
+        &mut self,
+        event: T,
+        add_time: Duration,
+        recurrence: Option<Recurrence>,
+        remaining: Option<u32>,
+    ) -> EventId {
+        let id = EventId(self.next_id);
+        self.next_id += 1;
+
+        let delay = millis(add_time);
+        let scheduled = ScheduledEvent {
+            id,
+            delay,
+            recurrence,
+            remaining,
+            event,
+        };
+        self.insert(self.calcul_target_time(delay), scheduled);
+
+        id
+    }
+
+    fn insert(&mut self, target_time: i64, scheduled: ScheduledEvent<T>) {
+        self.ids.insert(scheduled.id, target_time);
+
+        if let Some(events) = self.events.get_mut(&target_time) {
+            events.push_back(scheduled);
+        } else {
+            self.events.insert(target_time, VecDeque::from([scheduled]));
+        }
+    }
+
+    fn take(&mut self, id: EventId) -> Option<ScheduledEvent<T>> {
+        let time = self.ids.remove(&id)?;
+        let events = self.events.get_mut(&time)?;
+        let index = events.iter().position(|scheduled| scheduled.id == id)?;
+        let scheduled = events.remove(index);
+
+        if events.is_empty() {
+            self.events.shift_remove(&time);
+        }
+
+        scheduled
     }
 
     fn calcul_target_time(&self, add_time: i64) -> i64 {
-        self.m_time + add_time
+        self.m_time.saturating_add(add_time)
     }
 
     /// ### Examples
     /// ```rust, no_run
-    /// use rollo::game::{EventProcessor, Event};
+    /// use rollo::game::{EventProcessor, Event, EventAction};
     ///
     /// let event_processor = EventProcessor::<MyEvent>::new(1000000);
     /// assert!(event_processor.is_empty());
@@ -180,7 +463,9 @@ where
     /// struct MyEvent;
     ///
     /// impl Event for MyEvent {
-    ///     fn on_execute(&self, _diff: i64){}
+    ///     fn on_execute(&mut self, _ctx: &mut (), _diff: i64) -> EventAction<Self> {
+    ///         EventAction::Next
+    ///     }
     /// }
     /// ```
     pub fn is_empty(&self) -> bool {
@@ -189,9 +474,9 @@ where
 }
 
 /// Events for an Event.
-pub trait Event {
-    /// Execute an event.
-    fn on_execute(&self, _diff: i64);
+pub trait Event<Ctx = ()>: Sized {
+    /// Execute an event with the context given to `EventProcessor::update`.
+    fn on_execute(&mut self, ctx: &mut Ctx, diff: i64) -> EventAction<Self>;
 
     /// Is the event permanent?
     fn is_deletable(&self) -> bool {
@@ -204,7 +489,7 @@ pub trait Event {
     }
 
     /// Event aborted
-    fn on_abort(&self) {}
+    fn on_abort(&mut self) {}
 }
 
 #[cfg(test)]
@@ -230,28 +515,28 @@ mod tests {
             assert_eq!(event_processor.events[i as usize].len(), 1);
         });
 
-        event_processor.update(10);
+        event_processor.update(10, &mut ());
 
         assert_eq!(event_processor.events.len(), 4);
         (0..4).into_iter().for_each(|i| {
             assert_eq!(event_processor.events[i as usize].len(), 1);
         });
 
-        event_processor.update(2660);
+        event_processor.update(2660, &mut ());
 
         assert_eq!(event_processor.events.len(), 3);
         (0..3).into_iter().for_each(|i| {
             assert_eq!(event_processor.events[i as usize].len(), 1);
         });
 
-        event_processor.update(3650);
+        event_processor.update(3650, &mut ());
 
         assert_eq!(event_processor.events.len(), 2);
         (0..2).into_iter().for_each(|i| {
             assert_eq!(event_processor.events[i as usize].len(), 1);
         });
 
-        event_processor.update(365000);
+        event_processor.update(365000, &mut ());
 
         assert_eq!(event_processor.events.len(), 0);
     }
@@ -308,24 +593,24 @@ mod tests {
         event_processor.add_event(second_event.clone(), Duration::from_millis(2500));
         event_processor.add_event(second_event.clone(), Duration::from_secs(3));
 
-        event_processor.update(1);
+        event_processor.update(1, &mut ());
 
         assert_eq!(event.data.life.load(Ordering::Acquire), 0);
         assert_eq!(second_event.data.life.load(Ordering::Acquire), 0);
         assert_eq!(event_processor.events.get_index(0).unwrap().1.len(), 3);
         assert_eq!(event_processor.events.get_index(1).unwrap().1.len(), 1);
 
-        event_processor.update(2600);
+        event_processor.update(2600, &mut ());
 
         assert_eq!(event_processor.events.get_index(0).unwrap().1.len(), 1);
         assert_eq!(event_processor.events.len(), 1);
 
-        event_processor.update(2);
+        event_processor.update(2, &mut ());
 
         assert_eq!(event.data.life.load(Ordering::Acquire), 20);
         assert_eq!(second_event.data.life.load(Ordering::Acquire), 10);
 
-        event_processor.update(3600);
+        event_processor.update(3600, &mut ());
 
         assert_eq!(event_processor.events.len(), 0);
         assert_eq!(20, event.data.life.load(Ordering::Acquire));
@@ -348,26 +633,26 @@ mod tests {
         event_processor.add_event(event.clone(), Duration::from_millis(2500));
         assert_eq!(event_processor.events.first().unwrap().1.len(), 2);
 
-        event_processor.update(10);
+        event_processor.update(10, &mut ());
         assert_eq!(event.data.life.load(Ordering::Acquire), 0);
 
         assert_eq!(event_processor.events.len(), 1);
         assert_eq!(event_processor.events.first().unwrap().1.len(), 2);
 
-        event_processor.update(2600);
+        event_processor.update(2600, &mut ());
 
         assert_eq!(event.data.life.load(Ordering::Acquire), 20);
         assert_eq!(event_processor.events.len(), 1);
         assert_eq!(event_processor.events.first().unwrap().1.len(), 2);
 
-        event_processor.update(5500);
+        event_processor.update(5500, &mut ());
 
         assert_eq!(event.data.life.load(Ordering::Acquire), 40);
         assert_eq!(event_processor.events.len(), 1);
         assert_eq!(event_processor.events.first().unwrap().1.len(), 2);
 
         event.data.is_deletable.store(true, Ordering::Release);
-        event_processor.update(8500);
+        event_processor.update(8500, &mut ());
 
         assert_eq!(event.data.life.load(Ordering::Acquire), 60);
         assert_eq!(event_processor.events.len(), 0);
@@ -398,7 +683,7 @@ mod tests {
 
         assert_eq!(event_processor.events.len(), 2);
 
-        event_processor.update(2600);
+        event_processor.update(2600, &mut ());
 
         assert_eq!(event.data.life.load(Ordering::Acquire), 5);
         assert_eq!(event_processor.events.len(), 1);
@@ -430,17 +715,17 @@ mod tests {
         event_processor.add_event(second_event.clone(), Duration::from_millis(2500));
         event_processor.add_event(second_event.clone(), Duration::from_secs(3));
 
-        event_processor.update(20);
+        event_processor.update(20, &mut ());
 
         assert_eq!(event_processor.events.len(), 2);
         assert_eq!(event_processor.events.get(&2500).unwrap().len(), 3);
 
-        event_processor.update(2600);
+        event_processor.update(2600, &mut ());
 
         assert_eq!(event_processor.events.len(), 1);
         assert_eq!(event_processor.events.get(&3000).unwrap().len(), 1);
 
-        event_processor.update(3100);
+        event_processor.update(3100, &mut ());
         assert_eq!(event_processor.events.len(), 0);
     }
 
@@ -455,15 +740,200 @@ mod tests {
         let mut event_processor = EventProcessor::<MyEvent>::new(1000000);
         let event = MyEvent;
         event_processor.add_event(event, Duration::from_secs(5));
-        event_processor.update(1005000);
+        event_processor.update(1005000, &mut ());
+        assert!(event_processor.is_empty());
+    }
+
+    #[test]
+    fn test_cancel_event() {
+        let mut event_processor = EventProcessor::new(0);
+        let event = new();
+
+        let id = event_processor.add_event(event.clone(), Duration::from_millis(2500));
+        let second_id = event_processor.add_event(event.clone(), Duration::from_millis(2500));
+        assert_ne!(id, second_id);
+
+        assert!(event_processor.cancel_event(id, true));
+        assert!(!event_processor.cancel_event(id, true));
+        assert_eq!(event.data.life.load(Ordering::Acquire), 5);
+        assert!(!event_processor.contains_event(id));
+
+        assert!(event_processor.cancel_event(second_id, false));
+        assert!(event_processor.is_empty());
+
+        event_processor.update(3000, &mut ());
+        assert_eq!(event.data.life.load(Ordering::Acquire), 5);
+    }
+
+    #[test]
+    fn test_reschedule_event() {
+        let mut event_processor = EventProcessor::new(0);
+        let event = new();
+
+        let id = event_processor.add_event(event.clone(), Duration::from_millis(2500));
+        event_processor.update(1000, &mut ());
+        assert_eq!(
+            event_processor.remaining_time(id),
+            Some(Duration::from_millis(1500))
+        );
+
+        assert!(event_processor.reschedule_event(id, Duration::from_millis(4000)));
+        assert_eq!(
+            event_processor.remaining_time(id),
+            Some(Duration::from_millis(4000))
+        );
+
+        event_processor.update(3000, &mut ());
+        assert_eq!(event.data.life.load(Ordering::Acquire), 0);
+
+        event_processor.update(5000, &mut ());
+        assert_eq!(event.data.life.load(Ordering::Acquire), 10);
+        assert_eq!(event_processor.remaining_time(id), None);
+        assert!(!event_processor.reschedule_event(id, Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn test_fixed_rate() {
+        let mut event_processor = EventProcessor::new(0);
+        let event = new();
+
+        let id = event_processor.add_recurring_event(
+            event.clone(),
+            Duration::from_millis(100),
+            Recurrence::FixedRate(Duration::from_millis(100)),
+            None,
+        );
+
+        event_processor.update(130, &mut ());
+        assert_eq!(event.data.life.load(Ordering::Acquire), 10);
+        // No drift: the next execution is at 200.
+        assert_eq!(
+            event_processor.remaining_time(id),
+            Some(Duration::from_millis(70))
+        );
+
+        event_processor.update(450, &mut ());
+        assert_eq!(event.data.life.load(Ordering::Acquire), 20);
+        // Late, caught up at the next update.
+        assert_eq!(event_processor.remaining_time(id), Some(Duration::ZERO));
+        event_processor.update(450, &mut ());
+        assert_eq!(event.data.life.load(Ordering::Acquire), 30);
+    }
+
+    #[test]
+    fn test_fixed_delay() {
+        let mut event_processor = EventProcessor::new(0);
+        let event = new();
+
+        let id = event_processor.add_recurring_event(
+            event.clone(),
+            Duration::from_millis(100),
+            Recurrence::FixedDelay(Duration::from_millis(100)),
+            Some(2),
+        );
+
+        event_processor.update(130, &mut ());
+        assert_eq!(
+            event_processor.remaining_time(id),
+            Some(Duration::from_millis(100))
+        );
+
+        event_processor.update(230, &mut ());
+        assert_eq!(event.data.life.load(Ordering::Acquire), 20);
+        assert!(!event_processor.contains_event(id));
+        assert!(event_processor.is_empty());
+    }
+
+    #[derive(Default)]
+    struct Context {
+        log: Vec<&'static str>,
+    }
+
+    enum ContextEvent {
+        Spawn(u32),
+        Chain,
+        Finish,
+    }
+
+    impl Event<Context> for ContextEvent {
+        fn on_execute(&mut self, ctx: &mut Context, _diff: i64) -> EventAction<Self> {
+            match self {
+                ContextEvent::Spawn(count) => {
+                    ctx.log.push("spawn");
+                    *count -= 1;
+                    if *count == 0 {
+                        EventAction::Finish
+                    } else {
+                        EventAction::Reschedule(Duration::from_millis(50))
+                    }
+                }
+                ContextEvent::Chain => {
+                    ctx.log.push("chain");
+                    EventAction::Chain(ContextEvent::Finish, Duration::from_millis(10))
+                }
+                ContextEvent::Finish => {
+                    ctx.log.push("finish");
+                    EventAction::Finish
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_context() {
+        let mut event_processor = EventProcessor::<ContextEvent, Context>::new(0);
+        let mut ctx = Context::default();
+
+        let id = event_processor.add_event(ContextEvent::Spawn(2), Duration::from_millis(100));
+        event_processor.update(100, &mut ctx);
+        assert_eq!(ctx.log, ["spawn"]);
+        assert_eq!(
+            event_processor.remaining_time(id),
+            Some(Duration::from_millis(50))
+        );
+
+        event_processor.update(150, &mut ctx);
+        assert_eq!(ctx.log, ["spawn", "spawn"]);
+        assert!(event_processor.is_empty());
+    }
+
+    #[test]
+    fn test_chain() {
+        let mut event_processor = EventProcessor::<ContextEvent, Context>::new(0);
+        let mut ctx = Context::default();
+
+        event_processor.add_event(ContextEvent::Chain, Duration::from_millis(100));
+        event_processor.update(100, &mut ctx);
+        assert_eq!(ctx.log, ["chain"]);
+        assert!(!event_processor.is_empty());
+
+        event_processor.update(110, &mut ctx);
+        assert_eq!(ctx.log, ["chain", "finish"]);
+        assert!(event_processor.is_empty());
+    }
+
+    #[test]
+    fn test_finish_recurring() {
+        let mut event_processor = EventProcessor::<ContextEvent, Context>::new(0);
+        let mut ctx = Context::default();
+
+        event_processor.add_recurring_event(
+            ContextEvent::Finish,
+            Duration::from_millis(100),
+            Recurrence::FixedRate(Duration::from_millis(100)),
+            None,
+        );
+        event_processor.update(100, &mut ctx);
         assert!(event_processor.is_empty());
     }
 
     struct MyEvent;
 
     impl Event for MyEvent {
-        fn on_execute(&self, _diff: i64) {}
-        fn on_abort(&self) {}
+        fn on_execute(&mut self, _ctx: &mut (), _diff: i64) -> EventAction<Self> {
+            EventAction::Next
+        }
+        fn on_abort(&mut self) {}
     }
 
     struct GameData {
@@ -492,11 +962,12 @@ mod tests {
             self.data.to_abort.load(Ordering::Acquire)
         }
 
-        fn on_execute(&self, _diff: i64) {
+        fn on_execute(&mut self, _ctx: &mut (), _diff: i64) -> EventAction<Self> {
             self.data.life.fetch_add(10, Ordering::SeqCst);
+            EventAction::Next
         }
 
-        fn on_abort(&self) {
+        fn on_abort(&mut self) {
             self.data.life.store(5, Ordering::Release);
         }
 
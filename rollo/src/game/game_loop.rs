@@ -1,15 +1,154 @@
-use super::GameTime;
+use super::{Clock, GameTime, SystemClock};
 use crate::server::world::World;
 use crossbeam::atomic::AtomicCell;
-use spin_sleep::SpinSleeper;
-use std::time::Duration;
-use tokio::task::yield_now;
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{sync::watch::Sender, task::yield_now};
 
 /// Main Loop with an interval
 #[derive(Debug, Clone)]
 pub struct GameLoop {
     interval: i64,
     game_time: GameTime,
+    ticks: Option<Arc<Sender<u64>>>,
+    clock: Arc<dyn Clock>,
+    control: GameLoopControl,
+    // Difference between the time of the world and the real time (ms).
+    offset: i64,
+    remainder: f64,
+}
+
+/// # Game Loop Control
+/// Pauses, steps or scales the time of the world at runtime.
+///
+/// The `diff` and the `GameTime` given to `World::update` are scaled, the network keeps running
+/// at normal speed. The scheduler runs on the time of the world: its delays are scaled and
+/// paused with the world.
+/// ## Usage
+/// ```rust, no_run
+/// use rollo::game::GameLoop;
+/// use std::time::Duration;
+///
+/// let game_loop = GameLoop::new(Duration::from_millis(25));
+/// let control = game_loop.control();
+///
+/// control.set_speed(0.25);
+/// control.pause();
+/// control.step();
+/// control.resume();
+/// ```
+#[derive(Debug, Clone)]
+pub struct GameLoopControl {
+    state: Arc<ControlState>,
+}
+
+#[derive(Debug)]
+struct ControlState {
+    paused: AtomicBool,
+    steps: AtomicU32,
+    speed: AtomicCell<f64>,
+    ticks: AtomicU64,
+    // New interval of the ticks (ms), 0 if unchanged.
+    interval: AtomicU64,
+    // Durations of the ticks (microseconds).
+    last_tick: AtomicU64,
+    max_tick: AtomicU64,
+}
+
+impl GameLoopControl {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Arc::new(ControlState {
+                paused: AtomicBool::new(false),
+                steps: AtomicU32::new(0),
+                speed: AtomicCell::new(1.0),
+                ticks: AtomicU64::new(0),
+                interval: AtomicU64::new(0),
+                last_tick: AtomicU64::new(0),
+                max_tick: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Stops the updates of the world.
+    pub fn pause(&self) {
+        self.state.paused.store(true, Ordering::Release);
+    }
+
+    /// Restarts the updates of the world.
+    pub fn resume(&self) {
+        self.state.steps.store(0, Ordering::Release);
+        self.state.paused.store(false, Ordering::Release);
+    }
+
+    /// Returns true if the world is paused.
+    pub fn is_paused(&self) -> bool {
+        self.state.paused.load(Ordering::Acquire)
+    }
+
+    /// Executes one tick (one interval) while the world is paused.
+    pub fn step(&self) {
+        self.state.steps.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Changes the speed of the world (1.0 by default, 0.25 is four times slower).
+    pub fn set_speed(&self, speed: f64) {
+        self.state.speed.store(speed.max(0.0));
+    }
+
+    /// Returns the speed of the world.
+    pub fn speed(&self) -> f64 {
+        self.state.speed.load()
+    }
+
+    /// Returns the amount of ticks executed.
+    pub fn ticks(&self) -> u64 {
+        self.state.ticks.load(Ordering::Acquire)
+    }
+
+    /// Returns the duration of the last tick (update of the world and scheduler).
+    pub fn last_tick_duration(&self) -> Duration {
+        Duration::from_micros(self.state.last_tick.load(Ordering::Acquire))
+    }
+
+    /// Returns the duration of the longest tick.
+    pub fn max_tick_duration(&self) -> Duration {
+        Duration::from_micros(self.state.max_tick.load(Ordering::Acquire))
+    }
+
+    /// Changes the interval of the GameLoop from the next tick.
+    pub fn set_interval(&self, interval: Duration) {
+        let interval = (interval.as_millis() as u64).max(1);
+        self.state.interval.store(interval, Ordering::Release);
+    }
+
+    fn take_interval(&self) -> Option<i64> {
+        match self.state.interval.swap(0, Ordering::AcqRel) {
+            0 => None,
+            interval => Some(interval as i64),
+        }
+    }
+
+    fn record_tick(&self, duration: Duration) {
+        let micros = duration.as_micros() as u64;
+        self.state.last_tick.store(micros, Ordering::Release);
+        self.state.max_tick.fetch_max(micros, Ordering::AcqRel);
+        self.state.ticks.fetch_add(1, Ordering::AcqRel);
+    }
+
+    fn take_step(&self) -> bool {
+        self.state
+            .steps
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |steps| {
+                steps.checked_sub(1)
+            })
+            .is_ok()
+    }
 }
 
 impl GameLoop {
@@ -24,9 +163,43 @@ impl GameLoop {
         Self {
             interval: interval.as_millis() as i64,
             game_time: GameTime::new(),
+            ticks: None,
+            clock: Arc::new(SystemClock),
+            control: GameLoopControl::new(),
+            offset: 0,
+            remainder: 0.0,
         }
     }
 
+    /// Returns the runtime control of the GameLoop.
+    pub fn control(&self) -> GameLoopControl {
+        self.control.clone()
+    }
+
+    pub(crate) fn with_control(mut self, control: GameLoopControl) -> Self {
+        self.control = control;
+        self
+    }
+
+    /// Drives the GameLoop with a clock (`ManualClock` in the tests).
+    /// ```rust, no_run
+    /// use rollo::game::{GameLoop, ManualClock};
+    /// use std::{sync::Arc, time::Duration};
+    ///
+    /// let game_loop = GameLoop::new(Duration::from_millis(25)).with_clock(Arc::new(ManualClock::new()));
+    /// ```
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.game_time = GameTime::with_clock(&*clock);
+        self.clock = clock;
+        self
+    }
+
+    /// Notifies the end of each tick (flush of the sessions).
+    pub(crate) fn with_ticks(mut self, ticks: Arc<Sender<u64>>) -> Self {
+        self.ticks = Some(ticks);
+        self
+    }
+
     /// Start the Game Loop
     pub async fn start(
         &mut self,
@@ -34,24 +207,86 @@ impl GameLoop {
         game_time: Option<&'static AtomicCell<GameTime>>,
     ) {
         loop {
-            let old = self.game_time.timestamp;
-            self.game_time.update_time();
-            let diff = GameLoop::get_diff(old, self.game_time.timestamp);
+            self.tick(world, game_time).await;
+
+            self.sleep_until_interval().await;
+
+            yield_now().await;
+        }
+    }
+
+    /// Executes one tick without waiting for the interval.
+    pub async fn tick(
+        &mut self,
+        world: &'static impl World,
+        game_time: Option<&'static AtomicCell<GameTime>>,
+    ) {
+        let start = self.clock.monotonic();
+
+        if let Some(interval) = self.control.take_interval() {
+            self.interval = interval;
+        }
+
+        let old = self.game_time.timestamp;
+        self.game_time.update_time(&*self.clock);
+        let diff = GameLoop::get_diff(old, self.game_time.timestamp);
+
+        if let Some(diff) = self.scale(diff) {
+            let world_time = self.game_time.shifted(self.offset);
 
             if let Some(game_time) = game_time {
-                game_time.store(self.game_time);
+                game_time.store(world_time);
             }
 
-            World::update(world, diff, self.game_time);
+            if let Some(grid) = world.aoi_grid() {
+                grid.lock().remove_closed();
+            }
 
-            self.sleep_until_interval().await;
+            let scheduler = world.scheduler();
+            if let Some(scheduler) = scheduler {
+                scheduler.set_time(world_time.timestamp);
+            }
 
-            yield_now().await;
+            World::update(world, diff, world_time);
+
+            if let Some(scheduler) = scheduler {
+                scheduler.run(world_time.timestamp).await;
+            }
+        }
+
+        let duration = self.clock.monotonic().saturating_sub(start);
+        self.control.record_tick(duration);
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::METRICS.tick(duration, Duration::from_millis(self.interval as u64));
+
+        if let Some(ticks) = &self.ticks {
+            ticks.send_modify(|tick| *tick = tick.wrapping_add(1));
         }
     }
 
+    // Returns the diff of the world, None if the world is paused.
+    fn scale(&mut self, diff: i64) -> Option<i64> {
+        let scaled = if self.control.is_paused() {
+            if !self.control.take_step() {
+                self.offset -= diff;
+                return None;
+            }
+
+            self.interval
+        } else {
+            let scaled = diff as f64 * self.control.speed() + self.remainder;
+            self.remainder = scaled.fract();
+            scaled as i64
+        };
+
+        self.offset += scaled - diff;
+
+        Some(scaled)
+    }
+
     fn get_sleep_time(&mut self) -> i64 {
-        let new_date = GameTime::current_timestamp().as_millis() as i64;
+        let new_date = self.clock.now().as_millis() as i64;
 
         let execution_diff = new_date - self.game_time.timestamp;
 
@@ -71,9 +306,11 @@ impl GameLoop {
     }
 
     async fn sleep_until_interval(&mut self) {
-        let sleep_time = self.get_sleep_time();
-        if sleep_time > 0 {
-            SpinSleeper::default().sleep(Duration::from_millis(self.get_sleep_time() as u64));
+        if self.get_sleep_time() > 0 {
+            let deadline = (self.game_time.timestamp + self.interval) as u64;
+            self.clock
+                .sleep_until(Duration::from_millis(deadline))
+                .await;
         }
     }
 }
@@ -85,7 +322,10 @@ mod tests {
     use crate::server::world_session::WorldSession;
 
     use super::*;
+    use crate::game::{AoiEvent, AoiGrid, ManualClock, Scheduler};
+    use crate::server::world_session::SocketTools;
     use async_trait::async_trait;
+    use parking_lot::Mutex;
     use tokio::time::sleep;
 
     #[tokio::test]
@@ -96,7 +336,7 @@ mod tests {
 
         assert_eq!(game_loop.get_sleep_time(), 0);
 
-        game_loop.game_time.update_time();
+        game_loop.game_time.update_time(&SystemClock);
 
         sleep(Duration::from_millis(10)).await;
 
@@ -104,7 +344,7 @@ mod tests {
 
         assert!(time > 55 && time < 70);
 
-        game_loop.game_time.update_time();
+        game_loop.game_time.update_time(&SystemClock);
 
         sleep(Duration::from_millis(50)).await;
 
@@ -112,7 +352,7 @@ mod tests {
 
         assert!(time > 10 && time < 30);
 
-        game_loop.game_time.update_time();
+        game_loop.game_time.update_time(&SystemClock);
 
         sleep(Duration::from_millis(75)).await;
 
@@ -127,13 +367,13 @@ mod tests {
         let sleep_time = timer.elapsed().as_millis();
         assert!((21..=30).contains(&sleep_time));
 
-        game_loop.game_time.update_time();
+        game_loop.game_time.update_time(&SystemClock);
 
         game_loop.sleep_until_interval().await;
         let sleep_time = timer.elapsed().as_millis();
         assert!((42..=65).contains(&sleep_time));
 
-        game_loop.game_time.update_time();
+        game_loop.game_time.update_time(&SystemClock);
 
         game_loop.sleep_until_interval().await;
         let sleep_time = timer.elapsed().as_millis();
@@ -147,7 +387,7 @@ mod tests {
         sleep(Duration::from_millis(10)).await;
 
         let old = game_loop.game_time.timestamp;
-        game_loop.game_time.update_time();
+        game_loop.game_time.update_time(&SystemClock);
         let new_date = game_loop.game_time.timestamp;
 
         assert!(old != new_date && new_date > old);
@@ -164,6 +404,185 @@ mod tests {
             .unwrap();
     }
 
+    #[tokio::test]
+    async fn test_manual_clock() {
+        let clock = Arc::new(ManualClock::starting_at(Duration::from_secs(10)));
+        let world: &'static ClockWorld = Box::leak(Box::new(ClockWorld {
+            diffs: Mutex::new(Vec::new()),
+        }));
+        let game_time = Box::leak(Box::new(AtomicCell::new(GameTime::new())));
+
+        let mut game_loop = GameLoop::new(Duration::from_millis(50)).with_clock(clock.clone());
+        game_loop.tick(world, Some(game_time)).await;
+        clock.advance(Duration::from_millis(30));
+        game_loop.tick(world, Some(game_time)).await;
+
+        assert_eq!(*world.diffs.lock(), [0, 30]);
+        assert_eq!(game_time.load().timestamp, 10_030);
+        assert_eq!(game_loop.get_sleep_time(), 50);
+    }
+
+    #[tokio::test]
+    async fn test_loop_manual_clock() {
+        let clock = Arc::new(ManualClock::new());
+        let world: &'static ClockWorld = Box::leak(Box::new(ClockWorld {
+            diffs: Mutex::new(Vec::new()),
+        }));
+
+        let mut game_loop = GameLoop::new(Duration::from_millis(50)).with_clock(clock.clone());
+        tokio::spawn(async move { game_loop.start(world, None).await });
+
+        for ticks in 1..=3 {
+            while world.diffs.lock().len() < ticks {
+                tokio::task::yield_now().await;
+            }
+            clock.advance(Duration::from_millis(50));
+        }
+
+        sleep(Duration::from_millis(20)).await;
+        assert_eq!(*world.diffs.lock(), [0, 50, 50, 50]);
+    }
+
+    #[tokio::test]
+    async fn test_speed() {
+        let clock = Arc::new(ManualClock::starting_at(Duration::from_secs(10)));
+        let world: &'static ClockWorld = Box::leak(Box::new(ClockWorld {
+            diffs: Mutex::new(Vec::new()),
+        }));
+        let game_time = Box::leak(Box::new(AtomicCell::new(GameTime::new())));
+
+        let mut game_loop = GameLoop::new(Duration::from_millis(50)).with_clock(clock.clone());
+        game_loop.control().set_speed(0.25);
+        for _ in 0..2 {
+            clock.advance(Duration::from_millis(10));
+            game_loop.tick(world, Some(game_time)).await;
+        }
+        assert_eq!(*world.diffs.lock(), [2, 3]);
+        assert_eq!(game_time.load().timestamp, 10_005);
+
+        game_loop.control().set_speed(4.0);
+        clock.advance(Duration::from_millis(10));
+        game_loop.tick(world, Some(game_time)).await;
+        assert_eq!(*world.diffs.lock(), [2, 3, 40]);
+        assert_eq!(game_time.load().timestamp, 10_045);
+        assert_eq!(game_loop.control().ticks(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_set_interval() {
+        let clock = Arc::new(ManualClock::starting_at(Duration::from_secs(10)));
+        let world: &'static ClockWorld = Box::leak(Box::new(ClockWorld {
+            diffs: Mutex::new(Vec::new()),
+        }));
+
+        let mut game_loop = GameLoop::new(Duration::from_millis(50)).with_clock(clock.clone());
+        let control = game_loop.control();
+        control.pause();
+        control.step();
+        control.set_interval(Duration::from_millis(20));
+        game_loop.tick(world, None).await;
+        assert_eq!(*world.diffs.lock(), [20]);
+        assert_eq!(game_loop.get_sleep_time(), 20);
+    }
+
+    #[tokio::test]
+    async fn test_pause() {
+        let clock = Arc::new(ManualClock::starting_at(Duration::from_secs(10)));
+        let world: &'static ClockWorld = Box::leak(Box::new(ClockWorld {
+            diffs: Mutex::new(Vec::new()),
+        }));
+        let game_time = Box::leak(Box::new(AtomicCell::new(GameTime::new())));
+
+        let mut game_loop = GameLoop::new(Duration::from_millis(50)).with_clock(clock.clone());
+        let control = game_loop.control();
+        control.pause();
+        assert!(control.is_paused());
+
+        clock.advance(Duration::from_millis(100));
+        game_loop.tick(world, Some(game_time)).await;
+        assert!(world.diffs.lock().is_empty());
+
+        control.step();
+        clock.advance(Duration::from_millis(100));
+        game_loop.tick(world, Some(game_time)).await;
+        game_loop.tick(world, Some(game_time)).await;
+        assert_eq!(*world.diffs.lock(), [50]);
+        assert_eq!(game_time.load().timestamp, 10_050);
+
+        control.resume();
+        clock.advance(Duration::from_millis(20));
+        game_loop.tick(world, Some(game_time)).await;
+        assert_eq!(*world.diffs.lock(), [50, 20]);
+        assert_eq!(game_time.load().timestamp, 10_070);
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_world_time() {
+        let clock = Arc::new(ManualClock::starting_at(Duration::from_secs(10)));
+        let world: &'static SchedulerWorld = Box::leak(Box::new(SchedulerWorld {
+            scheduler: Scheduler::with_clock(clock.clone()),
+            executed: Arc::new(Mutex::new(Vec::new())),
+        }));
+
+        let mut game_loop = GameLoop::new(Duration::from_millis(50)).with_clock(clock.clone());
+        let control = game_loop.control();
+        control.set_speed(0.25);
+        world.schedule(1, 100);
+
+        // 100ms of the world are 400ms at a quarter of the speed.
+        for _ in 0..7 {
+            clock.advance(Duration::from_millis(50));
+            game_loop.tick(world, None).await;
+        }
+        assert!(world.executed.lock().is_empty());
+
+        clock.advance(Duration::from_millis(50));
+        game_loop.tick(world, None).await;
+        assert_eq!(*world.executed.lock(), [1]);
+
+        // The delays are stopped while the world is paused.
+        control.set_speed(1.0);
+        world.schedule(2, 100);
+        control.pause();
+        clock.advance(Duration::from_millis(500));
+        game_loop.tick(world, None).await;
+        world.schedule(3, 50);
+        control.resume();
+
+        clock.advance(Duration::from_millis(50));
+        game_loop.tick(world, None).await;
+        assert_eq!(*world.executed.lock(), [1, 3]);
+
+        clock.advance(Duration::from_millis(50));
+        game_loop.tick(world, None).await;
+        assert_eq!(*world.executed.lock(), [1, 3, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_aoi_grid() {
+        let world: &'static AoiWorld = Box::leak(Box::new(AoiWorld {
+            grid: Mutex::new(AoiGrid::new(10.0, 1)),
+            events: Mutex::new(Vec::new()),
+        }));
+        let (first, rx) = SocketTools::for_test(1);
+        let (second, _rx_second) = SocketTools::for_test(2);
+        world.grid.lock().insert(1, 5.0, 5.0, Some(first));
+        world.grid.lock().insert(2, 6.0, 6.0, Some(second));
+
+        let mut game_loop = GameLoop::new(Duration::from_millis(50));
+        game_loop.tick(world, None).await;
+        world.events.lock().clear();
+
+        drop(rx);
+        game_loop.tick(world, None).await;
+
+        assert_eq!(world.grid.lock().len(), 1);
+        assert!(world.events.lock().contains(&AoiEvent::Leave {
+            observer: 2,
+            entity: 1
+        }));
+    }
+
     #[test]
     fn test_get_diff() {
         let diff = GameLoop::get_diff(100, 150);
@@ -173,10 +592,10 @@ mod tests {
     struct SessionTest;
 
     #[async_trait]
-    impl WorldSession<TestGameLoop> for SessionTest {
+    impl<T: Send + Sync + 'static> WorldSession<T> for SessionTest {
         async fn on_open(
             _socket_tools: crate::server::world_session::SocketTools,
-            _world: &'static TestGameLoop,
+            _world: &'static T,
         ) -> Result<Arc<Self>, crate::error::Error> {
             todo!()
         }
@@ -187,19 +606,74 @@ mod tests {
 
         async fn on_message(
             _world_session: &Arc<Self>,
-            _world: &'static TestGameLoop,
+            _world: &'static T,
             _packet: crate::packet::Packet,
         ) {
             todo!()
         }
 
-        async fn on_close(_world_session: &Arc<Self>, _world: &'static TestGameLoop) {
+        async fn on_close(_world_session: &Arc<Self>, _world: &'static T) {
             todo!()
         }
     }
 
     struct TestGameLoop {}
 
+    struct ClockWorld {
+        diffs: Mutex<Vec<i64>>,
+    }
+
+    impl World for ClockWorld {
+        type WorldSessionimplementer = SessionTest;
+
+        fn update(&'static self, diff: i64, _game_time: GameTime) {
+            self.diffs.lock().push(diff);
+        }
+    }
+
+    struct SchedulerWorld {
+        scheduler: Scheduler,
+        executed: Arc<Mutex<Vec<u32>>>,
+    }
+
+    impl SchedulerWorld {
+        fn schedule(&self, task: u32, delay: u64) {
+            let executed = Arc::clone(&self.executed);
+            self.scheduler
+                .schedule(Duration::from_millis(delay), move || {
+                    executed.lock().push(task)
+                });
+        }
+    }
+
+    impl World for SchedulerWorld {
+        type WorldSessionimplementer = SessionTest;
+
+        fn update(&'static self, _diff: i64, _game_time: GameTime) {}
+
+        fn scheduler(&'static self) -> Option<&'static Scheduler> {
+            Some(&self.scheduler)
+        }
+    }
+
+    struct AoiWorld {
+        grid: Mutex<AoiGrid>,
+        events: Mutex<Vec<AoiEvent>>,
+    }
+
+    impl World for AoiWorld {
+        type WorldSessionimplementer = SessionTest;
+
+        fn update(&'static self, _diff: i64, _game_time: GameTime) {
+            let events = self.grid.lock().drain_events().collect::<Vec<_>>();
+            self.events.lock().extend(events);
+        }
+
+        fn aoi_grid(&'static self) -> Option<&'static Mutex<AoiGrid>> {
+            Some(&self.grid)
+        }
+    }
+
     impl World for TestGameLoop {
         type WorldSessionimplementer = SessionTest;
 
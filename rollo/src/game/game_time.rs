@@ -1,9 +1,10 @@
-use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use super::{Clock, SystemClock};
+use std::time::Duration;
 
 /// GameTime
 #[derive(Debug, Clone, Copy)]
 pub struct GameTime {
-    instant: Instant,
+    start: Duration,
     pub elapsed: Duration,
     pub system_time: Duration,
     pub timestamp: i64,
@@ -17,26 +18,47 @@ impl GameTime {
     /// let game_time = GameTime::new();
     /// ```
     pub fn new() -> Self {
-        let duration = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        Self::with_clock(&SystemClock)
+    }
+
+    /// New GameTime from a clock
+    /// ```rust, no_run
+    /// use rollo::game::{GameTime, ManualClock};
+    ///
+    /// let game_time = GameTime::with_clock(&ManualClock::new());
+    /// ```
+    pub fn with_clock(clock: &dyn Clock) -> Self {
+        let duration = clock.now();
         Self {
             system_time: duration,
             timestamp: duration.as_millis() as i64,
-            instant: Instant::now(),
+            start: clock.monotonic(),
             elapsed: Duration::ZERO,
         }
     }
 
-    pub(crate) fn update_time(&mut self) -> bool {
-        let duration = Self::current_timestamp();
+    pub(crate) fn update_time(&mut self, clock: &dyn Clock) -> bool {
+        let duration = clock.now();
         self.system_time = duration;
         self.timestamp = duration.as_millis() as i64;
-        self.elapsed = self.instant.elapsed();
+        self.elapsed = clock.monotonic().saturating_sub(self.start);
 
         true
     }
 
-    pub(crate) fn current_timestamp() -> Duration {
-        SystemTime::now().duration_since(UNIX_EPOCH).unwrap()
+    /// The same time, moved by an offset (milliseconds).
+    pub(crate) fn shifted(mut self, offset: i64) -> Self {
+        let shift = Duration::from_millis(offset.unsigned_abs());
+        if offset >= 0 {
+            self.system_time += shift;
+            self.elapsed += shift;
+        } else {
+            self.system_time = self.system_time.saturating_sub(shift);
+            self.elapsed = self.elapsed.saturating_sub(shift);
+        }
+        self.timestamp += offset;
+
+        self
     }
 }
 
@@ -48,9 +70,13 @@ impl Default for GameTime {
 
 #[cfg(test)]
 mod tests {
-    use std::thread::sleep;
+    use std::{
+        thread::sleep,
+        time::{SystemTime, UNIX_EPOCH},
+    };
 
     use super::*;
+    use crate::game::ManualClock;
 
     #[test]
     fn test_new() {
@@ -65,11 +91,13 @@ mod tests {
     #[test]
     fn test_current_timestamp() {
         let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
-        let game_time = GameTime::current_timestamp();
-        let diff = game_time.checked_sub(timestamp).unwrap_or_else(|| timestamp.checked_sub(game_time).unwrap());
+        let game_time = SystemClock.now();
+        let diff = game_time
+            .checked_sub(timestamp)
+            .unwrap_or_else(|| timestamp.checked_sub(game_time).unwrap());
         assert!(diff <= Duration::from_millis(10));
     }
-    
+
     #[test]
     fn test_update_time() {
         let mut game_time = GameTime::new();
@@ -79,10 +107,40 @@ mod tests {
         assert_eq!(ellapsed, Duration::ZERO);
         sleep(Duration::from_millis(1));
 
-        let r = game_time.update_time();
+        let r = game_time.update_time(&SystemClock);
         assert!(r);
         assert!(game_time.timestamp > timestamp);
         assert!(game_time.elapsed > ellapsed);
         assert!(game_time.system_time > system_time);
     }
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::starting_at(Duration::from_secs(10));
+        let mut game_time = GameTime::with_clock(&clock);
+        assert_eq!(game_time.timestamp, 10_000);
+
+        clock.advance(Duration::from_millis(25));
+        game_time.update_time(&clock);
+        assert_eq!(game_time.timestamp, 10_025);
+        assert_eq!(game_time.system_time, Duration::from_millis(10_025));
+        assert_eq!(game_time.elapsed, Duration::from_millis(25));
+    }
+
+    #[test]
+    fn test_shifted() {
+        let clock = ManualClock::starting_at(Duration::from_secs(10));
+        let mut game_time = GameTime::with_clock(&clock);
+        clock.advance(Duration::from_millis(100));
+        game_time.update_time(&clock);
+
+        let shifted = game_time.shifted(-40);
+        assert_eq!(shifted.timestamp, 10_060);
+        assert_eq!(shifted.system_time, Duration::from_millis(10_060));
+        assert_eq!(shifted.elapsed, Duration::from_millis(60));
+
+        let shifted = game_time.shifted(25);
+        assert_eq!(shifted.timestamp, 10_125);
+        assert_eq!(shifted.elapsed, Duration::from_millis(125));
+    }
 }
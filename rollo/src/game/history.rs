@@ -0,0 +1,208 @@
+use crate::server::world_session::SocketTools;
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+/// A state that can be interpolated between two records.
+pub trait Interpolate: Clone {
+    /// Returns the state between self (t = 0) and other (t = 1).
+    fn interpolate(&self, other: &Self, t: f32) -> Self;
+}
+
+impl Interpolate for f32 {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+impl<const N: usize> Interpolate for [f32; N] {
+    fn interpolate(&self, other: &Self, t: f32) -> Self {
+        let mut state = *self;
+        state
+            .iter_mut()
+            .zip(other.iter())
+            .for_each(|(value, other)| *value = value.interpolate(other, t));
+        state
+    }
+}
+
+/// # History
+/// Keeps the states of the entities during the max rewind window (lag compensation).
+/// ## Usage
+/// ```rust, no_run
+/// use rollo::game::{GameTime, History};
+/// use std::time::Duration;
+///
+/// let mut history = History::new(Duration::from_millis(500));
+///
+/// // In World::update
+/// let game_time = GameTime::new();
+/// history.record(1, game_time.timestamp, [10.0, 20.0]);
+/// // Once per tick, for the entities which no longer record.
+/// history.prune(game_time.timestamp);
+///
+/// // Validate a shot with the positions seen by the player.
+/// // let time = history.client_time(game_time.timestamp, socket_tools);
+/// let position = history.state_at(1, game_time.timestamp - 50);
+/// ```
+#[derive(Debug)]
+pub struct History<S: Interpolate> {
+    max_rewind: i64,
+    entities: HashMap<u64, VecDeque<(i64, S)>>,
+}
+
+impl<S: Interpolate> History<S> {
+    /// Creates the history with the max rewind window.
+    pub fn new(max_rewind: Duration) -> Self {
+        Self {
+            max_rewind: max_rewind.as_millis() as i64,
+            entities: HashMap::new(),
+        }
+    }
+
+    /// Records the state of an entity at a timestamp (`GameTime::timestamp`).
+    ///
+    /// The states older than the max rewind window are removed.
+    pub fn record(&mut self, id: u64, timestamp: i64, state: S) {
+        let records = self.entities.entry(id).or_default();
+
+        match records.back_mut() {
+            Some(last) if last.0 == timestamp => last.1 = state,
+            Some(last) if last.0 > timestamp => return,
+            _ => records.push_back((timestamp, state)),
+        }
+
+        prune_records(records, timestamp - self.max_rewind);
+    }
+
+    /// Removes the states older than the max rewind window for every entity, to call at each tick.
+    ///
+    /// The entities without state in the window are removed.
+    pub fn prune(&mut self, now: i64) {
+        let limit = now - self.max_rewind;
+
+        self.entities.retain(|_, records| {
+            prune_records(records, limit);
+            records.back().is_some_and(|(time, _)| *time >= limit)
+        });
+    }
+
+    /// Returns the state of an entity at a timestamp, interpolated between the records.
+    ///
+    /// The timestamp is clamped to the max rewind window and the recorded states.
+    pub fn state_at(&self, id: u64, timestamp: i64) -> Option<S> {
+        let records = self.entities.get(&id)?;
+        let (last_time, _) = records.back()?;
+        let timestamp = timestamp.clamp(last_time - self.max_rewind, *last_time);
+
+        let index = records.partition_point(|(time, _)| *time <= timestamp);
+        if index == 0 {
+            return records.front().map(|(_, state)| state.clone());
+        }
+
+        let (before_time, before) = &records[index - 1];
+        match records.get(index) {
+            Some((after_time, after)) => {
+                let t = (timestamp - before_time) as f32 / (after_time - before_time) as f32;
+                Some(before.interpolate(after, t))
+            }
+            None => Some(before.clone()),
+        }
+    }
+
+    /// Returns the time seen by the session, with the half of its round trip time.
+    pub fn client_time(&self, now: i64, socket_tools: &SocketTools) -> i64 {
+        let latency = socket_tools.get_latency().max(0);
+        now - (latency / 2).min(self.max_rewind)
+    }
+
+    /// Removes an entity.
+    pub fn remove(&mut self, id: u64) {
+        self.entities.remove(&id);
+    }
+
+    /// Returns the amount of entities.
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Returns true if there is no entity.
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+}
+
+// Keeps the record just before the window to interpolate at its start.
+fn prune_records<S>(records: &mut VecDeque<(i64, S)>, limit: i64) {
+    while records.len() > 1 && records[1].0 <= limit {
+        records.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn test_state_at() {
+        let mut history = History::new(Duration::from_millis(100));
+        history.record(1, 1000, [0.0, 10.0]);
+        history.record(1, 1050, [10.0, 20.0]);
+        history.record(1, 1100, [20.0, 20.0]);
+
+        assert_eq!(history.state_at(1, 1000), Some([0.0, 10.0]));
+        assert_eq!(history.state_at(1, 1025), Some([5.0, 15.0]));
+        assert_eq!(history.state_at(1, 1075), Some([15.0, 20.0]));
+        assert_eq!(history.state_at(1, 1200), Some([20.0, 20.0]));
+        assert_eq!(history.state_at(1, 500), Some([0.0, 10.0]));
+        assert_eq!(history.state_at(2, 1000), None);
+    }
+
+    #[test]
+    fn test_prune() {
+        let mut history = History::new(Duration::from_millis(100));
+        (0..10).for_each(|i| history.record(1, 1000 + i * 50, i as f32));
+
+        // 1450 - 100 = 1350, the record at 1350 is kept.
+        assert_eq!(history.entities[&1].len(), 3);
+        assert_eq!(history.state_at(1, 1000), Some(7.0));
+        assert_eq!(history.state_at(1, 1375), Some(7.5));
+
+        history.record(1, 1200, 0.0);
+        assert_eq!(history.entities[&1].len(), 3);
+
+        history.remove(1);
+        assert!(history.is_empty());
+    }
+
+    #[test]
+    fn test_prune_all() {
+        let mut history = History::new(Duration::from_millis(100));
+        (0..3).for_each(|i| history.record(1, 1000 + i * 50, i as f32));
+        (0..10).for_each(|i| history.record(2, 1000 + i * 50, i as f32));
+
+        // The entity 1 stopped recording at 1100.
+        history.prune(1150);
+        assert_eq!(history.entities[&1].len(), 2);
+        assert_eq!(history.state_at(1, 1050), Some(1.0));
+
+        history.prune(1450);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.state_at(1, 1000), None);
+        assert_eq!(history.entities[&2].len(), 3);
+    }
+
+    #[test]
+    fn test_client_time() {
+        let (socket_tools, _rx) = SocketTools::for_test(1);
+        let history: History<f32> = History::new(Duration::from_millis(100));
+
+        socket_tools.latency.store(80, Ordering::Relaxed);
+        assert_eq!(history.client_time(1000, &socket_tools), 960);
+
+        socket_tools.latency.store(1000, Ordering::Relaxed);
+        assert_eq!(history.client_time(1000, &socket_tools), 900);
+    }
+}
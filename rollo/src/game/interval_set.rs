@@ -0,0 +1,326 @@
+use indexmap::IndexMap;
+use rand::Rng;
+use rand_xoshiro::{rand_core::SeedableRng, Xoshiro256PlusPlus};
+use std::{hash::Hash, time::Duration};
+
+/// Configuration of an interval of an `IntervalSet`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IntervalConfig {
+    interval: i64,
+    offset: Option<i64>,
+    jitter: i64,
+}
+
+impl IntervalConfig {
+    /// Creates the configuration with the interval.
+    pub const fn new(interval: Duration) -> Self {
+        Self {
+            interval: interval.as_millis() as i64,
+            offset: None,
+            jitter: 0,
+        }
+    }
+
+    /// Delay before the first execution (the interval by default).
+    pub const fn with_offset(mut self, offset: Duration) -> Self {
+        self.offset = Some(offset.as_millis() as i64);
+        self
+    }
+
+    /// Each execution is moved randomly between -jitter and +jitter.
+    pub const fn with_jitter(mut self, jitter: Duration) -> Self {
+        self.jitter = jitter.as_millis() as i64;
+        self
+    }
+}
+
+#[derive(Debug)]
+struct IntervalEntry {
+    config: IntervalConfig,
+    current: i64,
+    target: i64,
+    paused: bool,
+}
+
+/// # Interval Set
+/// Many intervals (by name or id), all advanced by the same update.
+/// ## Usage
+/// ```rust, no_run
+/// use rollo::game::{IntervalConfig, IntervalSet};
+/// use std::time::Duration;
+///
+/// let mut intervals = IntervalSet::new();
+/// intervals.insert("regen", IntervalConfig::new(Duration::from_secs(2)));
+/// intervals.insert(
+///     "autosave",
+///     IntervalConfig::new(Duration::from_secs(60))
+///         .with_offset(Duration::from_secs(10))
+///         .with_jitter(Duration::from_secs(5)),
+/// );
+///
+/// // In World::update
+/// intervals.update(100, |name, diff| match *name {
+///     "regen" => {}
+///     "autosave" => {}
+///     _ => {}
+/// });
+/// ```
+#[derive(Debug)]
+pub struct IntervalSet<K>
+where
+    K: Hash + Eq,
+{
+    intervals: IndexMap<K, IntervalEntry>,
+    rng: Xoshiro256PlusPlus,
+}
+
+impl<K> IntervalSet<K>
+where
+    K: Hash + Eq,
+{
+    /// Creates the set with a random seed for the jitter.
+    pub fn new() -> Self {
+        Self::with_seed(rand::random())
+    }
+
+    /// Creates the set with the seed of the jitter.
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            intervals: IndexMap::new(),
+            rng: Xoshiro256PlusPlus::seed_from_u64(seed),
+        }
+    }
+
+    /// Adds (or replaces) an interval.
+    pub fn insert(&mut self, key: K, config: IntervalConfig) {
+        let target = match config.offset {
+            Some(offset) => offset,
+            None => self.next_target(config),
+        };
+
+        self.intervals.insert(
+            key,
+            IntervalEntry {
+                config,
+                current: 0,
+                target,
+                paused: false,
+            },
+        );
+    }
+
+    /// Removes an interval, returns false if the interval is unknown.
+    pub fn remove(&mut self, key: &K) -> bool {
+        self.intervals.shift_remove(key).is_some()
+    }
+
+    /// Stops the progress of an interval.
+    pub fn pause(&mut self, key: &K) -> bool {
+        self.set_paused(key, true)
+    }
+
+    /// Restarts the progress of an interval.
+    pub fn resume(&mut self, key: &K) -> bool {
+        self.set_paused(key, false)
+    }
+
+    /// Returns true if the interval is paused.
+    pub fn is_paused(&self, key: &K) -> bool {
+        self.intervals.get(key).is_some_and(|entry| entry.paused)
+    }
+
+    /// Changes the interval, the time already passed is kept.
+    pub fn set_interval(&mut self, key: &K, interval: Duration) -> bool {
+        let config = match self.intervals.get(key) {
+            Some(entry) => IntervalConfig {
+                interval: interval.as_millis() as i64,
+                ..entry.config
+            },
+            None => return false,
+        };
+
+        let target = self.next_target(config);
+        if let Some(entry) = self.intervals.get_mut(key) {
+            entry.config = config;
+            entry.target = target;
+        }
+
+        true
+    }
+
+    /// Returns the time before the next execution of an interval.
+    pub fn remaining_time(&self, key: &K) -> Option<Duration> {
+        self.intervals
+            .get(key)
+            .map(|entry| Duration::from_millis((entry.target - entry.current).max(0) as u64))
+    }
+
+    /// Returns true if the interval exists.
+    pub fn contains(&self, key: &K) -> bool {
+        self.intervals.contains_key(key)
+    }
+
+    /// Returns the amount of intervals.
+    pub fn len(&self) -> usize {
+        self.intervals.len()
+    }
+
+    /// Returns true if there is no interval.
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// ## Update the intervals
+    /// Calls the closure with the key and the time passed for each interval passed.
+    pub fn update(&mut self, diff: i64, mut f: impl FnMut(&K, i64)) {
+        let rng = &mut self.rng;
+
+        for (key, entry) in self.intervals.iter_mut() {
+            if entry.paused {
+                continue;
+            }
+
+            entry.current += diff;
+
+            if entry.current < entry.target {
+                continue;
+            }
+
+            f(key, entry.current);
+
+            let remaining = entry.current - entry.target;
+            entry.current = remaining % entry.config.interval.max(1);
+            entry.target = target(rng, entry.config);
+        }
+    }
+
+    fn set_paused(&mut self, key: &K, paused: bool) -> bool {
+        match self.intervals.get_mut(key) {
+            Some(entry) => {
+                entry.paused = paused;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn next_target(&mut self, config: IntervalConfig) -> i64 {
+        target(&mut self.rng, config)
+    }
+}
+
+impl<K> Default for IntervalSet<K>
+where
+    K: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn target(rng: &mut Xoshiro256PlusPlus, config: IntervalConfig) -> i64 {
+    let jitter = if config.jitter > 0 {
+        rng.gen_range(-config.jitter..=config.jitter)
+    } else {
+        0
+    };
+
+    (config.interval + jitter).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fired(intervals: &mut IntervalSet<&'static str>, diff: i64) -> Vec<(&'static str, i64)> {
+        let mut fired = Vec::new();
+        intervals.update(diff, |key, diff| fired.push((*key, diff)));
+        fired
+    }
+
+    #[test]
+    fn test_update() {
+        let mut intervals = IntervalSet::new();
+        intervals.insert("first", IntervalConfig::new(Duration::from_millis(50)));
+        intervals.insert("second", IntervalConfig::new(Duration::from_millis(100)));
+
+        assert!(fired(&mut intervals, 30).is_empty());
+        assert_eq!(fired(&mut intervals, 30), [("first", 60)]);
+        assert_eq!(fired(&mut intervals, 50), [("first", 60), ("second", 110)]);
+        assert_eq!(
+            intervals.remaining_time(&"first"),
+            Some(Duration::from_millis(40))
+        );
+        assert_eq!(intervals.len(), 2);
+    }
+
+    #[test]
+    fn test_offset() {
+        let mut intervals = IntervalSet::new();
+        intervals.insert(
+            "first",
+            IntervalConfig::new(Duration::from_millis(100)).with_offset(Duration::from_millis(10)),
+        );
+
+        assert_eq!(fired(&mut intervals, 10), [("first", 10)]);
+        assert!(fired(&mut intervals, 90).is_empty());
+        assert_eq!(fired(&mut intervals, 10), [("first", 100)]);
+    }
+
+    #[test]
+    fn test_jitter() {
+        let mut intervals = IntervalSet::with_seed(5);
+        let config =
+            IntervalConfig::new(Duration::from_millis(100)).with_jitter(Duration::from_millis(20));
+        intervals.insert("first", config);
+
+        let mut same_seed = IntervalSet::with_seed(5);
+        same_seed.insert("first", config);
+        assert_eq!(
+            intervals.remaining_time(&"first"),
+            same_seed.remaining_time(&"first")
+        );
+
+        let mut times = Vec::new();
+        while times.len() < 20 {
+            intervals.update(1, |_, diff| times.push(diff));
+        }
+
+        assert!(times.iter().all(|diff| (80..=120).contains(diff)));
+        assert!(times.iter().any(|diff| *diff != times[0]));
+    }
+
+    #[test]
+    fn test_pause() {
+        let mut intervals = IntervalSet::new();
+        intervals.insert(1, IntervalConfig::new(Duration::from_millis(50)));
+
+        assert!(intervals.pause(&1));
+        assert!(intervals.is_paused(&1));
+        intervals.update(100, |_, _| panic!("paused"));
+
+        assert!(intervals.resume(&1));
+        let mut count = 0;
+        intervals.update(50, |_, _| count += 1);
+        assert_eq!(count, 1);
+        assert!(!intervals.pause(&2));
+    }
+
+    #[test]
+    fn test_set_interval() {
+        let mut intervals = IntervalSet::new();
+        intervals.insert(1, IntervalConfig::new(Duration::from_millis(50)));
+
+        let mut count = 0;
+        intervals.update(40, |_, _| count += 1);
+        assert!(intervals.set_interval(&1, Duration::from_millis(100)));
+        intervals.update(40, |_, _| count += 1);
+        assert_eq!(count, 0);
+        intervals.update(20, |_, _| count += 1);
+        assert_eq!(count, 1);
+
+        assert!(intervals.remove(&1));
+        assert!(!intervals.set_interval(&1, Duration::from_millis(100)));
+        assert!(intervals.is_empty());
+    }
+}
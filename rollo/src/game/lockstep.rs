@@ -0,0 +1,513 @@
+use super::GameTime;
+use crate::error::{Error, Result};
+use crate::packet::to_bytes;
+use crate::server::{world_session::SocketTools, world_socket::ContainerBytes};
+use bytes::BufMut;
+use std::{collections::BTreeMap, convert::TryInto, sync::Arc, time::Duration};
+
+/// Inputs for frames too far ahead of the current frame are rejected.
+const MAX_FRAMES_AHEAD: u32 = 128;
+
+/// The checksums of a frame still missing players this amount of frames later are dropped.
+const CHECKSUM_WINDOW: u32 = 128;
+
+/// What to do when the inputs of a player are missing at the deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StallPolicy {
+    /// The frame is sent without the missing inputs.
+    #[default]
+    Skip,
+    /// The simulation waits for the missing inputs.
+    Wait,
+}
+
+/// Result of an input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputStatus {
+    /// Added to the requested frame.
+    Accepted,
+    /// The frame was already sent, the input is added to this frame.
+    Late(u32),
+    /// Unknown player, frame too far ahead or late input with an input already present.
+    Rejected,
+}
+
+/// Events of the lockstep.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockstepEvent {
+    /// The frame was sent to the players.
+    Frame(u32),
+    /// The deadline of the frame passed without the inputs of these players.
+    Stall { frame: u32, missing: Vec<u64> },
+    /// The checksums of the players are different for the frame.
+    Desync {
+        frame: u32,
+        checksums: Vec<(u64, u64)>,
+    },
+}
+
+/// # Lockstep
+/// Collects the inputs of the players for each frame, then sends the combined frame to
+/// every player once all the inputs are received or at the deadline.
+///
+/// The frames are numbered from 0 and one frame at most is sent by update (tick).
+/// ## Usage
+/// ```rust, no_run
+/// use rollo::game::{GameTime, Lockstep, LockstepEvent, StallPolicy};
+/// use std::{sync::Mutex, time::Duration};
+///
+/// let lockstep = Mutex::new(
+///     Lockstep::new(20, Duration::from_millis(100)).with_stall_policy(StallPolicy::Wait),
+/// );
+///
+/// // In WorldSession::on_message
+/// lockstep.lock().unwrap().submit_input(1, 0, &[1, 2]);
+///
+/// // In World::update
+/// let mut lockstep = lockstep.lock().unwrap();
+/// lockstep.update(GameTime::new());
+/// for event in lockstep.drain_events() {
+///     match event {
+///         LockstepEvent::Frame(frame) => {}
+///         LockstepEvent::Stall { frame, missing } => {}
+///         LockstepEvent::Desync { frame, checksums } => {}
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Lockstep {
+    cmd: u16,
+    deadline: i64,
+    stall_policy: StallPolicy,
+    frame: u32,
+    frame_start: Option<i64>,
+    stalled: bool,
+    players: BTreeMap<u64, SocketTools>,
+    inputs: BTreeMap<u32, BTreeMap<u64, Vec<u8>>>,
+    checksums: BTreeMap<u32, BTreeMap<u64, u64>>,
+    events: Vec<LockstepEvent>,
+}
+
+impl Lockstep {
+    /// ## Create the lockstep
+    /// The frames are sent with the command, the deadline starts when the previous frame is sent.
+    /// ### Examples
+    /// ```rust, no_run
+    /// use rollo::game::Lockstep;
+    /// use std::time::Duration;
+    ///
+    /// let lockstep = Lockstep::new(20, Duration::from_millis(100));
+    /// ```
+    pub fn new(cmd: u16, deadline: Duration) -> Self {
+        Self {
+            cmd,
+            deadline: deadline.as_millis() as i64,
+            stall_policy: StallPolicy::default(),
+            frame: 0,
+            frame_start: None,
+            stalled: false,
+            players: BTreeMap::new(),
+            inputs: BTreeMap::new(),
+            checksums: BTreeMap::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Sets the stall policy.
+    pub fn with_stall_policy(mut self, stall_policy: StallPolicy) -> Self {
+        self.stall_policy = stall_policy;
+        self
+    }
+
+    /// Returns the next frame to send.
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// Adds a player.
+    pub fn add_player(&mut self, id: u64, socket_tools: SocketTools) {
+        self.players.insert(id, socket_tools);
+    }
+
+    /// Removes a player, the frames no longer wait for its inputs and its checksums.
+    pub fn remove_player(&mut self, id: u64) -> Option<SocketTools> {
+        self.inputs.values_mut().for_each(|inputs| {
+            inputs.remove(&id);
+        });
+        self.checksums.values_mut().for_each(|checksums| {
+            checksums.remove(&id);
+        });
+
+        let socket_tools = self.players.remove(&id);
+
+        // The frames which only waited for this player are compared.
+        let frames = self.checksums.keys().copied().collect::<Vec<_>>();
+        frames
+            .into_iter()
+            .for_each(|frame| self.compare_checksums(frame));
+
+        socket_tools
+    }
+
+    /// Adds the input of a player for a frame.
+    pub fn submit_input(&mut self, player: u64, frame: u32, input: &[u8]) -> InputStatus {
+        if !self.players.contains_key(&player) || frame > self.frame + MAX_FRAMES_AHEAD {
+            return InputStatus::Rejected;
+        }
+
+        let (frame, status) = if frame < self.frame {
+            (self.frame, InputStatus::Late(self.frame))
+        } else {
+            (frame, InputStatus::Accepted)
+        };
+
+        let inputs = self.inputs.entry(frame).or_default();
+        if inputs.contains_key(&player) {
+            return InputStatus::Rejected;
+        }
+
+        inputs.insert(player, input.to_vec());
+
+        status
+    }
+
+    /// Adds the checksum of the state of a player after a frame.
+    ///
+    /// The checksums of a frame are dropped if a player didn't send its checksum
+    /// `CHECKSUM_WINDOW` (128) frames later.
+    pub fn submit_checksum(&mut self, player: u64, frame: u32, checksum: u64) {
+        if !self.players.contains_key(&player)
+            || frame >= self.frame
+            || frame + CHECKSUM_WINDOW < self.frame
+        {
+            return;
+        }
+
+        self.checksums
+            .entry(frame)
+            .or_default()
+            .insert(player, checksum);
+        self.compare_checksums(frame);
+    }
+
+    /// Sends the current frame if it is ready, to call at each tick.
+    pub fn update(&mut self, game_time: GameTime) {
+        if self.players.is_empty() {
+            return;
+        }
+
+        let frame_start = *self.frame_start.get_or_insert(game_time.timestamp);
+        let missing = self.missing();
+
+        if !missing.is_empty() {
+            if game_time.timestamp - frame_start < self.deadline {
+                return;
+            }
+
+            if !self.stalled {
+                self.stalled = true;
+                self.events.push(LockstepEvent::Stall {
+                    frame: self.frame,
+                    missing,
+                });
+            }
+
+            if self.stall_policy == StallPolicy::Wait {
+                return;
+            }
+        }
+
+        self.send_frame();
+        self.frame_start = Some(game_time.timestamp);
+    }
+
+    /// Returns the events since the last call.
+    pub fn drain_events(&mut self) -> std::vec::Drain<'_, LockstepEvent> {
+        self.events.drain(..)
+    }
+
+    // Compares the checksums of a frame once every player sent its checksum.
+    fn compare_checksums(&mut self, frame: u32) {
+        let complete = self
+            .checksums
+            .get(&frame)
+            .is_some_and(|checksums| checksums.len() >= self.players.len());
+
+        if !complete {
+            return;
+        }
+
+        if let Some(checksums) = self.checksums.remove(&frame) {
+            let mut values = checksums.values();
+            let first = values.next();
+
+            if values.any(|checksum| Some(checksum) != first) {
+                self.events.push(LockstepEvent::Desync {
+                    frame,
+                    checksums: checksums.into_iter().collect(),
+                });
+            }
+        }
+    }
+
+    fn missing(&self) -> Vec<u64> {
+        let inputs = self.inputs.get(&self.frame);
+
+        self.players
+            .keys()
+            .filter(|id| !inputs.is_some_and(|inputs| inputs.contains_key(id)))
+            .copied()
+            .collect()
+    }
+
+    fn send_frame(&mut self) {
+        let inputs = self.inputs.remove(&self.frame).unwrap_or_default();
+
+        let mut payload = Vec::new();
+        payload.put_u32(self.frame);
+        payload.put_u16(inputs.len() as u16);
+        inputs.iter().for_each(|(player, input)| {
+            payload.put_u64(*player);
+            payload.put_u32(input.len() as u32);
+            payload.extend_from_slice(input);
+        });
+
+        let bytes = Arc::new(to_bytes(self.cmd, Some(&payload)));
+        self.players.values().for_each(|socket_tools| {
+            socket_tools.send_data(ContainerBytes::Arc(Arc::clone(&bytes)))
+        });
+
+        self.events.push(LockstepEvent::Frame(self.frame));
+        self.frame += 1;
+        self.stalled = false;
+
+        if let Some(oldest) = self.frame.checked_sub(CHECKSUM_WINDOW) {
+            self.checksums = self.checksums.split_off(&oldest);
+        }
+    }
+}
+
+/// A frame received by a player.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockstepFrame {
+    pub frame: u32,
+    /// The inputs sorted by player.
+    pub inputs: Vec<(u64, Vec<u8>)>,
+}
+
+impl LockstepFrame {
+    /// Reads a frame sent by `Lockstep` (client side).
+    pub fn decode(payload: &[u8]) -> Result<Self> {
+        let mut data = payload;
+        let mut read = |len: usize| -> Result<&[u8]> {
+            if data.len() < len {
+                return Err(Error::PacketPayload);
+            }
+            let (bytes, rest) = data.split_at(len);
+            data = rest;
+            Ok(bytes)
+        };
+
+        let frame = u32::from_be_bytes(read(4)?.try_into().map_err(|_| Error::PacketPayload)?);
+        let count = u16::from_be_bytes(read(2)?.try_into().map_err(|_| Error::PacketPayload)?);
+
+        let mut inputs = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let player = u64::from_be_bytes(read(8)?.try_into().map_err(|_| Error::PacketPayload)?);
+            let len =
+                u32::from_be_bytes(read(4)?.try_into().map_err(|_| Error::PacketPayload)?) as usize;
+            inputs.push((player, read(len)?.to_vec()));
+        }
+
+        Ok(Self { frame, inputs })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::world_socket::WriterMessage;
+    use tokio::sync::mpsc::UnboundedReceiver;
+
+    fn game_time(timestamp: i64) -> GameTime {
+        let mut game_time = GameTime::new();
+        game_time.timestamp = timestamp;
+        game_time
+    }
+
+    fn received(rx: &mut UnboundedReceiver<WriterMessage>) -> Option<LockstepFrame> {
+        match rx.try_recv() {
+            Ok(WriterMessage::Send(bytes, _)) => {
+                Some(LockstepFrame::decode(&bytes.bytes()[6..]).unwrap())
+            }
+            _ => None,
+        }
+    }
+
+    fn lockstep(policy: StallPolicy) -> (Lockstep, Vec<UnboundedReceiver<WriterMessage>>) {
+        let mut lockstep = Lockstep::new(20, Duration::from_millis(100)).with_stall_policy(policy);
+        let (first, rx) = SocketTools::for_test(1);
+        let (second, rx_second) = SocketTools::for_test(2);
+        lockstep.add_player(1, first);
+        lockstep.add_player(2, second);
+        (lockstep, vec![rx, rx_second])
+    }
+
+    #[test]
+    fn test_frame() {
+        let (mut lockstep, mut rx) = lockstep(StallPolicy::Skip);
+
+        assert_eq!(lockstep.submit_input(2, 0, &[2]), InputStatus::Accepted);
+        lockstep.update(game_time(1000));
+        assert!(received(&mut rx[0]).is_none());
+
+        assert_eq!(lockstep.submit_input(1, 0, &[1]), InputStatus::Accepted);
+        assert_eq!(lockstep.submit_input(1, 0, &[1]), InputStatus::Rejected);
+        assert_eq!(lockstep.submit_input(3, 0, &[1]), InputStatus::Rejected);
+        lockstep.update(game_time(1010));
+
+        let expected = LockstepFrame {
+            frame: 0,
+            inputs: vec![(1, vec![1]), (2, vec![2])],
+        };
+        assert_eq!(received(&mut rx[0]).unwrap(), expected);
+        assert_eq!(received(&mut rx[1]).unwrap(), expected);
+        assert_eq!(lockstep.frame(), 1);
+        assert_eq!(
+            lockstep.drain_events().collect::<Vec<_>>(),
+            [LockstepEvent::Frame(0)]
+        );
+    }
+
+    #[test]
+    fn test_skip() {
+        let (mut lockstep, mut rx) = lockstep(StallPolicy::Skip);
+
+        lockstep.submit_input(1, 0, &[1]);
+        lockstep.update(game_time(1000));
+        lockstep.update(game_time(1100));
+
+        assert_eq!(
+            received(&mut rx[0]).unwrap(),
+            LockstepFrame {
+                frame: 0,
+                inputs: vec![(1, vec![1])],
+            }
+        );
+        assert_eq!(
+            lockstep.drain_events().collect::<Vec<_>>(),
+            [
+                LockstepEvent::Stall {
+                    frame: 0,
+                    missing: vec![2]
+                },
+                LockstepEvent::Frame(0)
+            ]
+        );
+
+        // The input of the frame 0 is added to the frame 1.
+        assert_eq!(lockstep.submit_input(2, 0, &[2]), InputStatus::Late(1));
+    }
+
+    #[test]
+    fn test_wait() {
+        let (mut lockstep, mut rx) = lockstep(StallPolicy::Wait);
+
+        lockstep.submit_input(1, 0, &[1]);
+        lockstep.update(game_time(1000));
+        lockstep.update(game_time(1100));
+        lockstep.update(game_time(1200));
+        assert!(received(&mut rx[0]).is_none());
+        assert_eq!(lockstep.drain_events().count(), 1);
+
+        lockstep.remove_player(2);
+        lockstep.update(game_time(1225));
+        assert_eq!(received(&mut rx[0]).unwrap().inputs, [(1, vec![1])]);
+    }
+
+    #[test]
+    fn test_desync() {
+        let (mut lockstep, _rx) = lockstep(StallPolicy::Skip);
+        lockstep.update(game_time(1000));
+        lockstep.update(game_time(1100));
+        lockstep.update(game_time(1200));
+        lockstep.drain_events();
+
+        lockstep.submit_checksum(1, 0, 10);
+        lockstep.submit_checksum(2, 0, 10);
+        lockstep.submit_checksum(1, 1, 10);
+        lockstep.submit_checksum(2, 1, 11);
+        // Not sent yet.
+        lockstep.submit_checksum(2, 5, 11);
+
+        assert_eq!(
+            lockstep.drain_events().collect::<Vec<_>>(),
+            [LockstepEvent::Desync {
+                frame: 1,
+                checksums: vec![(1, 10), (2, 11)]
+            }]
+        );
+    }
+
+    #[test]
+    fn test_checksum_removed_player() {
+        let (mut lockstep, _rx) = lockstep(StallPolicy::Skip);
+        lockstep.update(game_time(1000));
+        lockstep.update(game_time(1100));
+        lockstep.update(game_time(1200));
+        lockstep.drain_events();
+
+        // The frame 0 only waits for the player 2.
+        lockstep.submit_checksum(1, 0, 10);
+        lockstep.remove_player(2);
+        assert!(lockstep.checksums.is_empty());
+
+        // The frame 1 is compared without the checksum of the removed player.
+        let (third, _rx_third) = SocketTools::for_test(3);
+        lockstep.add_player(3, third);
+        lockstep.submit_checksum(1, 1, 10);
+        lockstep.submit_checksum(3, 1, 11);
+        assert_eq!(
+            lockstep.drain_events().collect::<Vec<_>>(),
+            [LockstepEvent::Desync {
+                frame: 1,
+                checksums: vec![(1, 10), (3, 11)]
+            }]
+        );
+        assert!(lockstep.checksums.is_empty());
+    }
+
+    #[test]
+    fn test_checksum_window() {
+        let (mut lockstep, _rx) = lockstep(StallPolicy::Skip);
+        lockstep.update(game_time(0));
+        lockstep.update(game_time(100));
+
+        // The player 2 never sends its checksum.
+        lockstep.submit_checksum(1, 0, 10);
+        assert_eq!(lockstep.checksums.len(), 1);
+
+        (2..=CHECKSUM_WINDOW as i64 + 1).for_each(|i| lockstep.update(game_time(i * 100)));
+        assert_eq!(lockstep.frame(), CHECKSUM_WINDOW + 1);
+        assert!(lockstep.checksums.is_empty());
+
+        // Too old.
+        lockstep.submit_checksum(1, 0, 10);
+        assert!(lockstep.checksums.is_empty());
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(
+            LockstepFrame::decode(&[0, 0, 0, 1, 0, 1, 0]).unwrap_err(),
+            Error::PacketPayload
+        );
+        assert_eq!(
+            LockstepFrame::decode(&[0, 0, 0, 1, 0, 0]).unwrap(),
+            LockstepFrame {
+                frame: 1,
+                inputs: Vec::new()
+            }
+        );
+    }
+}
@@ -1,11 +1,35 @@
+mod aoi;
+pub use aoi::{AoiEvent, AoiGrid};
+
+mod clock;
+pub use clock::{Clock, ManualClock, SystemClock};
+
 mod event_processor;
-pub use event_processor::{Event, EventProcessor};
+pub use event_processor::{Event, EventAction, EventId, EventProcessor, Recurrence};
 
 pub(crate) mod game_loop;
-pub use game_loop::GameLoop;
+pub use game_loop::{GameLoop, GameLoopControl};
 
 pub(crate) mod game_time;
 pub use game_time::GameTime;
 
+mod history;
+pub use history::{History, Interpolate};
+
 mod interval_mgr;
 pub use interval_mgr::{IntervalExecutor, IntervalMgr};
+
+mod interval_set;
+pub use interval_set::{IntervalConfig, IntervalSet};
+
+mod lockstep;
+pub use lockstep::{InputStatus, Lockstep, LockstepEvent, LockstepFrame, StallPolicy};
+
+mod replication;
+pub use replication::{Replicator, Snapshot};
+
+mod scheduler;
+pub use scheduler::{Scheduler, TaskHandle};
+
+mod timing_wheel;
+pub use timing_wheel::WheelEventProcessor;
@@ -0,0 +1,448 @@
+use crate::error::{Error, Result};
+use crate::io::read::MAX_SIZE;
+use bytes::BufMut;
+use easy_pool::{PoolObjectContainer, PoolSegQueue};
+use once_cell::sync::Lazy;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    convert::TryInto,
+    mem,
+    sync::Arc,
+};
+
+static POOL_VEC_SNAPSHOT: Lazy<Arc<PoolSegQueue<Vec<u8>>>> =
+    Lazy::new(|| Arc::new(PoolSegQueue::new(1024)));
+
+const FULL: u8 = 0;
+const DELTA: u8 = 1;
+
+/// The state of the entities at a tick (`GameTime::timestamp`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    tick: i64,
+    entities: BTreeMap<u64, Vec<u8>>,
+}
+
+impl Snapshot {
+    /// Creates an empty snapshot.
+    /// ```rust, no_run
+    /// use rollo::game::{GameTime, Snapshot};
+    ///
+    /// let game_time = GameTime::new();
+    /// let mut snapshot = Snapshot::new(game_time.timestamp);
+    /// snapshot.insert(1, &[10, 20]);
+    /// ```
+    pub fn new(tick: i64) -> Self {
+        Self {
+            tick,
+            entities: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the serialized state of an entity.
+    pub fn insert(&mut self, id: u64, state: &[u8]) {
+        self.entities.insert(id, state.to_vec());
+    }
+
+    /// Returns the serialized state of an entity.
+    pub fn get(&self, id: u64) -> Option<&[u8]> {
+        self.entities.get(&id).map(Vec::as_slice)
+    }
+
+    /// Returns the tick of the snapshot.
+    pub fn tick(&self) -> i64 {
+        self.tick
+    }
+
+    /// Returns the amount of entities.
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Returns true if there is no entity.
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Rebuilds a snapshot from a payload written by `Replicator::encode` (client side).
+    ///
+    /// The baseline is the last acknowledged snapshot, required to decode a delta.
+    pub fn decode(payload: &[u8], baseline: Option<&Snapshot>) -> Result<Self> {
+        let mut cursor = Cursor { data: payload };
+
+        let kind = cursor.u8()?;
+        let tick = cursor.i64()?;
+        let baseline_tick = cursor.i64()?;
+
+        let mut snapshot = match kind {
+            FULL => Snapshot::new(tick),
+            DELTA => match baseline {
+                Some(baseline) if baseline.tick == baseline_tick => Snapshot {
+                    tick,
+                    entities: baseline.entities.clone(),
+                },
+                _ => return Err(Error::PacketPayload),
+            },
+            _ => return Err(Error::PacketPayload),
+        };
+
+        for _ in 0..cursor.u32()? {
+            let id = cursor.u64()?;
+            let len = cursor.u32()? as usize;
+            snapshot.entities.insert(id, cursor.bytes(len)?.to_vec());
+        }
+
+        for _ in 0..cursor.u32()? {
+            snapshot.entities.remove(&cursor.u64()?);
+        }
+
+        Ok(snapshot)
+    }
+}
+
+/// # Replicator
+/// Keeps the last snapshots and the last snapshot acknowledged by each session,
+/// then encodes for each session the changes since its baseline.
+///
+/// A lost packet doesn't need to be resent: the next delta is still computed against the
+/// last acknowledged snapshot and contains its changes. Sessions without a baseline
+/// (or with a baseline too old) receive a full snapshot.
+///
+/// A packet is limited to 14 KiB, `encode` fails with `Error::PacketSize` beyond it.
+/// Big snapshots are sent with `encode_payload` and `SocketTools::send_stream`.
+/// ## Usage
+/// ```rust, no_run
+/// use rollo::game::{GameTime, Replicator, Snapshot};
+///
+/// let mut replicator = Replicator::new(32);
+///
+/// // In World::update
+/// let game_time = GameTime::new();
+/// let mut snapshot = Snapshot::new(game_time.timestamp);
+/// snapshot.insert(1, &[10, 20]);
+/// replicator.push(snapshot);
+///
+/// // For each session
+/// match replicator.encode(1, 50) {
+///     Ok(Some(bytes)) => {
+///         // socket_tools.send_data(bytes.into());
+///     }
+///     Ok(None) => {}
+///     Err(_) => {
+///         // Too big for a packet.
+///         let payload = replicator.encode_payload(1).unwrap();
+///         // socket_tools.send_stream(Channel::DEFAULT, 50, &payload);
+///     }
+/// }
+///
+/// // When the session acknowledges a tick
+/// replicator.ack(1, game_time.timestamp);
+/// ```
+#[derive(Debug)]
+pub struct Replicator {
+    max_snapshots: usize,
+    snapshots: VecDeque<Snapshot>,
+    acks: HashMap<u64, i64>,
+}
+
+impl Replicator {
+    /// Creates the replicator with the amount of snapshots kept as baselines.
+    pub fn new(max_snapshots: usize) -> Self {
+        Self {
+            max_snapshots: max_snapshots.max(1),
+            snapshots: VecDeque::with_capacity(max_snapshots),
+            acks: HashMap::new(),
+        }
+    }
+
+    /// Adds the snapshot of the current tick.
+    pub fn push(&mut self, snapshot: Snapshot) {
+        debug_assert!(self
+            .snapshots
+            .back()
+            .map_or(true, |last| last.tick < snapshot.tick));
+
+        if self.snapshots.len() == self.max_snapshots {
+            self.snapshots.pop_front();
+        }
+
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// Returns the last snapshot.
+    pub fn last(&self) -> Option<&Snapshot> {
+        self.snapshots.back()
+    }
+
+    /// The session acknowledged a tick.
+    pub fn ack(&mut self, session_id: u64, tick: i64) {
+        let ack = self.acks.entry(session_id).or_insert(tick);
+        if tick > *ack {
+            *ack = tick;
+        }
+    }
+
+    /// Returns the last tick acknowledged by the session.
+    pub fn acked(&self, session_id: u64) -> Option<i64> {
+        self.acks.get(&session_id).copied()
+    }
+
+    /// Forgets a session (closed).
+    pub fn remove_session(&mut self, session_id: u64) {
+        self.acks.remove(&session_id);
+    }
+
+    /// Encodes the last snapshot for a session as a packet with the command.
+    ///
+    /// Returns None if there is no snapshot, or `Error::PacketSize` if the packet is bigger than
+    /// the maximum size (`encode_payload` for `SocketTools::send_stream`).
+    pub fn encode(
+        &self,
+        session_id: u64,
+        cmd: u16,
+    ) -> Result<Option<PoolObjectContainer<Vec<u8>>>> {
+        let mut vec = POOL_VEC_SNAPSHOT.create();
+        debug_assert!(vec.is_empty());
+
+        vec.put_u32(0);
+        vec.put_u16(cmd);
+
+        if !self.write(session_id, &mut vec) {
+            return Ok(None);
+        }
+
+        let size = vec.len() - HEADER_SIZE;
+        if size >= MAX_SIZE {
+            return Err(Error::PacketSize);
+        }
+
+        vec[..mem::size_of::<u32>()].copy_from_slice(&(size as u32).to_be_bytes());
+
+        Ok(Some(vec))
+    }
+
+    /// Encodes the last snapshot for a session without header, for any size.
+    ///
+    /// Returns None if there is no snapshot.
+    pub fn encode_payload(&self, session_id: u64) -> Option<Vec<u8>> {
+        let mut vec = Vec::new();
+
+        if self.write(session_id, &mut vec) {
+            Some(vec)
+        } else {
+            None
+        }
+    }
+
+    // Writes the last snapshot (full or delta), returns false if there is no snapshot.
+    fn write(&self, session_id: u64, vec: &mut Vec<u8>) -> bool {
+        let snapshot = match self.snapshots.back() {
+            Some(snapshot) => snapshot,
+            None => return false,
+        };
+        let baseline = self
+            .acked(session_id)
+            .and_then(|tick| self.snapshots.iter().find(|s| s.tick == tick));
+
+        if let Some(baseline) = baseline {
+            vec.put_u8(DELTA);
+            vec.put_i64(snapshot.tick);
+            vec.put_i64(baseline.tick);
+
+            let changed: Vec<(&u64, &Vec<u8>)> = snapshot
+                .entities
+                .iter()
+                .filter(|(id, state)| baseline.entities.get(id) != Some(state))
+                .collect();
+            let removed: Vec<&u64> = baseline
+                .entities
+                .keys()
+                .filter(|id| !snapshot.entities.contains_key(id))
+                .collect();
+
+            put_entities(vec, changed.into_iter());
+            vec.put_u32(removed.len() as u32);
+            removed.into_iter().for_each(|id| vec.put_u64(*id));
+        } else {
+            vec.put_u8(FULL);
+            vec.put_i64(snapshot.tick);
+            vec.put_i64(0);
+
+            put_entities(vec, snapshot.entities.iter());
+            vec.put_u32(0);
+        }
+
+        true
+    }
+}
+
+const HEADER_SIZE: usize = mem::size_of::<u32>() + mem::size_of::<u16>();
+
+fn put_entities<'a>(
+    vec: &mut Vec<u8>,
+    entities: impl ExactSizeIterator<Item = (&'a u64, &'a Vec<u8>)>,
+) {
+    vec.put_u32(entities.len() as u32);
+    entities.for_each(|(id, state)| {
+        vec.put_u64(*id);
+        vec.put_u32(state.len() as u32);
+        vec.extend_from_slice(state);
+    });
+}
+
+struct Cursor<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(Error::PacketPayload);
+        }
+
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(
+            self.bytes(4)?
+                .try_into()
+                .map_err(|_| Error::PacketPayload)?,
+        ))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_be_bytes(
+            self.bytes(8)?
+                .try_into()
+                .map_err(|_| Error::PacketPayload)?,
+        ))
+    }
+
+    fn i64(&mut self) -> Result<i64> {
+        Ok(i64::from_be_bytes(
+            self.bytes(8)?
+                .try_into()
+                .map_err(|_| Error::PacketPayload)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(tick: i64, entities: &[(u64, &[u8])]) -> Snapshot {
+        let mut snapshot = Snapshot::new(tick);
+        entities
+            .iter()
+            .for_each(|(id, state)| snapshot.insert(*id, state));
+        snapshot
+    }
+
+    fn payload(bytes: &[u8]) -> &[u8] {
+        let size = u32::from_be_bytes(bytes[..4].try_into().unwrap()) as usize;
+        assert_eq!(size, bytes.len() - HEADER_SIZE);
+        &bytes[HEADER_SIZE..]
+    }
+
+    #[test]
+    fn test_full() {
+        let mut replicator = Replicator::new(4);
+        assert!(replicator.encode(1, 50).unwrap().is_none());
+
+        let first = snapshot(100, &[(1, &[1, 2]), (2, &[3])]);
+        replicator.push(first.clone());
+
+        let bytes = replicator.encode(1, 50).unwrap().unwrap();
+        assert_eq!(u16::from_be_bytes(bytes[4..6].try_into().unwrap()), 50);
+        assert_eq!(payload(&bytes)[0], FULL);
+        assert_eq!(Snapshot::decode(payload(&bytes), None).unwrap(), first);
+    }
+
+    #[test]
+    fn test_delta() {
+        let mut replicator = Replicator::new(4);
+        let first = snapshot(100, &[(1, &[1, 2]), (2, &[3]), (3, &[4])]);
+        replicator.push(first.clone());
+        replicator.ack(1, 100);
+
+        let second = snapshot(125, &[(1, &[1, 2]), (2, &[5]), (4, &[6])]);
+        replicator.push(second.clone());
+
+        let bytes = replicator.encode(1, 50).unwrap().unwrap();
+        let data = payload(&bytes);
+        assert_eq!(data[0], DELTA);
+        // Entity 1 is not sent again.
+        assert!(bytes.len() < replicator.encode(2, 50).unwrap().unwrap().len());
+        assert_eq!(Snapshot::decode(data, Some(&first)).unwrap(), second);
+        assert!(Snapshot::decode(data, None).is_err());
+
+        // The second packet is lost: the third delta still uses the first baseline.
+        let third = snapshot(150, &[(1, &[9, 2]), (4, &[6])]);
+        replicator.push(third.clone());
+        let bytes = replicator.encode(1, 50).unwrap().unwrap();
+        assert_eq!(
+            Snapshot::decode(payload(&bytes), Some(&first)).unwrap(),
+            third
+        );
+    }
+
+    #[test]
+    fn test_baseline_too_old() {
+        let mut replicator = Replicator::new(2);
+        replicator.push(snapshot(100, &[(1, &[1])]));
+        replicator.ack(1, 100);
+        replicator.push(snapshot(125, &[(1, &[2])]));
+        replicator.push(snapshot(150, &[(1, &[3])]));
+
+        let bytes = replicator.encode(1, 50).unwrap().unwrap();
+        assert_eq!(payload(&bytes)[0], FULL);
+    }
+
+    #[test]
+    fn test_ack() {
+        let mut replicator = Replicator::new(2);
+        replicator.ack(1, 100);
+        replicator.ack(1, 50);
+        assert_eq!(replicator.acked(1), Some(100));
+        replicator.ack(1, 150);
+        assert_eq!(replicator.acked(1), Some(150));
+        replicator.remove_session(1);
+        assert_eq!(replicator.acked(1), None);
+    }
+
+    #[test]
+    fn test_max_size() {
+        let mut replicator = Replicator::new(2);
+        assert!(replicator.encode_payload(1).is_none());
+
+        // 1000 entities * (8 + 4 + 8) bytes, more than 14 KiB.
+        let mut big = Snapshot::new(100);
+        (0..1000).for_each(|id| big.insert(id, &id.to_be_bytes()));
+        replicator.push(big.clone());
+
+        assert_eq!(replicator.encode(1, 50).unwrap_err(), Error::PacketSize);
+
+        let data = replicator.encode_payload(1).unwrap();
+        assert!(data.len() >= MAX_SIZE);
+        assert_eq!(Snapshot::decode(&data, None).unwrap(), big);
+
+        // The delta is small enough for a packet.
+        replicator.ack(1, 100);
+        let mut next = big.clone();
+        next.tick = 125;
+        next.insert(1, &[1]);
+        replicator.push(next.clone());
+
+        let bytes = replicator.encode(1, 50).unwrap().unwrap();
+        assert_eq!(Snapshot::decode(payload(&bytes), Some(&big)).unwrap(), next);
+    }
+}
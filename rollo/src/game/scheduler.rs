@@ -0,0 +1,322 @@
+use super::{event_processor::millis, Clock, SystemClock};
+use parking_lot::Mutex;
+use std::{
+    cmp::Ordering as CmpOrdering,
+    collections::BinaryHeap,
+    fmt,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+enum Job {
+    Closure(Box<dyn FnOnce() + Send>),
+    Future(Pin<Box<dyn Future<Output = ()> + Send>>),
+}
+
+struct Task {
+    time: i64,
+    id: u64,
+    cancelled: Arc<AtomicBool>,
+    job: Job,
+}
+
+impl PartialEq for Task {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == CmpOrdering::Equal
+    }
+}
+
+impl Eq for Task {}
+
+impl PartialOrd for Task {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Task {
+    // Reversed, the first task of the heap is the oldest.
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        (other.time, other.id).cmp(&(self.time, self.id))
+    }
+}
+
+/// Handle of a task added to a `Scheduler`.
+#[derive(Debug, Clone)]
+pub struct TaskHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl TaskHandle {
+    /// Cancels the task if it is not executed yet.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    /// Returns true if the task was cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+}
+
+/// # Scheduler
+/// Runs closures and futures on the game loop, after a delay, in time order.
+///
+/// The tasks can be added from any thread, the GameLoop runs the scheduler of the world
+/// (`World::scheduler`) at each tick, after `World::update`.
+/// A future is awaited by the game loop: the tick waits for it.
+///
+/// The delays are counted in the time of the world: they are scaled with the speed of the game
+/// loop and stopped while it is paused. A delay starts at the time of the current tick.
+/// ## Usage
+/// ```rust, no_run
+/// use rollo::game::Scheduler;
+/// use std::time::Duration;
+///
+/// let scheduler = Scheduler::new();
+///
+/// // In WorldSession::on_message
+/// let handle = scheduler.schedule(Duration::from_secs(5), || println!("5 seconds later"));
+/// scheduler.schedule_future(Duration::from_secs(1), async {
+///     println!("1 second later");
+/// });
+///
+/// handle.cancel();
+/// ```
+pub struct Scheduler {
+    tx: UnboundedSender<Task>,
+    rx: Mutex<UnboundedReceiver<Task>>,
+    tasks: Mutex<BinaryHeap<Task>>,
+    counter: AtomicU64,
+    // Time of the world at the last tick (ms).
+    time: AtomicI64,
+}
+
+impl Scheduler {
+    /// Creates the scheduler.
+    pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    /// Creates the scheduler with the clock of the game loop (`ManualClock` in the tests).
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        let (tx, rx) = unbounded_channel();
+
+        Self {
+            tx,
+            rx: Mutex::new(rx),
+            tasks: Mutex::new(BinaryHeap::new()),
+            counter: AtomicU64::new(0),
+            time: AtomicI64::new(clock.now().as_millis() as i64),
+        }
+    }
+
+    /// Runs the closure on the game loop after the delay.
+    pub fn schedule<F>(&self, delay: Duration, f: F) -> TaskHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.push(delay, Job::Closure(Box::new(f)))
+    }
+
+    /// Awaits the future on the game loop after the delay.
+    pub fn schedule_future<F>(&self, delay: Duration, future: F) -> TaskHandle
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.push(delay, Job::Future(Box::pin(future)))
+    }
+
+    /// Returns the amount of tasks waiting for their execution (received by the game loop).
+    pub fn len(&self) -> usize {
+        self.tasks.lock().len()
+    }
+
+    /// Returns true if there is no task waiting (received by the game loop).
+    pub fn is_empty(&self) -> bool {
+        self.tasks.lock().is_empty()
+    }
+
+    /// Sets the time of the world (milliseconds), the start of the delays of the new tasks.
+    pub(crate) fn set_time(&self, time: i64) {
+        self.time.store(time, Ordering::Release);
+    }
+
+    /// Executes the tasks planned before the time of the world (milliseconds).
+    pub(crate) async fn run(&self, time: i64) {
+        self.set_time(time);
+        self.receive();
+
+        while let Some(task) = self.pop(time) {
+            if task.cancelled.load(Ordering::Acquire) {
+                continue;
+            }
+
+            match task.job {
+                Job::Closure(f) => f(),
+                Job::Future(future) => future.await,
+            }
+        }
+    }
+
+    fn push(&self, delay: Duration, job: Job) -> TaskHandle {
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let task = Task {
+            time: self
+                .time
+                .load(Ordering::Acquire)
+                .saturating_add(millis(delay)),
+            id: self.counter.fetch_add(1, Ordering::Relaxed),
+            cancelled: Arc::clone(&cancelled),
+            job,
+        };
+
+        // The receiver is owned by the scheduler.
+        let _ = self.tx.send(task);
+
+        TaskHandle { cancelled }
+    }
+
+    fn receive(&self) {
+        let mut rx = self.rx.lock();
+        let mut tasks = self.tasks.lock();
+
+        while let Ok(task) = rx.try_recv() {
+            tasks.push(task);
+        }
+    }
+
+    fn pop(&self, time: i64) -> Option<Task> {
+        let mut tasks = self.tasks.lock();
+
+        match tasks.peek() {
+            Some(task) if task.time <= time => tasks.pop(),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field("counter", &self.counter)
+            .field("time", &self.time)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::ManualClock;
+    use std::sync::atomic::AtomicI32;
+
+    fn now() -> i64 {
+        SystemClock.now().as_millis() as i64
+    }
+
+    #[tokio::test]
+    async fn test_order() {
+        let scheduler = Scheduler::new();
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        for (i, delay) in [300, 100, 200].iter().copied().enumerate() {
+            let order = Arc::clone(&order);
+            scheduler.schedule(Duration::from_millis(delay), move || order.lock().push(i));
+        }
+
+        let time = now();
+        scheduler.run(time).await;
+        assert!(order.lock().is_empty());
+        assert_eq!(scheduler.len(), 3);
+
+        scheduler.run(time + 250).await;
+        assert_eq!(*order.lock(), [1, 2]);
+
+        scheduler.run(time + 1000).await;
+        assert_eq!(*order.lock(), [1, 2, 0]);
+        assert!(scheduler.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_future() {
+        let scheduler = Scheduler::new();
+        let value = Arc::new(AtomicI32::new(0));
+
+        let clone = Arc::clone(&value);
+        scheduler.schedule_future(Duration::ZERO, async move {
+            tokio::task::yield_now().await;
+            clone.store(10, Ordering::SeqCst);
+        });
+
+        scheduler.run(now()).await;
+        assert_eq!(value.load(Ordering::SeqCst), 10);
+    }
+
+    #[tokio::test]
+    async fn test_cancel() {
+        let scheduler = Scheduler::new();
+        let value = Arc::new(AtomicI32::new(0));
+
+        let clone = Arc::clone(&value);
+        let handle = scheduler.schedule(Duration::ZERO, move || {
+            clone.store(10, Ordering::SeqCst);
+        });
+        handle.cancel();
+        assert!(handle.is_cancelled());
+
+        scheduler.run(now()).await;
+        assert_eq!(value.load(Ordering::SeqCst), 0);
+        assert!(scheduler.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_manual_clock() {
+        let clock = Arc::new(ManualClock::starting_at(Duration::from_secs(10)));
+        let scheduler = Scheduler::with_clock(clock.clone());
+        let value = Arc::new(AtomicI32::new(0));
+
+        let clone = Arc::clone(&value);
+        scheduler.schedule(Duration::from_millis(100), move || {
+            clone.store(10, Ordering::SeqCst);
+        });
+
+        clock.advance(Duration::from_millis(99));
+        scheduler.run(clock.now().as_millis() as i64).await;
+        assert_eq!(value.load(Ordering::SeqCst), 0);
+
+        clock.advance(Duration::from_millis(1));
+        scheduler.run(clock.now().as_millis() as i64).await;
+        assert_eq!(value.load(Ordering::SeqCst), 10);
+    }
+
+    #[tokio::test]
+    async fn test_other_thread() {
+        let scheduler = Arc::new(Scheduler::new());
+        let value = Arc::new(AtomicI32::new(0));
+
+        let (clone, value_clone) = (Arc::clone(&scheduler), Arc::clone(&value));
+        std::thread::spawn(move || {
+            clone.schedule(Duration::ZERO, move || {
+                value_clone.store(10, Ordering::SeqCst);
+            });
+        })
+        .join()
+        .unwrap();
+
+        scheduler.run(now()).await;
+        assert_eq!(value.load(Ordering::SeqCst), 10);
+    }
+}
@@ -0,0 +1,554 @@
+use super::event_processor::{millis, Event, EventId, Recurrence, ScheduledEvent};
+use std::{collections::HashMap, marker::PhantomData, time::Duration};
+
+const SLOT_BITS: usize = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const SLOT_MASK: u64 = (SLOTS - 1) as u64;
+const LEVELS: usize = 6;
+/// About 795 days.
+const MAX_DURATION: u64 = (1 << (SLOT_BITS * LEVELS)) - 1;
+/// Shift of the slots of the last level.
+const TOP_SHIFT: usize = SLOT_BITS * (LEVELS - 1);
+
+/// # Wheel Event Processor
+/// Same API as `EventProcessor`, with a hierarchical timing wheel (milliseconds):
+/// adding and cancelling an event are O(1) and an update only visits the expired slots.
+///
+/// The time given to `update` must not go backward. The events planned beyond the range of the
+/// wheel (about 795 days) wait in an overflow list until they are in range.
+/// ## Usage
+/// ```rust, no_run
+/// use rollo::game::{Event, EventAction, WheelEventProcessor};
+/// use std::time::Duration;
+///
+/// let mut event_processor = WheelEventProcessor::<MyEvent>::new(1000000);
+/// let id = event_processor.add_event(MyEvent, Duration::from_secs(5));
+/// event_processor.update(1005000, &mut ());
+///
+/// struct MyEvent;
+///
+/// impl Event for MyEvent {
+///     fn on_execute(&mut self, _ctx: &mut (), _diff: i64) -> EventAction<Self> {
+///         EventAction::Next
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct WheelEventProcessor<T, Ctx = ()>
+where
+    T: Event<Ctx>,
+{
+    origin: i64,
+    m_time: i64,
+    levels: Vec<Level>,
+    /// The events beyond the last level.
+    overflow: Vec<(EventId, u32)>,
+    entries: HashMap<EventId, WheelEntry<T>>,
+    next_id: u64,
+    _ctx: PhantomData<fn(&mut Ctx)>,
+}
+
+#[derive(Debug)]
+struct WheelEntry<T> {
+    when: i64,
+    generation: u32,
+    scheduled: ScheduledEvent<T>,
+}
+
+#[derive(Debug)]
+struct Level {
+    occupied: u64,
+    /// The cancelled and rescheduled events are removed lazily (old generation).
+    slots: Vec<Vec<(EventId, u32)>>,
+}
+
+impl Level {
+    fn new() -> Self {
+        Self {
+            occupied: 0,
+            slots: (0..SLOTS).map(|_| Vec::new()).collect(),
+        }
+    }
+}
+
+impl<T, Ctx> WheelEventProcessor<T, Ctx>
+where
+    T: Event<Ctx>,
+{
+    /// ## Create an event processor
+    /// Time (current time in milliseconds)
+    /// ### Examples
+    /// ```rust, no_run
+    /// use rollo::game::{Event, EventAction, WheelEventProcessor};
+    ///
+    /// let event_processor = WheelEventProcessor::<MyEvent>::new(1000000);
+    ///
+    /// struct MyEvent;
+    ///
+    /// impl Event for MyEvent {
+    ///     fn on_execute(&mut self, _ctx: &mut (), _diff: i64) -> EventAction<Self> {
+    ///         EventAction::Next
+    ///     }
+    /// }
+    /// ```
+    pub fn new(time: i64) -> Self {
+        Self {
+            origin: time,
+            m_time: time,
+            levels: (0..LEVELS).map(|_| Level::new()).collect(),
+            overflow: Vec::new(),
+            entries: HashMap::new(),
+            next_id: 0,
+            _ctx: PhantomData,
+        }
+    }
+
+    /// ## Update events
+    /// Time (current time in milliseconds), the context is given to the executed events.
+    pub fn update(&mut self, time: i64, ctx: &mut Ctx) {
+        let time = time.max(self.m_time);
+        let target = self.elapsed(time);
+        let mut rescheduled = Vec::new();
+        let mut chained = Vec::new();
+
+        loop {
+            self.place_overflow();
+
+            let (level, slot, deadline) = match self.next_expiration() {
+                Some(expiration) => expiration,
+                None => break,
+            };
+
+            if deadline > target {
+                break;
+            }
+
+            self.m_time = self.origin + deadline as i64;
+            self.levels[level].occupied &= !(1 << slot);
+            let ids = std::mem::take(&mut self.levels[level].slots[slot]);
+
+            for (id, generation) in ids {
+                let when = match self.entries.get(&id) {
+                    Some(entry) if entry.generation == generation => entry.when,
+                    _ => continue,
+                };
+
+                if when > self.m_time {
+                    // Moves to a lower level.
+                    self.place(id, generation, when);
+                    continue;
+                }
+
+                if let Some(mut entry) = self.entries.remove(&id) {
+                    let (new_time, event) = entry.scheduled.run(ctx, entry.when, time);
+                    chained.extend(event);
+
+                    if let Some(new_time) = new_time {
+                        entry.when = new_time;
+                        rescheduled.push(entry);
+                    }
+                }
+            }
+        }
+
+        self.m_time = time;
+        self.place_overflow();
+
+        // Executed at the next update at the earliest.
+        rescheduled.into_iter().for_each(|entry| self.insert(entry));
+        chained.into_iter().for_each(|(event, delay)| {
+            self.add_event(event, delay);
+        });
+    }
+
+    /// ## Add an event
+    /// The duration is the delay before the execution.
+    pub fn add_event(&mut self, event: T, add_time: Duration) -> EventId {
+        self.schedule(event, add_time, None, None)
+    }
+
+    /// ## Add a recurring event
+    /// The event is executed after the delay, then every period until it is cancelled
+    /// or executed `repeat` times (None for no limit).
+    pub fn add_recurring_event(
+        &mut self,
+        event: T,
+        delay: Duration,
+        recurrence: Recurrence,
+        repeat: Option<u32>,
+    ) -> EventId {
+        self.schedule(event, delay, Some(recurrence), repeat)
+    }
+
+    /// ## Cancel an event
+    /// Returns false if the event is unknown (executed or cancelled).
+    pub fn cancel_event(&mut self, id: EventId, abort: bool) -> bool {
+        match self.entries.remove(&id) {
+            Some(mut entry) => {
+                if abort {
+                    entry.scheduled.event.on_abort();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// ## Reschedule an event
+    /// The event is executed after the new delay, returns false if the event is unknown.
+    pub fn reschedule_event(&mut self, id: EventId, delay: Duration) -> bool {
+        match self.entries.remove(&id) {
+            Some(mut entry) => {
+                let delay = millis(delay);
+                if entry.scheduled.recurrence.is_none() {
+                    entry.scheduled.delay = delay;
+                }
+                entry.when = self.m_time.saturating_add(delay);
+                entry.generation = entry.generation.wrapping_add(1);
+                self.insert(entry);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// ## Remaining time
+    /// Returns the time before the next execution of an event, None if the event is unknown.
+    pub fn remaining_time(&self, id: EventId) -> Option<Duration> {
+        self.entries
+            .get(&id)
+            .map(|entry| Duration::from_millis((entry.when - self.m_time).max(0) as u64))
+    }
+
+    /// Returns true if the event is waiting for its execution.
+    pub fn contains_event(&self, id: EventId) -> bool {
+        self.entries.contains_key(&id)
+    }
+
+    /// ## Remove Events
+    /// Remove all events and abort them (on_abort()) if abort is true.
+    pub fn remove_events(&mut self, abort: bool) {
+        if abort {
+            self.entries
+                .values_mut()
+                .for_each(|entry| entry.scheduled.event.on_abort());
+        }
+
+        self.entries.clear();
+        self.overflow.clear();
+        self.levels.iter_mut().for_each(|level| {
+            level.occupied = 0;
+            level.slots.iter_mut().for_each(Vec::clear);
+        });
+    }
+
+    /// Returns true if there is no event.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn schedule(
+        &mut self,
+        event: T,
+        add_time: Duration,
+        recurrence: Option<Recurrence>,
+        remaining: Option<u32>,
+    ) -> EventId {
+        let id = EventId(self.next_id);
+        self.next_id += 1;
+
+        let delay = millis(add_time);
+        self.insert(WheelEntry {
+            when: self.m_time.saturating_add(delay),
+            generation: 0,
+            scheduled: ScheduledEvent {
+                id,
+                delay,
+                recurrence,
+                remaining,
+                event,
+            },
+        });
+
+        id
+    }
+
+    fn insert(&mut self, entry: WheelEntry<T>) {
+        let (id, generation, when) = (entry.scheduled.id, entry.generation, entry.when);
+        self.entries.insert(id, entry);
+        self.place(id, generation, when);
+    }
+
+    fn place(&mut self, id: EventId, generation: u32, when: i64) {
+        let now = self.elapsed(self.m_time);
+        let when = self.elapsed(when).max(now);
+
+        // The last level would wrap around to the current slot.
+        if (when >> TOP_SHIFT) - (now >> TOP_SHIFT) >= SLOTS as u64 {
+            self.overflow.push((id, generation));
+            return;
+        }
+
+        let level = level_for(now, when);
+        let slot = ((when >> (level * SLOT_BITS)) & SLOT_MASK) as usize;
+
+        let level = &mut self.levels[level];
+        level.slots[slot].push((id, generation));
+        level.occupied |= 1 << slot;
+    }
+
+    /// Moves the events of the overflow list in range to the wheel.
+    fn place_overflow(&mut self) {
+        if self.overflow.is_empty() {
+            return;
+        }
+
+        for (id, generation) in std::mem::take(&mut self.overflow) {
+            match self.entries.get(&id) {
+                Some(entry) if entry.generation == generation => {
+                    let when = entry.when;
+                    self.place(id, generation, when);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the next occupied slot and its start.
+    fn next_expiration(&self) -> Option<(usize, usize, u64)> {
+        let now = self.elapsed(self.m_time);
+
+        self.levels.iter().enumerate().find_map(|(index, level)| {
+            if level.occupied == 0 {
+                return None;
+            }
+
+            let slot_range = 1u64 << (index * SLOT_BITS);
+            let level_range = slot_range << SLOT_BITS;
+            let now_slot = (now / slot_range) & SLOT_MASK;
+
+            let zeros = level
+                .occupied
+                .rotate_right(now_slot as u32)
+                .trailing_zeros() as u64;
+            let slot = (zeros + now_slot) & SLOT_MASK;
+
+            let level_start = now & !(level_range - 1);
+            let mut deadline = level_start + slot * slot_range;
+            if deadline + slot_range <= now {
+                deadline += level_range;
+            }
+
+            Some((index, slot as usize, deadline.max(now)))
+        })
+    }
+
+    fn elapsed(&self, time: i64) -> u64 {
+        time.saturating_sub(self.origin).max(0) as u64
+    }
+}
+
+fn level_for(now: u64, when: u64) -> usize {
+    let masked = (now ^ when) | SLOT_MASK;
+
+    if masked >= MAX_DURATION {
+        return LEVELS - 1;
+    }
+
+    let significant = 63 - masked.leading_zeros() as usize;
+    significant / SLOT_BITS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::EventAction;
+    use std::{cell::RefCell, rc::Rc};
+
+    #[derive(Clone, Default)]
+    struct MyEvent {
+        executions: Rc<RefCell<Vec<i64>>>,
+        aborted: Rc<RefCell<bool>>,
+    }
+
+    impl Event for MyEvent {
+        fn on_execute(&mut self, _ctx: &mut (), diff: i64) -> EventAction<Self> {
+            self.executions.borrow_mut().push(diff);
+            EventAction::Next
+        }
+
+        fn on_abort(&mut self) {
+            *self.aborted.borrow_mut() = true;
+        }
+    }
+
+    #[test]
+    fn test_level_for() {
+        assert_eq!(level_for(0, 10), 0);
+        assert_eq!(level_for(0, 63), 0);
+        assert_eq!(level_for(0, 64), 1);
+        assert_eq!(level_for(0, 4095), 1);
+        assert_eq!(level_for(0, 4096), 2);
+        assert_eq!(level_for(0, u64::MAX), LEVELS - 1);
+    }
+
+    #[test]
+    fn test_update() {
+        let mut event_processor = WheelEventProcessor::new(1000);
+        let event = MyEvent::default();
+
+        for delay in [5, 70, 5000, 300_000, 10_000_000] {
+            event_processor.add_event(event.clone(), Duration::from_millis(delay));
+        }
+
+        event_processor.update(1004, &mut ());
+        assert!(event.executions.borrow().is_empty());
+
+        event_processor.update(1005, &mut ());
+        assert_eq!(*event.executions.borrow(), [5]);
+
+        event_processor.update(1100, &mut ());
+        assert_eq!(*event.executions.borrow(), [5, 100]);
+
+        event_processor.update(6000, &mut ());
+        event_processor.update(6001, &mut ());
+        assert_eq!(event.executions.borrow().len(), 3);
+
+        event_processor.update(301_000, &mut ());
+        assert_eq!(event.executions.borrow().len(), 4);
+
+        event_processor.update(10_000_999, &mut ());
+        assert_eq!(event.executions.borrow().len(), 4);
+        event_processor.update(10_001_000, &mut ());
+        assert_eq!(event.executions.borrow().len(), 5);
+        assert!(event_processor.is_empty());
+    }
+
+    #[test]
+    fn test_beyond_max_duration() {
+        let mut event_processor = WheelEventProcessor::new(0);
+        let event = MyEvent::default();
+
+        let far = 1i64 << 36;
+        event_processor.add_event(event.clone(), Duration::from_millis(far as u64 + 5));
+        let never = event_processor.add_event(event.clone(), Duration::MAX);
+
+        // The last level would wrap around to the current slot.
+        event_processor.update(1000, &mut ());
+        let wrap =
+            event_processor.add_event(event.clone(), Duration::from_millis(far as u64 - 500));
+        event_processor.update(1001, &mut ());
+        assert!(event.executions.borrow().is_empty());
+        assert_eq!(
+            event_processor.remaining_time(wrap),
+            Some(Duration::from_millis(far as u64 - 501))
+        );
+
+        event_processor.update(far + 4, &mut ());
+        assert!(event.executions.borrow().is_empty());
+
+        event_processor.update(far + 5, &mut ());
+        assert_eq!(event.executions.borrow().len(), 1);
+
+        event_processor.update(far + 499, &mut ());
+        assert_eq!(event.executions.borrow().len(), 1);
+        event_processor.update(far + 500, &mut ());
+        assert_eq!(event.executions.borrow().len(), 2);
+
+        assert_eq!(
+            event_processor.remaining_time(never),
+            Some(Duration::from_millis((i64::MAX - far - 500) as u64))
+        );
+        event_processor.update(far * 4, &mut ());
+        assert!(event_processor.contains_event(never));
+        assert_eq!(event.executions.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_order() {
+        let mut event_processor = WheelEventProcessor::new(0);
+        let event = MyEvent::default();
+
+        for delay in (1..2000).rev() {
+            event_processor.add_event(event.clone(), Duration::from_millis(delay));
+        }
+
+        for time in (0..2000).step_by(37) {
+            event_processor.update(time, &mut ());
+            assert_eq!(event.executions.borrow().len(), time as usize);
+        }
+    }
+
+    #[test]
+    fn test_cancel_event() {
+        let mut event_processor = WheelEventProcessor::new(0);
+        let event = MyEvent::default();
+
+        let id = event_processor.add_event(event.clone(), Duration::from_millis(100));
+        assert!(event_processor.cancel_event(id, true));
+        assert!(!event_processor.cancel_event(id, true));
+        assert!(*event.aborted.borrow());
+
+        event_processor.update(200, &mut ());
+        assert!(event.executions.borrow().is_empty());
+        assert!(event_processor.is_empty());
+    }
+
+    #[test]
+    fn test_reschedule_event() {
+        let mut event_processor = WheelEventProcessor::new(0);
+        let event = MyEvent::default();
+
+        let id = event_processor.add_event(event.clone(), Duration::from_millis(100));
+        event_processor.update(50, &mut ());
+        assert!(event_processor.reschedule_event(id, Duration::from_millis(100)));
+        assert_eq!(
+            event_processor.remaining_time(id),
+            Some(Duration::from_millis(100))
+        );
+
+        event_processor.update(120, &mut ());
+        assert!(event.executions.borrow().is_empty());
+
+        event_processor.update(150, &mut ());
+        assert_eq!(*event.executions.borrow(), [100]);
+        assert!(!event_processor.contains_event(id));
+    }
+
+    #[test]
+    fn test_recurring_event() {
+        let mut event_processor = WheelEventProcessor::new(0);
+        let event = MyEvent::default();
+
+        let id = event_processor.add_recurring_event(
+            event.clone(),
+            Duration::from_millis(100),
+            Recurrence::FixedRate(Duration::from_millis(100)),
+            Some(3),
+        );
+
+        event_processor.update(130, &mut ());
+        assert_eq!(
+            event_processor.remaining_time(id),
+            Some(Duration::from_millis(70))
+        );
+
+        event_processor.update(200, &mut ());
+        event_processor.update(300, &mut ());
+        assert_eq!(event.executions.borrow().len(), 3);
+        assert!(event_processor.is_empty());
+    }
+
+    #[test]
+    fn test_remove_events() {
+        let mut event_processor = WheelEventProcessor::new(0);
+        let event = MyEvent::default();
+
+        event_processor.add_event(event.clone(), Duration::from_millis(100));
+        event_processor.remove_events(true);
+
+        assert!(*event.aborted.borrow());
+        assert!(event_processor.is_empty());
+        assert!(event_processor.next_expiration().is_none());
+    }
+}
@@ -1,11 +1,16 @@
 use crate::error::{Error, Result};
+use crate::packet::POOL_SIZE;
 use easy_pool::{PoolObjectContainer, PoolSegQueue};
 use once_cell::sync::Lazy;
-use std::{convert::TryFrom, sync::Arc};
+use std::{
+    convert::TryFrom,
+    sync::{atomic::Ordering, Arc},
+};
 use tokio::io::AsyncReadExt;
 
 pub(crate) const MAX_SIZE: usize = 1024 * 14;
-static POOL_VEC: Lazy<Arc<PoolSegQueue<Vec<u8>>>> = Lazy::new(|| Arc::new(PoolSegQueue::new(4096)));
+static POOL_VEC: Lazy<Arc<PoolSegQueue<Vec<u8>>>> =
+    Lazy::new(|| Arc::new(PoolSegQueue::new(POOL_SIZE.load(Ordering::Acquire))));
 
 pub(crate) struct Reader<'a, R>
 where
@@ -22,13 +22,30 @@ cfg_game! {
     pub mod game;
 }
 
-cfg_server! {
+cfg_packet! {
+    #[cfg_attr(not(feature = "server"), allow(dead_code))]
     pub mod packet;
+    pub use bytes;
+    pub use tokio;
+    mod io;
+}
+
+cfg_server! {
     pub mod server;
     pub use server::world_socket::ContainerBytes;
     pub use async_trait::async_trait;
-    pub use bytes;
-    pub use tokio;
     pub use crossbeam::atomic::AtomicCell;
-    mod io;
+    pub use parking_lot::Mutex;
+}
+
+cfg_client! {
+    pub mod client;
+}
+
+cfg_metrics! {
+    pub mod metrics;
+}
+
+cfg_config! {
+    pub mod config;
 }
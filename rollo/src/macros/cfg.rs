@@ -10,6 +10,46 @@ macro_rules! cfg_server {
     }
 }
 
+macro_rules! cfg_client {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "client")]
+            #[cfg_attr(docsrs, doc(cfg(feature = "client")))]
+            $item
+        )*
+    }
+}
+
+macro_rules! cfg_packet {
+    ($($item:item)*) => {
+        $(
+            #[cfg(any(feature = "server", feature = "client"))]
+            #[cfg_attr(docsrs, doc(cfg(any(feature = "server", feature = "client"))))]
+            $item
+        )*
+    }
+}
+
+macro_rules! cfg_metrics {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "metrics")]
+            #[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+            $item
+        )*
+    }
+}
+
+macro_rules! cfg_config {
+    ($($item:item)*) => {
+        $(
+            #[cfg(feature = "config")]
+            #[cfg_attr(docsrs, doc(cfg(feature = "config")))]
+            $item
+        )*
+    }
+}
+
 macro_rules! cfg_macros {
     ($($item:item)*) => {
         $(
@@ -1,2 +1,5 @@
 #[macro_use]
 pub(crate) mod cfg;
+
+#[macro_use]
+pub(crate) mod trace;
@@ -0,0 +1,49 @@
+#![allow(unused_macros)]
+
+// Diagnostics with `tracing` (spans of the sessions) if the feature is enabled, `log` otherwise.
+
+// The span is created before the future (the future can take the fields).
+#[cfg(feature = "tracing")]
+macro_rules! instrument {
+    ($span:expr, $future:expr) => {{
+        let span = $span;
+        tracing::Instrument::instrument($future, span)
+    }};
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! instrument {
+    ($span:expr, $future:expr) => {
+        $future
+    };
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! log_debug {
+    ($($arg:tt)*) => { tracing::debug!($($arg)*) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! log_debug {
+    ($($arg:tt)*) => { log::debug!($($arg)*) };
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! log_info {
+    ($($arg:tt)*) => { tracing::info!($($arg)*) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! log_info {
+    ($($arg:tt)*) => { log::info!($($arg)*) };
+}
+
+#[cfg(feature = "tracing")]
+macro_rules! log_error {
+    ($($arg:tt)*) => { tracing::error!($($arg)*) };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! log_error {
+    ($($arg:tt)*) => { log::error!($($arg)*) };
+}
@@ -0,0 +1,456 @@
+//! # Metrics
+//! Counters of the server and the game loop, exported as OpenMetrics (Prometheus) text.
+//!
+//! The metrics are collected by every `WorldSocketMgr` and `GameLoop` of the process.
+//! ## Usage
+//! ```rust, no_run
+//! # async fn test() {
+//! // With a scrape function
+//! let text = rollo::metrics::scrape();
+//!
+//! // Or with a local HTTP endpoint (GET /metrics)
+//! tokio::spawn(rollo::metrics::serve("127.0.0.1:9100"));
+//! # }
+//! ```
+use crate::server::DosPolicy;
+use once_cell::sync::Lazy;
+use parking_lot::RwLock;
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io, mem,
+    sync::{
+        atomic::{AtomicI64, AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    time::timeout,
+};
+
+pub(crate) static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+/// Buckets of the tick duration histogram (seconds).
+const TICK_BUCKETS: [f64; 10] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0];
+
+const HEADER_SIZE: usize = mem::size_of::<u32>() + mem::size_of::<u16>();
+const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+const TIMEOUT_REQUEST: Duration = Duration::from_secs(5);
+
+/// Returns the metrics as OpenMetrics text.
+pub fn scrape() -> String {
+    METRICS.encode()
+}
+
+/// Serves the metrics on a local HTTP endpoint (`GET /metrics`).
+pub async fn serve(addr: impl ToSocketAddrs) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+
+    loop {
+        let (socket, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            if let Err(error) = respond(socket).await {
+                log_info!("Error when serving the metrics {:?}", error);
+            }
+        });
+    }
+}
+
+async fn respond(mut socket: TcpStream) -> io::Result<()> {
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        let read = timeout(TIMEOUT_REQUEST, socket.read(&mut buffer))
+            .await
+            .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+        if read == 0 || request.len() + read > MAX_REQUEST_SIZE {
+            return Ok(());
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+
+    let response = if request.starts_with(b"GET /metrics ") {
+        let body = scrape();
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            CONTENT_TYPE,
+            body.len(),
+            body
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+    };
+
+    socket.write_all(response.as_bytes()).await?;
+    socket.shutdown().await
+}
+
+#[derive(Debug, Default)]
+struct Traffic {
+    packets: AtomicU64,
+    bytes: AtomicU64,
+}
+
+/// Amount of cmds with their own series, the next ones are counted under `cmd="other"`.
+const MAX_CMDS: usize = 256;
+
+/// Traffic per cmd.
+#[derive(Debug, Default)]
+struct CmdTraffic {
+    cmds: RwLock<BTreeMap<u16, Arc<Traffic>>>,
+    other: Traffic,
+}
+
+impl CmdTraffic {
+    fn add(&self, cmd: u16, bytes: usize) {
+        let traffic = self.cmds.read().get(&cmd).cloned();
+        let traffic = traffic.or_else(|| {
+            let mut cmds = self.cmds.write();
+            if cmds.len() < MAX_CMDS || cmds.contains_key(&cmd) {
+                Some(Arc::clone(cmds.entry(cmd).or_default()))
+            } else {
+                None
+            }
+        });
+        let traffic = traffic.as_deref().unwrap_or(&self.other);
+
+        traffic.packets.fetch_add(1, Ordering::Relaxed);
+        traffic.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    // The series of the cmds, then the other cmds if any.
+    fn series(&self, f: impl Fn(&Traffic) -> &AtomicU64) -> Vec<(String, u64)> {
+        let mut series = self
+            .cmds
+            .read()
+            .iter()
+            .map(|(cmd, traffic)| (cmd.to_string(), f(traffic).load(Ordering::Relaxed)))
+            .collect::<Vec<_>>();
+
+        if self.other.packets.load(Ordering::Relaxed) > 0 {
+            series.push(("other".to_owned(), f(&self.other).load(Ordering::Relaxed)));
+        }
+
+        series
+    }
+}
+
+#[derive(Debug)]
+struct Histogram {
+    buckets: [AtomicU64; TICK_BUCKETS.len()],
+    count: AtomicU64,
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            buckets: Default::default(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        if let Some(index) = TICK_BUCKETS.iter().position(|bucket| seconds <= *bucket) {
+            self.buckets[index].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+}
+
+/// The metrics of the process.
+#[derive(Debug)]
+pub(crate) struct Metrics {
+    connections: AtomicI64,
+    accepts: AtomicU64,
+    tls_failures: AtomicU64,
+    received: CmdTraffic,
+    sent: CmdTraffic,
+    dos_triggers: [AtomicU64; 3],
+    writer_queue: AtomicI64,
+    ticks: Histogram,
+    overruns: AtomicU64,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        Self {
+            connections: AtomicI64::new(0),
+            accepts: AtomicU64::new(0),
+            tls_failures: AtomicU64::new(0),
+            received: CmdTraffic::default(),
+            sent: CmdTraffic::default(),
+            dos_triggers: Default::default(),
+            writer_queue: AtomicI64::new(0),
+            ticks: Histogram::new(),
+            overruns: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn accepted(&self) {
+        self.accepts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn tls_failed(&self) {
+        self.tls_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn opened(&self) {
+        self.connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn closed(&self) {
+        self.connections.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// A packet is received, size of the payload.
+    pub(crate) fn received(&self, cmd: u16, size: usize) {
+        self.received.add(cmd, HEADER_SIZE + size);
+    }
+
+    /// A packet is written, size of the payload.
+    pub(crate) fn sent(&self, cmd: u16, size: usize) {
+        self.sent.add(cmd, HEADER_SIZE + size);
+    }
+
+    pub(crate) fn dos_triggered(&self, policy: &DosPolicy) {
+        self.dos_triggers[Self::policy_index(policy)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn enqueued(&self) {
+        self.writer_queue.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn dequeued(&self) {
+        self.writer_queue.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn tick(&self, duration: Duration, interval: Duration) {
+        self.ticks.observe(duration);
+        if duration > interval {
+            self.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn policy_index(policy: &DosPolicy) -> usize {
+        match policy {
+            DosPolicy::None => 0,
+            DosPolicy::Log => 1,
+            DosPolicy::Close => 2,
+        }
+    }
+
+    fn encode(&self) -> String {
+        let mut text = String::new();
+
+        Self::metric(
+            &mut text,
+            "rollo_connections",
+            "gauge",
+            "Active connections.",
+        );
+        let _ = writeln!(
+            text,
+            "rollo_connections {}",
+            self.connections.load(Ordering::Relaxed)
+        );
+
+        Self::counter(
+            &mut text,
+            "rollo_accepts",
+            "Accepted connections.",
+            &self.accepts,
+        );
+        Self::counter(
+            &mut text,
+            "rollo_tls_handshake_failures",
+            "Failed TLS handshakes.",
+            &self.tls_failures,
+        );
+
+        Self::traffic(&mut text, "received", &self.received);
+        Self::traffic(&mut text, "sent", &self.sent);
+
+        Self::metric(
+            &mut text,
+            "rollo_dos_triggers",
+            "counter",
+            "Triggers of the DoS protection per policy.",
+        );
+        for (policy, triggers) in ["none", "log", "close"].iter().zip(&self.dos_triggers) {
+            let _ = writeln!(
+                text,
+                "rollo_dos_triggers_total{{policy=\"{}\"}} {}",
+                policy,
+                triggers.load(Ordering::Relaxed)
+            );
+        }
+
+        Self::metric(
+            &mut text,
+            "rollo_writer_queue_depth",
+            "gauge",
+            "Messages waiting for the writers of the sessions.",
+        );
+        let _ = writeln!(
+            text,
+            "rollo_writer_queue_depth {}",
+            self.writer_queue.load(Ordering::Relaxed).max(0)
+        );
+
+        Self::metric(
+            &mut text,
+            "rollo_tick_duration_seconds",
+            "histogram",
+            "Duration of the ticks of the game loop.",
+        );
+        let mut cumulative = 0;
+        for (bucket, count) in TICK_BUCKETS.iter().zip(&self.ticks.buckets) {
+            cumulative += count.load(Ordering::Relaxed);
+            let _ = writeln!(
+                text,
+                "rollo_tick_duration_seconds_bucket{{le=\"{}\"}} {}",
+                bucket, cumulative
+            );
+        }
+        let count = self.ticks.count.load(Ordering::Relaxed);
+        let _ = writeln!(
+            text,
+            "rollo_tick_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+            count
+        );
+        let _ = writeln!(
+            text,
+            "rollo_tick_duration_seconds_sum {}",
+            self.ticks.sum.load(Ordering::Relaxed) as f64 / 1_000_000.0
+        );
+        let _ = writeln!(text, "rollo_tick_duration_seconds_count {}", count);
+
+        Self::counter(
+            &mut text,
+            "rollo_tick_overruns",
+            "Ticks longer than the interval of the game loop.",
+            &self.overruns,
+        );
+
+        text.push_str("# EOF\n");
+        text
+    }
+
+    fn metric(text: &mut String, name: &str, kind: &str, help: &str) {
+        let _ = writeln!(text, "# TYPE {} {}", name, kind);
+        let _ = writeln!(text, "# HELP {} {}", name, help);
+    }
+
+    fn counter(text: &mut String, name: &str, help: &str, value: &AtomicU64) {
+        Self::metric(text, name, "counter", help);
+        let _ = writeln!(text, "{}_total {}", name, value.load(Ordering::Relaxed));
+    }
+
+    fn traffic(text: &mut String, direction: &str, traffic: &CmdTraffic) {
+        Self::metric(
+            text,
+            &format!("rollo_packets_{}", direction),
+            "counter",
+            &format!("Packets {} per cmd.", direction),
+        );
+        for (cmd, packets) in traffic.series(|traffic| &traffic.packets) {
+            let _ = writeln!(
+                text,
+                "rollo_packets_{}_total{{cmd=\"{}\"}} {}",
+                direction, cmd, packets
+            );
+        }
+
+        Self::metric(
+            text,
+            &format!("rollo_bytes_{}", direction),
+            "counter",
+            &format!("Bytes {} per cmd (header included).", direction),
+        );
+        for (cmd, bytes) in traffic.series(|traffic| &traffic.bytes) {
+            let _ = writeln!(
+                text,
+                "rollo_bytes_{}_total{{cmd=\"{}\"}} {}",
+                direction, cmd, bytes
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let metrics = Metrics::new();
+        metrics.accepted();
+        metrics.opened();
+        metrics.received(5, 10);
+        metrics.received(5, 0);
+        metrics.sent(6, 4);
+        metrics.dos_triggered(&DosPolicy::Close);
+        metrics.enqueued();
+        metrics.tick(Duration::from_millis(3), Duration::from_millis(50));
+        metrics.tick(Duration::from_millis(60), Duration::from_millis(50));
+
+        let text = metrics.encode();
+        for line in [
+            "rollo_connections 1",
+            "rollo_accepts_total 1",
+            "rollo_tls_handshake_failures_total 0",
+            "rollo_packets_received_total{cmd=\"5\"} 2",
+            "rollo_bytes_received_total{cmd=\"5\"} 22",
+            "rollo_packets_sent_total{cmd=\"6\"} 1",
+            "rollo_bytes_sent_total{cmd=\"6\"} 10",
+            "rollo_dos_triggers_total{policy=\"close\"} 1",
+            "rollo_dos_triggers_total{policy=\"log\"} 0",
+            "rollo_writer_queue_depth 1",
+            "rollo_tick_duration_seconds_bucket{le=\"0.0025\"} 0",
+            "rollo_tick_duration_seconds_bucket{le=\"0.005\"} 1",
+            "rollo_tick_duration_seconds_bucket{le=\"0.1\"} 2",
+            "rollo_tick_duration_seconds_bucket{le=\"+Inf\"} 2",
+            "rollo_tick_duration_seconds_count 2",
+            "rollo_tick_overruns_total 1",
+        ]
+        .iter()
+        {
+            assert!(text.lines().any(|l| l == *line), "{} in\n{}", line, text);
+        }
+        assert!(text.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn test_max_cmds() {
+        let metrics = Metrics::new();
+        for cmd in 0..=u16::MAX {
+            metrics.received(cmd, 0);
+        }
+        metrics.received(5, 0);
+
+        let text = metrics.encode();
+        let series = text
+            .lines()
+            .filter(|l| l.starts_with("rollo_packets_received_total"))
+            .count();
+        assert_eq!(series, MAX_CMDS + 1);
+        assert!(text
+            .lines()
+            .any(|l| l == "rollo_packets_received_total{cmd=\"5\"} 2"));
+        assert!(text.lines().any(|l| l
+            == format!(
+                "rollo_packets_received_total{{cmd=\"other\"}} {}",
+                u16::MAX as usize + 1 - MAX_CMDS
+            )));
+    }
+}
@@ -1,7 +1,49 @@
+use crate::error::{Error, Result};
 use bytes::BufMut;
 use easy_pool::{PoolObjectContainer, PoolSegQueue};
 use once_cell::sync::Lazy;
-use std::{mem, sync::Arc};
+use std::{
+    convert::TryInto,
+    mem,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// Reserved command of a fragment of a message sent on a `Channel`.
+///
+/// The payload starts with the `u8` id of the channel and a `u8` set to 1 on the last fragment.
+/// The fragments of a channel, once joined, are the bytes of the original message.
+pub const FRAGMENT_CMD: u16 = u16::MAX - 2;
+
+/// Reserved command of a fragment of a stream (message bigger than the maximum size).
+///
+/// The payload starts with the `u32` id of the stream, the `u16` command of the stream and
+/// a `u8` set to 1 on the last fragment.
+pub const STREAM_CMD: u16 = u16::MAX - 3;
+
+/// Reserved command of a request (RPC).
+///
+/// The payload starts with a `u32` request id and the `u16` command of the request.
+pub const RPC_REQUEST_CMD: u16 = u16::MAX - 1;
+
+/// Reserved command of a reply to a request (RPC).
+///
+/// The payload starts with the `u32` request id and the `u16` command of the request.
+pub const RPC_RESPONSE_CMD: u16 = u16::MAX;
+
+/// First reserved command, the commands from `RESERVED_CMD` to `u16::MAX` are used by the protocol
+/// (streams, channels, requests).
+///
+/// The packets sent on them with `SocketTools::send`, `send_on`, `send_stream` or `request` are
+/// rejected, a session sending an unknown reserved command is closed.
+pub const RESERVED_CMD: u16 = STREAM_CMD;
+
+/// Returns true if the command is reserved by the protocol.
+pub const fn is_reserved(cmd: u16) -> bool {
+    cmd >= RESERVED_CMD
+}
 
 /// Represents a message with a command and a payload.
 #[derive(Debug)]
@@ -32,10 +74,98 @@ impl Packet {
     pub fn freeze(self) -> Arc<Self> {
         Arc::new(self)
     }
+
+    /// Splits a request (RPC) or a reply into its request id and the inner Packet.
+    pub(crate) fn into_rpc(self) -> Result<(u32, Self)> {
+        let mut payload = self.payload.ok_or(Error::PacketPayload)?;
+
+        if payload.len() < HEADER_SIZE {
+            return Err(Error::PacketPayload);
+        }
+
+        let request_id = u32::from_be_bytes(
+            payload[..mem::size_of::<u32>()]
+                .try_into()
+                .map_err(|_| Error::PacketPayload)?,
+        );
+        let cmd = u16::from_be_bytes(
+            payload[mem::size_of::<u32>()..HEADER_SIZE]
+                .try_into()
+                .map_err(|_| Error::PacketPayload)?,
+        );
+
+        payload.drain(..HEADER_SIZE);
+
+        if payload.is_empty() {
+            Ok((request_id, Self::new(cmd, None)))
+        } else {
+            Ok((request_id, Self::new(cmd, Some(payload))))
+        }
+    }
+
+    /// Splits a message routed between the nodes of a cluster into its target and the inner Packet.
+    pub(crate) fn into_routed(self) -> Result<(u64, Self)> {
+        let mut payload = self.payload.ok_or(Error::PacketPayload)?;
+
+        if payload.len() < ROUTED_HEADER_SIZE {
+            return Err(Error::PacketPayload);
+        }
+
+        let target = u64::from_be_bytes(
+            payload[..mem::size_of::<u64>()]
+                .try_into()
+                .map_err(|_| Error::PacketPayload)?,
+        );
+        let cmd = u16::from_be_bytes(
+            payload[mem::size_of::<u64>()..ROUTED_HEADER_SIZE]
+                .try_into()
+                .map_err(|_| Error::PacketPayload)?,
+        );
+
+        payload.drain(..ROUTED_HEADER_SIZE);
+
+        if payload.is_empty() {
+            Ok((target, Self::new(cmd, None)))
+        } else {
+            Ok((target, Self::new(cmd, Some(payload))))
+        }
+    }
+
+    /// Splits a fragment of a stream into its stream id, the last flag and the inner Packet.
+    pub(crate) fn into_stream(self) -> Result<(u32, bool, Self)> {
+        let mut payload = self.payload.ok_or(Error::PacketPayload)?;
+
+        if payload.len() < STREAM_HEADER_SIZE {
+            return Err(Error::PacketPayload);
+        }
+
+        let stream_id = u32::from_be_bytes(
+            payload[..mem::size_of::<u32>()]
+                .try_into()
+                .map_err(|_| Error::PacketPayload)?,
+        );
+        let cmd = u16::from_be_bytes(
+            payload[mem::size_of::<u32>()..HEADER_SIZE]
+                .try_into()
+                .map_err(|_| Error::PacketPayload)?,
+        );
+        let last = payload[HEADER_SIZE] == 1;
+
+        payload.drain(..STREAM_HEADER_SIZE);
+
+        if payload.is_empty() {
+            Ok((stream_id, last, Self::new(cmd, None)))
+        } else {
+            Ok((stream_id, last, Self::new(cmd, Some(payload))))
+        }
+    }
 }
 
 const HEADER_SIZE: usize = mem::size_of::<u32>() + mem::size_of::<u16>();
 
+/// Header of a message routed between the nodes of a cluster (`u64` target, `u16` cmd).
+pub(crate) const ROUTED_HEADER_SIZE: usize = mem::size_of::<u64>() + mem::size_of::<u16>();
+
 /// Converts a command and a payload to a byte buffer.
 ///
 /// # Examples
@@ -69,12 +199,143 @@ pub fn to_bytes(cmd: u16, payload: Option<&[u8]>) -> PoolObjectContainer<Vec<u8>
     vec
 }
 
-static POOL_VEC: Lazy<Arc<PoolSegQueue<Vec<u8>>>> = Lazy::new(|| Arc::new(PoolSegQueue::new(4096)));
+/// Converts a request (RPC) or a reply to a byte buffer.
+pub(crate) fn to_rpc_bytes(
+    rpc_cmd: u16,
+    request_id: u32,
+    cmd: u16,
+    payload: Option<&[u8]>,
+) -> PoolObjectContainer<Vec<u8>> {
+    debug_assert!(rpc_cmd == RPC_REQUEST_CMD || rpc_cmd == RPC_RESPONSE_CMD);
+
+    let payload_size = payload.map_or_else(|| 0, |p| p.len());
+    let size = (HEADER_SIZE + payload_size) as u32;
+    let target_capacity = HEADER_SIZE + size as usize;
+    let mut vec = POOL_VEC.create();
+    debug_assert!(vec.is_empty());
+
+    vec.reserve_exact(target_capacity);
+
+    vec.put_u32(size);
+    vec.put_u16(rpc_cmd);
+    vec.put_u32(request_id);
+    vec.put_u16(cmd);
+
+    if let Some(payload) = payload {
+        vec.extend(payload);
+    }
+
+    debug_assert!(vec.len() == target_capacity);
+
+    vec
+}
+
+/// Header of a fragment of a channel (`u8` channel id, `u8` last).
+pub(crate) const FRAGMENT_HEADER_SIZE: usize = mem::size_of::<u8>() * 2;
+
+/// Converts a fragment of a message to a byte buffer.
+pub(crate) fn to_fragment_bytes(
+    channel_id: u8,
+    last: bool,
+    chunk: &[u8],
+) -> PoolObjectContainer<Vec<u8>> {
+    let size = (FRAGMENT_HEADER_SIZE + chunk.len()) as u32;
+    let target_capacity = HEADER_SIZE + size as usize;
+    let mut vec = POOL_VEC.create();
+    debug_assert!(vec.is_empty());
+
+    vec.reserve_exact(target_capacity);
+
+    vec.put_u32(size);
+    vec.put_u16(FRAGMENT_CMD);
+    vec.put_u8(channel_id);
+    vec.put_u8(last as u8);
+    vec.extend(chunk);
+
+    debug_assert!(vec.len() == target_capacity);
+
+    vec
+}
+
+const STREAM_HEADER_SIZE: usize = HEADER_SIZE + mem::size_of::<u8>();
+
+/// Converts a message routed to a node of a cluster to a byte buffer.
+pub(crate) fn to_routed_bytes(
+    link_cmd: u16,
+    target: u64,
+    cmd: u16,
+    payload: &[u8],
+) -> PoolObjectContainer<Vec<u8>> {
+    let size = (ROUTED_HEADER_SIZE + payload.len()) as u32;
+    let target_capacity = HEADER_SIZE + size as usize;
+    let mut vec = POOL_VEC.create();
+    debug_assert!(vec.is_empty());
+
+    vec.reserve_exact(target_capacity);
+
+    vec.put_u32(size);
+    vec.put_u16(link_cmd);
+    vec.put_u64(target);
+    vec.put_u16(cmd);
+    vec.extend(payload);
+
+    debug_assert!(vec.len() == target_capacity);
+
+    vec
+}
+
+/// Converts a fragment of a stream to a byte buffer.
+pub(crate) fn to_stream_bytes(
+    stream_id: u32,
+    cmd: u16,
+    last: bool,
+    chunk: &[u8],
+) -> PoolObjectContainer<Vec<u8>> {
+    let size = (STREAM_HEADER_SIZE + chunk.len()) as u32;
+    let target_capacity = HEADER_SIZE + size as usize;
+    let mut vec = POOL_VEC.create();
+    debug_assert!(vec.is_empty());
+
+    vec.reserve_exact(target_capacity);
+
+    vec.put_u32(size);
+    vec.put_u16(STREAM_CMD);
+    vec.put_u32(stream_id);
+    vec.put_u16(cmd);
+    vec.put_u8(last as u8);
+    vec.extend(chunk);
+
+    debug_assert!(vec.len() == target_capacity);
+
+    vec
+}
+
+/// Iterates over the packets `(cmd, payload)` of a byte buffer (one or more packets).
+pub(crate) fn frames(mut bytes: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        if bytes.len() < HEADER_SIZE {
+            return None;
+        }
+
+        let size = u32::from_be_bytes(bytes[..4].try_into().unwrap()) as usize;
+        let cmd = u16::from_be_bytes(bytes[4..HEADER_SIZE].try_into().unwrap());
+        let end = (HEADER_SIZE + size).min(bytes.len());
+        let payload = &bytes[HEADER_SIZE..end];
+        bytes = &bytes[end..];
+
+        Some((cmd, payload))
+    })
+}
+
+/// Maximum amount of buffers kept by the pools of the packets, read when the pools are first used.
+pub(crate) static POOL_SIZE: AtomicUsize = AtomicUsize::new(4096);
+
+static POOL_VEC: Lazy<Arc<PoolSegQueue<Vec<u8>>>> =
+    Lazy::new(|| Arc::new(PoolSegQueue::new(POOL_SIZE.load(Ordering::Acquire))));
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::convert::TryInto;
 
     #[test]
     fn test_to_bytes() {
@@ -107,4 +368,112 @@ mod tests {
             assert_eq!(result[6..], content);
         }
     }
+
+    #[test]
+    fn test_frames() {
+        let mut bytes = to_bytes(1, Some(&[1, 2])).to_vec();
+        bytes.extend_from_slice(&to_bytes(2, None));
+        bytes.extend_from_slice(&to_bytes(3, Some(&[3, 4, 5]))[..8]);
+
+        let frames = frames(&bytes).collect::<Vec<_>>();
+        assert_eq!(frames, [(1, &[1, 2][..]), (2, &[][..]), (3, &[3, 4][..])]);
+    }
+
+    #[test]
+    fn test_is_reserved() {
+        assert!(!is_reserved(0));
+        assert!(!is_reserved(RESERVED_CMD - 1));
+        assert!(is_reserved(STREAM_CMD));
+        assert!(is_reserved(FRAGMENT_CMD));
+        assert!(is_reserved(RPC_REQUEST_CMD));
+        assert!(is_reserved(RPC_RESPONSE_CMD));
+    }
+
+    #[test]
+    fn test_to_rpc_bytes() {
+        let result = to_rpc_bytes(RPC_REQUEST_CMD, 7, 12, Some(&[1, 2]));
+        assert_eq!(result.len(), 14);
+        let size = u32::from_be_bytes(result[..4].try_into().unwrap());
+        assert_eq!(size, 8);
+        let op_code = u16::from_be_bytes(result[4..6].try_into().unwrap());
+        assert_eq!(op_code, RPC_REQUEST_CMD);
+        let request_id = u32::from_be_bytes(result[6..10].try_into().unwrap());
+        assert_eq!(request_id, 7);
+        let cmd = u16::from_be_bytes(result[10..12].try_into().unwrap());
+        assert_eq!(cmd, 12);
+        assert_eq!(result[12..], [1, 2]);
+    }
+
+    #[test]
+    fn test_into_rpc() {
+        let mut payload = POOL_VEC.create();
+        payload.extend_from_slice(&to_rpc_bytes(RPC_RESPONSE_CMD, 9, 3, Some(&[5]))[6..]);
+        let (request_id, packet) = Packet::new(RPC_RESPONSE_CMD, Some(payload))
+            .into_rpc()
+            .unwrap();
+        assert_eq!(request_id, 9);
+        assert_eq!(packet.cmd, 3);
+        assert_eq!(*packet.payload.unwrap(), [5]);
+
+        let mut payload = POOL_VEC.create();
+        payload.extend_from_slice(&to_rpc_bytes(RPC_RESPONSE_CMD, 1, 3, None)[6..]);
+        let (_, packet) = Packet::new(RPC_RESPONSE_CMD, Some(payload))
+            .into_rpc()
+            .unwrap();
+        assert!(packet.payload.is_none());
+
+        let mut payload = POOL_VEC.create();
+        payload.extend_from_slice(&[0, 1]);
+        assert_eq!(
+            Packet::new(RPC_RESPONSE_CMD, Some(payload))
+                .into_rpc()
+                .unwrap_err(),
+            Error::PacketPayload
+        );
+        assert!(Packet::new(RPC_RESPONSE_CMD, None).into_rpc().is_err());
+    }
+
+    #[test]
+    fn test_into_stream() {
+        let mut payload = POOL_VEC.create();
+        payload.extend_from_slice(&to_stream_bytes(4, 30, true, &[1, 2, 3])[6..]);
+        let (stream_id, last, packet) = Packet::new(STREAM_CMD, Some(payload))
+            .into_stream()
+            .unwrap();
+        assert_eq!(stream_id, 4);
+        assert!(last);
+        assert_eq!(packet.cmd, 30);
+        assert_eq!(*packet.payload.unwrap(), [1, 2, 3]);
+
+        let mut payload = POOL_VEC.create();
+        payload.extend_from_slice(&to_stream_bytes(4, 30, false, &[])[6..]);
+        let (_, last, packet) = Packet::new(STREAM_CMD, Some(payload))
+            .into_stream()
+            .unwrap();
+        assert!(!last);
+        assert!(packet.payload.is_none());
+
+        let mut payload = POOL_VEC.create();
+        payload.extend_from_slice(&[0, 0, 0, 1, 0, 30]);
+        assert!(Packet::new(STREAM_CMD, Some(payload))
+            .into_stream()
+            .is_err());
+    }
+
+    #[test]
+    fn test_into_routed() {
+        let bytes = to_routed_bytes(4, 12, 30, &[1, 2, 3]);
+        assert_eq!(bytes[..6], [0, 0, 0, 13, 0, 4]);
+
+        let mut payload = POOL_VEC.create();
+        payload.extend_from_slice(&bytes[6..]);
+        let (target, packet) = Packet::new(4, Some(payload)).into_routed().unwrap();
+        assert_eq!(target, 12);
+        assert_eq!(packet.cmd, 30);
+        assert_eq!(*packet.payload.unwrap(), [1, 2, 3]);
+
+        let mut payload = POOL_VEC.create();
+        payload.extend_from_slice(&[0, 0, 0, 1]);
+        assert!(Packet::new(4, Some(payload)).into_routed().is_err());
+    }
 }
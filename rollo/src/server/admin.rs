@@ -0,0 +1,341 @@
+use super::{
+    dos_protection::{DosLimits, DosPolicy},
+    registry::SessionRegistry,
+    world::World,
+    world_session::WorldSession,
+};
+use crate::game::{game_loop::GameLoopControl, GameTime};
+use crossbeam::atomic::AtomicCell;
+use std::{fmt, fmt::Write, io, net::IpAddr, sync::Arc};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{lookup_host, TcpListener},
+};
+
+#[cfg(unix)]
+use std::path::Path;
+
+const HELP: &str = "\
+sessions                          list the sessions (id, address, latency)
+kick <id>                         close a session
+ban <id|ip>                       ban an address and close its sessions (TCP)
+unban <ip>                        remove a ban
+bans                              list the banned addresses
+dos                               show the replaced DoS limits
+dos global <amount> <size>        replace the global limit
+dos cmd <cmd> <amount> <size> <none|log|close>
+                                  replace the limit of a command
+dos reset [global|<cmd>]          restore the limits of the World
+tick                              show the game time and the tick timing
+help                              show this help
+quit                              close the connection
+";
+
+/// Where the admin console listens.
+#[derive(Debug, Clone, Copy)]
+pub enum AdminListener<'a> {
+    /// TCP address, only loopback addresses are accepted.
+    Tcp(&'a str),
+    /// Unix socket path, the file must not exist.
+    #[cfg(unix)]
+    Unix(&'a Path),
+}
+
+/// # Admin Console
+/// Lists the sessions, kicks or bans them, changes the DoS limits and shows the tick timing.
+///
+/// Each command is a line, each response ends with a line `OK` or `ERR <reason>`.
+/// The `rollo-admin` binary connects to the console.
+/// ## Usage
+/// ```rust, no_run
+/// use rollo::server::{AdminListener, World, WorldSocketMgr};
+///
+/// # async fn test<W: World + 'static>(world: &'static W) {
+/// let server = WorldSocketMgr::new(world);
+/// let console = server.admin_console();
+///
+/// println!("{}", console.execute("sessions"));
+/// tokio::spawn(console.serve(AdminListener::Tcp("127.0.0.1:6670")));
+/// # }
+/// ```
+pub struct AdminConsole<W>
+where
+    W: World,
+{
+    registry: Arc<SessionRegistry<W>>,
+    limits: Arc<DosLimits>,
+    control: GameLoopControl,
+    game_time: &'static AtomicCell<GameTime>,
+}
+
+impl<W> AdminConsole<W>
+where
+    W: Send + Sync + 'static + World,
+{
+    pub(crate) fn new(
+        registry: Arc<SessionRegistry<W>>,
+        limits: Arc<DosLimits>,
+        control: GameLoopControl,
+        game_time: &'static AtomicCell<GameTime>,
+    ) -> Self {
+        Self {
+            registry,
+            limits,
+            control,
+            game_time,
+        }
+    }
+
+    /// Executes a command, returns the response.
+    pub fn execute(&self, line: &str) -> String {
+        let args = line.split_whitespace().collect::<Vec<_>>();
+        let mut output = String::new();
+
+        let result = match args.as_slice() {
+            ["sessions"] => {
+                self.sessions(&mut output);
+                Ok(())
+            }
+            ["kick", id] => self.kick(id),
+            ["ban", target] => self.ban(target, &mut output),
+            ["unban", ip] => self.unban(ip),
+            ["bans"] => {
+                for ip in self.registry.bans() {
+                    let _ = writeln!(output, "{}", ip);
+                }
+                Ok(())
+            }
+            ["dos"] => {
+                self.dos(&mut output);
+                Ok(())
+            }
+            ["dos", "global", amount, size] => self.dos_global(amount, size),
+            ["dos", "cmd", cmd, amount, size, policy] => self.dos_cmd(cmd, amount, size, policy),
+            ["dos", "reset"] => {
+                self.limits.reset();
+                Ok(())
+            }
+            ["dos", "reset", "global"] => {
+                self.limits.reset_global();
+                Ok(())
+            }
+            ["dos", "reset", cmd] => parse(cmd).map(|cmd| self.limits.reset_packet(cmd)),
+            ["tick"] => {
+                self.tick(&mut output);
+                Ok(())
+            }
+            ["help"] => {
+                output.push_str(HELP);
+                Ok(())
+            }
+            [] => Err("empty command".to_string()),
+            _ => Err(format!("unknown command: {}", line.trim())),
+        };
+
+        match result {
+            Ok(()) => output.push_str("OK\n"),
+            Err(reason) => {
+                let _ = writeln!(output, "ERR {}", reason);
+            }
+        }
+        output
+    }
+
+    /// Serves the console, one task per connection.
+    pub async fn serve(self, listener: AdminListener<'_>) -> io::Result<()> {
+        match listener {
+            AdminListener::Tcp(addr) => {
+                let addrs = lookup_host(addr).await?.collect::<Vec<_>>();
+                if addrs.is_empty() || addrs.iter().any(|addr| !addr.ip().is_loopback()) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "the admin console only listens on loopback addresses",
+                    ));
+                }
+
+                let listener = TcpListener::bind(addrs.as_slice()).await?;
+                loop {
+                    let (socket, _) = listener.accept().await?;
+                    tokio::spawn(self.clone().handle(socket));
+                }
+            }
+            #[cfg(unix)]
+            AdminListener::Unix(path) => {
+                let listener = tokio::net::UnixListener::bind(path)?;
+                loop {
+                    let (socket, _) = listener.accept().await?;
+                    tokio::spawn(self.clone().handle(socket));
+                }
+            }
+        }
+    }
+
+    async fn handle<S>(self, socket: S)
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let (reader, mut writer) = tokio::io::split(socket);
+        let mut lines = BufReader::new(reader).lines();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            match line.trim() {
+                "" => continue,
+                "quit" => break,
+                line => {
+                    log_info!("Admin command: {}", line);
+                    if writer
+                        .write_all(self.execute(line).as_bytes())
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+        }
+        let _ = writer.shutdown().await;
+    }
+
+    fn sessions(&self, output: &mut String) {
+        for world_session in self.registry.sessions() {
+            let tools = world_session.socket_tools();
+            let _ = writeln!(
+                output,
+                "{} {} {}ms",
+                tools.id,
+                tools.socket_addr,
+                tools.get_latency()
+            );
+        }
+    }
+
+    fn kick(&self, id: &str) -> Result<(), String> {
+        let world_session = self
+            .registry
+            .get(parse(id)?)
+            .ok_or_else(|| format!("unknown session: {}", id))?;
+        let _ = world_session.socket_tools().close();
+        Ok(())
+    }
+
+    fn ban(&self, target: &str, output: &mut String) -> Result<(), String> {
+        let ip = match target.parse::<IpAddr>() {
+            Ok(ip) => ip,
+            Err(_) => self
+                .registry
+                .get(parse(target)?)
+                .ok_or_else(|| format!("unknown session: {}", target))?
+                .socket_tools()
+                .peer_addr()
+                .ok_or_else(|| format!("session without address: {}", target))?
+                .ip(),
+        };
+
+        self.registry.ban(ip);
+        for world_session in self.registry.sessions() {
+            let tools = world_session.socket_tools();
+            if tools.peer_addr().is_some_and(|addr| addr.ip() == ip) {
+                let _ = tools.close();
+            }
+        }
+        let _ = writeln!(output, "banned {}", ip);
+        Ok(())
+    }
+
+    fn unban(&self, ip: &str) -> Result<(), String> {
+        let ip = parse::<IpAddr>(ip)?;
+        if self.registry.unban(ip) {
+            Ok(())
+        } else {
+            Err(format!("not banned: {}", ip))
+        }
+    }
+
+    fn dos(&self, output: &mut String) {
+        match self.limits.global() {
+            Some((amount, size)) => {
+                let _ = writeln!(output, "global {} {}", amount, size);
+            }
+            None => output.push_str("global world\n"),
+        }
+        for (cmd, (amount, size, policy)) in self.limits.packets() {
+            let _ = writeln!(output, "cmd {} {} {} {:?}", cmd, amount, size, policy);
+        }
+    }
+
+    fn dos_global(&self, amount: &str, size: &str) -> Result<(), String> {
+        self.limits.set_global(parse(amount)?, parse(size)?);
+        Ok(())
+    }
+
+    fn dos_cmd(&self, cmd: &str, amount: &str, size: &str, policy: &str) -> Result<(), String> {
+        self.limits.set_packet(
+            parse(cmd)?,
+            parse(amount)?,
+            parse(size)?,
+            parse_policy(policy)?,
+        );
+        Ok(())
+    }
+
+    fn tick(&self, output: &mut String) {
+        let game_time = self.game_time.load();
+        let _ = writeln!(output, "timestamp {}", game_time.timestamp);
+        let _ = writeln!(output, "elapsed {}ms", game_time.elapsed.as_millis());
+        let _ = writeln!(output, "paused {}", self.control.is_paused());
+        let _ = writeln!(output, "speed {}", self.control.speed());
+        let _ = writeln!(output, "ticks {}", self.control.ticks());
+        let _ = writeln!(
+            output,
+            "last_tick {}us",
+            self.control.last_tick_duration().as_micros()
+        );
+        let _ = writeln!(
+            output,
+            "max_tick {}us",
+            self.control.max_tick_duration().as_micros()
+        );
+    }
+}
+
+impl<W> Clone for AdminConsole<W>
+where
+    W: World,
+{
+    fn clone(&self) -> Self {
+        Self {
+            registry: Arc::clone(&self.registry),
+            limits: Arc::clone(&self.limits),
+            control: self.control.clone(),
+            game_time: self.game_time,
+        }
+    }
+}
+
+impl<W> fmt::Debug for AdminConsole<W>
+where
+    W: World,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdminConsole")
+            .field("registry", &self.registry)
+            .field("limits", &self.limits)
+            .field("control", &self.control)
+            .finish()
+    }
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value: {}", value))
+}
+
+fn parse_policy(value: &str) -> Result<DosPolicy, String> {
+    match value {
+        "none" => Ok(DosPolicy::None),
+        "log" => Ok(DosPolicy::Log),
+        "close" => Ok(DosPolicy::Close),
+        _ => Err(format!("invalid policy: {}", value)),
+    }
+}
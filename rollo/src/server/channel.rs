@@ -0,0 +1,269 @@
+use super::world_socket::ContainerBytes;
+use crate::io::read::MAX_SIZE;
+use crate::packet::{to_fragment_bytes, FRAGMENT_HEADER_SIZE};
+use std::collections::VecDeque;
+
+/// Biggest fragment accepted by the reader of the receiver (payload below `MAX_SIZE`).
+const MAX_FRAGMENT_SIZE: usize = MAX_SIZE - 1 - FRAGMENT_HEADER_SIZE;
+
+/// A logical channel of a connection.
+///
+/// Each channel has its own queue. The channels with the highest priority are written first
+/// and the channels with the same priority share the socket according to their weight.
+/// Messages bigger than the fragment size are split so they can't monopolize the socket.
+///
+/// ```rust, no_run
+/// use rollo::server::Channel;
+///
+/// const MOVEMENT: Channel = Channel::new(1, 10, 1);
+/// const CHAT: Channel = Channel::new(2, 1, 1);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Channel {
+    id: u8,
+    priority: u8,
+    weight: u8,
+}
+
+impl Channel {
+    /// The channel used by `SocketTools::send`, written first and never fragmented.
+    pub const DEFAULT: Channel = Channel::new(0, u8::MAX, 1);
+
+    /// Creates a channel, the id 0 is the default channel and a weight of 0 is the same as 1.
+    pub const fn new(id: u8, priority: u8, weight: u8) -> Self {
+        Self {
+            id,
+            priority,
+            weight,
+        }
+    }
+
+    /// Returns the id of the channel.
+    pub const fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Returns the priority of the channel.
+    pub const fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    /// Returns the weight of the channel.
+    pub const fn weight(&self) -> u8 {
+        self.weight
+    }
+
+    fn credits(&self) -> u8 {
+        self.weight.max(1)
+    }
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+struct ChannelQueue {
+    channel: Channel,
+    messages: VecDeque<ContainerBytes>,
+    offset: usize,
+    credits: u8,
+}
+
+impl ChannelQueue {
+    fn new(channel: Channel) -> Self {
+        Self {
+            channel,
+            messages: VecDeque::new(),
+            offset: 0,
+            credits: channel.credits(),
+        }
+    }
+}
+
+/// Schedules the messages of the channels, one fragment at a time.
+pub(crate) struct ChannelScheduler {
+    fragment_size: usize,
+    queues: Vec<ChannelQueue>,
+    cursor: usize,
+}
+
+impl ChannelScheduler {
+    pub(crate) fn new(fragment_size: usize) -> Self {
+        Self {
+            fragment_size: fragment_size.clamp(1, MAX_FRAGMENT_SIZE),
+            queues: Vec::new(),
+            cursor: 0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, channel: Channel, bytes: ContainerBytes) {
+        if bytes.is_empty() {
+            return;
+        }
+
+        if let Some(queue) = self.queues.iter_mut().find(|q| q.channel.id == channel.id) {
+            queue.messages.push_back(bytes);
+        } else {
+            let mut queue = ChannelQueue::new(channel);
+            queue.messages.push_back(bytes);
+            self.queues.push(queue);
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.queues.iter().all(|q| q.messages.is_empty())
+    }
+
+    /// Returns the next bytes to write, a whole message or a fragment.
+    pub(crate) fn next_fragment(&mut self) -> Option<ContainerBytes> {
+        let index = self.select()?;
+        let fragment_size = self.fragment_size;
+        let queue = &mut self.queues[index];
+
+        queue.credits -= 1;
+        if queue.credits == 0 {
+            self.cursor = index + 1;
+        }
+
+        let message = queue.messages.front()?;
+        let len = message.bytes().len();
+
+        if queue.offset == 0 && len <= fragment_size {
+            return queue.messages.pop_front();
+        }
+
+        let end = (queue.offset + fragment_size).min(len);
+        let last = end == len;
+        let fragment =
+            to_fragment_bytes(queue.channel.id, last, &message.bytes()[queue.offset..end]);
+
+        if last {
+            queue.offset = 0;
+            queue.messages.pop_front();
+        } else {
+            queue.offset = end;
+        }
+
+        Some(fragment.into())
+    }
+
+    fn select(&mut self) -> Option<usize> {
+        let priority = self
+            .queues
+            .iter()
+            .filter(|q| !q.messages.is_empty())
+            .map(|q| q.channel.priority)
+            .max()?;
+        let len = self.queues.len();
+
+        for _ in 0..2 {
+            for i in 0..len {
+                let index = (self.cursor + i) % len;
+                let queue = &self.queues[index];
+
+                if !queue.messages.is_empty()
+                    && queue.channel.priority == priority
+                    && queue.credits > 0
+                {
+                    self.cursor = index;
+                    return Some(index);
+                }
+            }
+
+            self.queues
+                .iter_mut()
+                .filter(|q| q.channel.priority == priority)
+                .for_each(|q| q.credits = q.channel.credits());
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::{to_bytes, FRAGMENT_CMD};
+    use std::convert::TryInto;
+
+    fn message(cmd: u16, size: usize) -> ContainerBytes {
+        to_bytes(cmd, Some(&vec![cmd as u8; size])).into()
+    }
+
+    fn cmd(bytes: &ContainerBytes) -> u16 {
+        u16::from_be_bytes(bytes.bytes()[4..6].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_priority() {
+        let mut scheduler = ChannelScheduler::new(100);
+        scheduler.push(Channel::new(1, 1, 1), message(1, 10));
+        scheduler.push(Channel::new(2, 5, 1), message(2, 10));
+        scheduler.push(Channel::new(1, 1, 1), message(3, 10));
+
+        assert_eq!(cmd(&scheduler.next_fragment().unwrap()), 2);
+        assert_eq!(cmd(&scheduler.next_fragment().unwrap()), 1);
+        assert_eq!(cmd(&scheduler.next_fragment().unwrap()), 3);
+        assert!(scheduler.next_fragment().is_none());
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn test_weight() {
+        let mut scheduler = ChannelScheduler::new(100);
+        for _ in 0..4 {
+            scheduler.push(Channel::new(1, 1, 2), message(1, 10));
+            scheduler.push(Channel::new(2, 1, 1), message(2, 10));
+        }
+
+        let order: Vec<u16> = (0..6)
+            .map(|_| cmd(&scheduler.next_fragment().unwrap()))
+            .collect();
+        assert_eq!(order, [1, 1, 2, 1, 1, 2]);
+    }
+
+    #[test]
+    fn test_fragment() {
+        let mut scheduler = ChannelScheduler::new(8);
+        scheduler.push(Channel::new(3, 1, 1), message(9, 10));
+        scheduler.push(Channel::new(4, 2, 1), message(4, 1));
+
+        // Whole message of the channel with the highest priority.
+        assert_eq!(cmd(&scheduler.next_fragment().unwrap()), 4);
+
+        let mut data = Vec::new();
+        while let Some(fragment) = scheduler.next_fragment() {
+            let bytes = fragment.bytes();
+            let size = u32::from_be_bytes(bytes[..4].try_into().unwrap()) as usize;
+            assert_eq!(size, bytes.len() - 6);
+            assert_eq!(cmd(&fragment), FRAGMENT_CMD);
+            assert_eq!(bytes[6], 3);
+            data.extend_from_slice(&bytes[8..]);
+
+            if bytes[7] == 1 {
+                break;
+            }
+        }
+
+        assert!(scheduler.is_empty());
+        assert_eq!(data, *to_bytes(9, Some(&[9; 10])));
+    }
+
+    #[test]
+    fn test_max_fragment_size() {
+        let mut scheduler = ChannelScheduler::new(usize::MAX);
+        scheduler.push(Channel::new(3, 1, 1), message(9, MAX_SIZE * 2));
+
+        let mut fragments = 0;
+        while let Some(fragment) = scheduler.next_fragment() {
+            let bytes = fragment.bytes();
+            let size = u32::from_be_bytes(bytes[..4].try_into().unwrap()) as usize;
+            assert!(size < MAX_SIZE);
+            fragments += 1;
+        }
+
+        assert_eq!(fragments, 3);
+    }
+}
@@ -0,0 +1,561 @@
+use super::{registry::SessionRegistry, world::World, world_session::WorldSession};
+use crate::{
+    error::{Error, Result},
+    io::read::{Reader, MAX_SIZE},
+    packet::{to_bytes, to_routed_bytes, Packet, ROUTED_HEADER_SIZE},
+};
+use easy_pool::PoolObjectContainer;
+use parking_lot::RwLock;
+use ring::{
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    fmt, mem,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{split, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+        watch,
+    },
+    time::{interval, sleep, timeout},
+};
+
+/// Id of a node of a cluster.
+pub type NodeId = u32;
+
+const LINK_HELLO: u16 = 1;
+const LINK_AUTH: u16 = 2;
+const LINK_PING: u16 = 3;
+const LINK_SESSION: u16 = 4;
+const LINK_WORLD: u16 = 5;
+
+const NONCE_SIZE: usize = 32;
+const HELLO_SIZE: usize = mem::size_of::<NodeId>() + NONCE_SIZE;
+const AUTH_CONTEXT: &[u8] = b"rollo-cluster";
+const INITIATOR: u8 = 0;
+const RESPONDER: u8 = 1;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const DEFAULT_HEARTBEAT: Duration = Duration::from_secs(5);
+
+/// # Cluster Configuration
+/// The node, the secret shared by the nodes and the static list of the other nodes.
+///
+/// A node connects to the nodes with a greater id, the nodes with a lower id connect to it.
+/// The links use the packet framing of the sessions, the nodes prove they know the secret
+/// (HMAC-SHA256 of both challenges) in both directions, the connecting node first.
+/// ## Usage
+/// ```rust, no_run
+/// use rollo::server::{ClusterConfig, World, WorldSocketMgr};
+///
+/// # async fn test<W: World + 'static>(world: &'static W) {
+/// let server = WorldSocketMgr::new(world);
+/// let config = ClusterConfig::new(2, "secret")
+///     .with_listen("10.0.0.2:7000")
+///     .with_peer(1, "10.0.0.1:7000")
+///     .with_peer(3, "10.0.0.3:7000");
+///
+/// let cluster = server.start_cluster(config).await.unwrap();
+/// cluster.send_to_session(3, 42, 10, Some(&[1, 2, 3])).unwrap();
+/// cluster.send_to_world(1, 20, None).unwrap();
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct ClusterConfig {
+    node_id: NodeId,
+    secret: Vec<u8>,
+    listen: Option<String>,
+    peers: HashMap<NodeId, String>,
+    heartbeat: Duration,
+}
+
+impl ClusterConfig {
+    /// Node with its id and the secret of the cluster.
+    pub fn new(node_id: NodeId, secret: impl Into<Vec<u8>>) -> Self {
+        Self {
+            node_id,
+            secret: secret.into(),
+            listen: None,
+            peers: HashMap::new(),
+            heartbeat: DEFAULT_HEARTBEAT,
+        }
+    }
+
+    /// Address where the nodes with a lower id connect.
+    pub fn with_listen(mut self, addr: impl Into<String>) -> Self {
+        self.listen = Some(addr.into());
+        self
+    }
+
+    /// Adds a node of the cluster.
+    pub fn with_peer(mut self, node_id: NodeId, addr: impl Into<String>) -> Self {
+        self.peers.insert(node_id, addr.into());
+        self
+    }
+
+    /// Interval of the pings, a link without data during 3 intervals is closed.
+    pub fn with_heartbeat(mut self, heartbeat: Duration) -> Self {
+        self.heartbeat = heartbeat;
+        self
+    }
+}
+
+impl fmt::Debug for ClusterConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClusterConfig")
+            .field("node_id", &self.node_id)
+            .field("listen", &self.listen)
+            .field("peers", &self.peers)
+            .field("heartbeat", &self.heartbeat)
+            .finish()
+    }
+}
+
+/// # Cluster
+/// Links of a node with the other nodes, returned by `WorldSocketMgr::start_cluster`.
+///
+/// The messages are sent to a session (to its client) or to the world of another node
+/// (`World::on_node_message`). The links are reconnected, `World::on_node_up` and
+/// `World::on_node_down` are called when they change.
+#[derive(Debug, Clone)]
+pub struct Cluster {
+    state: Arc<ClusterState>,
+}
+
+#[derive(Debug)]
+struct ClusterState {
+    node_id: NodeId,
+    links: RwLock<HashMap<NodeId, Link>>,
+    generation: AtomicU64,
+    shutdown: watch::Sender<bool>,
+}
+
+#[derive(Debug)]
+struct Link {
+    generation: u64,
+    sender: UnboundedSender<PoolObjectContainer<Vec<u8>>>,
+}
+
+impl Cluster {
+    /// Id of this node.
+    pub fn node_id(&self) -> NodeId {
+        self.state.node_id
+    }
+
+    /// The connected nodes, sorted by id.
+    pub fn nodes(&self) -> Vec<NodeId> {
+        let mut nodes = self.state.links.read().keys().copied().collect::<Vec<_>>();
+        nodes.sort_unstable();
+        nodes
+    }
+
+    /// Returns true if the node is connected.
+    pub fn is_connected(&self, node: NodeId) -> bool {
+        self.state.links.read().contains_key(&node)
+    }
+
+    /// Sends a packet to the client of a session of another node.
+    pub fn send_to_session(
+        &self,
+        node: NodeId,
+        session: u64,
+        cmd: u16,
+        payload: Option<&[u8]>,
+    ) -> Result<()> {
+        self.send(node, LINK_SESSION, session, cmd, payload)
+    }
+
+    /// Sends a packet to the world of another node (`World::on_node_message`).
+    pub fn send_to_world(&self, node: NodeId, cmd: u16, payload: Option<&[u8]>) -> Result<()> {
+        self.send(node, LINK_WORLD, 0, cmd, payload)
+    }
+
+    /// Closes the links and stops the connections.
+    pub fn shutdown(&self) {
+        self.state.shutdown.send_replace(true);
+    }
+
+    fn send(
+        &self,
+        node: NodeId,
+        link_cmd: u16,
+        target: u64,
+        cmd: u16,
+        payload: Option<&[u8]>,
+    ) -> Result<()> {
+        let payload = payload.unwrap_or_default();
+        if ROUTED_HEADER_SIZE + payload.len() >= MAX_SIZE {
+            return Err(Error::PacketSize);
+        }
+
+        self.state
+            .links
+            .read()
+            .get(&node)
+            .ok_or(Error::NodeUnavailable)?
+            .sender
+            .send(to_routed_bytes(link_cmd, target, cmd, payload))
+            .map_err(|_| Error::NodeUnavailable)
+    }
+}
+
+pub(crate) async fn start<W>(
+    world: &'static W,
+    registry: Arc<SessionRegistry<W>>,
+    config: ClusterConfig,
+) -> Result<Cluster>
+where
+    W: Send + Sync + 'static + World,
+{
+    let listener = match &config.listen {
+        Some(addr) => Some(TcpListener::bind(addr).await.map_err(|_| Error::Bind)?),
+        None => None,
+    };
+
+    let state = Arc::new(ClusterState {
+        node_id: config.node_id,
+        links: RwLock::new(HashMap::new()),
+        generation: AtomicU64::new(0),
+        shutdown: watch::channel(false).0,
+    });
+    let node = Arc::new(Node {
+        world,
+        registry,
+        key: hmac::Key::new(hmac::HMAC_SHA256, &config.secret),
+        config,
+        state: Arc::clone(&state),
+    });
+
+    if let Some(listener) = listener {
+        tokio::spawn(Arc::clone(&node).listen(listener));
+    }
+    for (peer, addr) in node.config.peers.iter() {
+        if *peer > node.config.node_id {
+            tokio::spawn(Arc::clone(&node).dial(*peer, addr.clone()));
+        }
+    }
+
+    Ok(Cluster { state })
+}
+
+struct Node<W>
+where
+    W: World + 'static,
+{
+    world: &'static W,
+    registry: Arc<SessionRegistry<W>>,
+    key: hmac::Key,
+    config: ClusterConfig,
+    state: Arc<ClusterState>,
+}
+
+impl<W> Node<W>
+where
+    W: Send + Sync + 'static + World,
+{
+    async fn listen(self: Arc<Self>, listener: TcpListener) {
+        let mut shutdown = self.state.shutdown.subscribe();
+
+        loop {
+            tokio::select! {
+                result = listener.accept() => {
+                    if let Ok((socket, _)) = result {
+                        let _ = socket.set_nodelay(true);
+                        tokio::spawn(Arc::clone(&self).link(socket, None));
+                    }
+                }
+                _ = stopped(&mut shutdown) => return,
+            }
+        }
+    }
+
+    async fn dial(self: Arc<Self>, peer: NodeId, addr: String) {
+        let mut shutdown = self.state.shutdown.subscribe();
+
+        loop {
+            if let Ok(Ok(socket)) = timeout(HANDSHAKE_TIMEOUT, TcpStream::connect(&addr)).await {
+                let _ = socket.set_nodelay(true);
+                Arc::clone(&self).link(socket, Some(peer)).await;
+            }
+
+            tokio::select! {
+                _ = sleep(RECONNECT_DELAY) => {}
+                _ = stopped(&mut shutdown) => return,
+            }
+        }
+    }
+
+    async fn link<S>(self: Arc<Self>, socket: S, expected: Option<NodeId>)
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let (reader, mut writer) = split(socket);
+        let mut reader = BufReader::new(reader);
+
+        let handshake = self.handshake(&mut reader, &mut writer, expected);
+        let node = match timeout(HANDSHAKE_TIMEOUT, handshake).await {
+            Ok(Ok(node)) => node,
+            Ok(Err(error)) => {
+                log_info!("Cluster link refused: {:?}.", error);
+                return;
+            }
+            Err(_) => {
+                log_info!("Cluster link refused: timeout.");
+                return;
+            }
+        };
+
+        let (sender, receiver) = unbounded_channel();
+        let generation = self.state.generation.fetch_add(1, Ordering::AcqRel);
+        self.state
+            .links
+            .write()
+            .insert(node, Link { generation, sender });
+        log_info!("Node {} is up.", node);
+        self.world.on_node_up(node);
+
+        let mut shutdown = self.state.shutdown.subscribe();
+        tokio::select! {
+            result = self.read_link(node, &mut reader) => {
+                log_debug!("Link with node {} closed: {:?}.", node, result);
+            }
+            _ = Self::write_link(&mut writer, receiver, self.config.heartbeat) => {}
+            _ = stopped(&mut shutdown) => {}
+        }
+        let _ = writer.shutdown().await;
+
+        let current = {
+            let mut links = self.state.links.write();
+            let current = links.get(&node).map(|link| link.generation) == Some(generation);
+            if current {
+                links.remove(&node);
+            }
+            current
+        };
+        if current {
+            log_info!("Node {} is down.", node);
+            self.world.on_node_down(node);
+        }
+    }
+
+    // Returns the id of the other node once both nodes are authenticated.
+    //
+    // The node which connects (`expected`) is the initiator, it proves first that it knows the
+    // secret. The responder only signs once the initiator is authenticated, a tag is bound to the
+    // role, both ids and both nonces.
+    async fn handshake<R, T>(
+        &self,
+        reader: &mut R,
+        writer: &mut T,
+        expected: Option<NodeId>,
+    ) -> Result<NodeId>
+    where
+        R: AsyncRead + Unpin,
+        T: AsyncWrite + Unpin,
+    {
+        let node_id = self.config.node_id;
+        let initiator = expected.is_some();
+        let mut nonce = [0; NONCE_SIZE];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| Error::ClusterAuth)?;
+
+        let mut hello = Vec::with_capacity(HELLO_SIZE);
+        hello.extend_from_slice(&node_id.to_be_bytes());
+        hello.extend_from_slice(&nonce);
+        if initiator {
+            write_packet(writer, LINK_HELLO, &hello).await?;
+        }
+
+        let peer_hello = match read_packet(reader).await? {
+            Packet {
+                cmd: LINK_HELLO,
+                payload: Some(payload),
+            } if payload.len() == HELLO_SIZE => payload,
+            _ => return Err(Error::ClusterAuth),
+        };
+        let peer = NodeId::from_be_bytes(
+            peer_hello[..mem::size_of::<NodeId>()]
+                .try_into()
+                .map_err(|_| Error::ClusterAuth)?,
+        );
+        if peer == node_id
+            || !self.config.peers.contains_key(&peer)
+            || expected.is_some_and(|expected| expected != peer)
+        {
+            return Err(Error::ClusterAuth);
+        }
+
+        if !initiator {
+            write_packet(writer, LINK_HELLO, &hello).await?;
+        }
+
+        let peer_nonce = &peer_hello[mem::size_of::<NodeId>()..];
+        let transcript = if initiator {
+            Transcript::new(node_id, &nonce, peer, peer_nonce)
+        } else {
+            Transcript::new(peer, peer_nonce, node_id, &nonce)
+        };
+        let own_role = if initiator { INITIATOR } else { RESPONDER };
+        let tag = hmac::sign(&self.key, &transcript.auth_message(own_role));
+
+        if initiator {
+            write_packet(writer, LINK_AUTH, tag.as_ref()).await?;
+        }
+
+        match read_packet(reader).await? {
+            Packet {
+                cmd: LINK_AUTH,
+                payload: Some(peer_tag),
+            } => {
+                let peer_role = if initiator { RESPONDER } else { INITIATOR };
+                hmac::verify(&self.key, &transcript.auth_message(peer_role), &peer_tag)
+                    .map_err(|_| Error::ClusterAuth)?
+            }
+            _ => return Err(Error::ClusterAuth),
+        }
+
+        if !initiator {
+            write_packet(writer, LINK_AUTH, tag.as_ref()).await?;
+        }
+
+        Ok(peer)
+    }
+
+    async fn read_link<R>(&self, node: NodeId, reader: &mut R) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let limit = self.config.heartbeat * 3;
+
+        loop {
+            let packet = timeout(limit, read_packet(reader))
+                .await
+                .map_err(|_| Error::TimeoutReading)??;
+
+            match packet.cmd {
+                LINK_PING => {}
+                LINK_SESSION => {
+                    let (session, packet) = packet.into_routed()?;
+                    match self.registry.get(session) {
+                        Some(world_session) => world_session
+                            .socket_tools()
+                            .send(packet.cmd, packet.payload.as_deref().map(|p| p.as_slice())),
+                        None => log_debug!("Unknown session {} from node {}.", session, node),
+                    }
+                }
+                LINK_WORLD => {
+                    let (_, packet) = packet.into_routed()?;
+                    self.world.on_node_message(node, packet);
+                }
+                _ => return Err(Error::PacketPayload),
+            }
+        }
+    }
+
+    async fn write_link<T>(
+        writer: &mut T,
+        mut receiver: UnboundedReceiver<PoolObjectContainer<Vec<u8>>>,
+        heartbeat: Duration,
+    ) where
+        T: AsyncWrite + Unpin,
+    {
+        let mut ping = interval(heartbeat);
+
+        loop {
+            let result = tokio::select! {
+                message = receiver.recv() => match message {
+                    Some(bytes) => writer.write_all(&bytes).await,
+                    None => return,
+                },
+                _ = ping.tick() => writer.write_all(&to_bytes(LINK_PING, None)).await,
+            };
+
+            if result.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+// The hellos of a handshake, signed by both nodes with their role.
+struct Transcript<'a> {
+    initiator: NodeId,
+    initiator_nonce: &'a [u8],
+    responder: NodeId,
+    responder_nonce: &'a [u8],
+}
+
+impl<'a> Transcript<'a> {
+    fn new(
+        initiator: NodeId,
+        initiator_nonce: &'a [u8],
+        responder: NodeId,
+        responder_nonce: &'a [u8],
+    ) -> Self {
+        Self {
+            initiator,
+            initiator_nonce,
+            responder,
+            responder_nonce,
+        }
+    }
+
+    fn auth_message(&self, role: u8) -> Vec<u8> {
+        let mut message = Vec::with_capacity(AUTH_CONTEXT.len() + 1 + 2 * HELLO_SIZE);
+        message.extend_from_slice(AUTH_CONTEXT);
+        message.push(role);
+        message.extend_from_slice(&self.initiator.to_be_bytes());
+        message.extend_from_slice(self.initiator_nonce);
+        message.extend_from_slice(&self.responder.to_be_bytes());
+        message.extend_from_slice(self.responder_nonce);
+        message
+    }
+}
+
+async fn write_packet<T>(writer: &mut T, cmd: u16, payload: &[u8]) -> Result<()>
+where
+    T: AsyncWrite + Unpin,
+{
+    writer
+        .write_all(&to_bytes(cmd, Some(payload)))
+        .await
+        .map_err(|_| Error::Channel)
+}
+
+async fn read_packet<R>(reader: &mut R) -> Result<Packet>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut reader = Reader::new(reader);
+    let size = reader.read_size().await?;
+    let cmd = reader.read_cmd().await?;
+
+    if size >= MAX_SIZE {
+        Err(Error::PacketSize)
+    } else if size == 0 {
+        Ok(Packet::new(cmd, None))
+    } else {
+        Ok(Packet::new(cmd, reader.read_payload(size).await?))
+    }
+}
+
+// Waits until the cluster is shut down.
+async fn stopped(shutdown: &mut watch::Receiver<bool>) {
+    while !*shutdown.borrow_and_update() {
+        if shutdown.changed().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
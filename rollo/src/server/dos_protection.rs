@@ -1,3 +1,5 @@
+use super::world::World;
+use parking_lot::RwLock;
 use std::{
     collections::HashMap,
     fmt::{Debug, Formatter},
@@ -78,9 +80,7 @@ impl DosProtection {
 
 impl Debug for DosProtection {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
-        f.debug_map()
-            .entries(self.counters.iter().map(|(k, v)| (k, v)))
-            .finish()
+        f.debug_map().entries(self.counters.iter()).finish()
     }
 }
 
@@ -101,13 +101,94 @@ impl GlobalCounter {
 }
 
 /// Policy if session exceed the limit.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "config", derive(serde::Deserialize))]
+#[cfg_attr(feature = "config", serde(rename_all = "lowercase"))]
 pub enum DosPolicy {
     Close,
     Log,
     None,
 }
 
+/// # DoS Limits
+/// Replaces the limits of the `World` at runtime (`WorldSocketMgr::dos_limits`).
+///
+/// The limits without replacement are read from `World::global_limit` and
+/// `World::get_packet_limit`.
+/// ## Usage
+/// ```rust, no_run
+/// use rollo::server::{DosPolicy, World, WorldSocketMgr};
+///
+/// # fn test<W: World + 'static>(world: &'static W) {
+/// let server = WorldSocketMgr::new(world);
+/// let limits = server.dos_limits();
+///
+/// limits.set_global(100, 10 * 1024);
+/// limits.set_packet(10, 30, 1024, DosPolicy::Close);
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct DosLimits {
+    global: RwLock<Option<(u16, u32)>>,
+    packets: RwLock<HashMap<u16, (u16, u32, DosPolicy)>>,
+}
+
+impl DosLimits {
+    /// Replaces the global limit (amount and size per second).
+    pub fn set_global(&self, amount: u16, size: u32) {
+        *self.global.write() = Some((amount, size));
+    }
+
+    /// Replaces the limit of a command (amount and size per second, policy).
+    pub fn set_packet(&self, cmd: u16, amount: u16, size: u32, policy: DosPolicy) {
+        self.packets.write().insert(cmd, (amount, size, policy));
+    }
+
+    /// Restores the global limit of the `World`.
+    pub fn reset_global(&self) {
+        *self.global.write() = None;
+    }
+
+    /// Restores the limit of a command of the `World`.
+    pub fn reset_packet(&self, cmd: u16) {
+        self.packets.write().remove(&cmd);
+    }
+
+    /// Restores all the limits of the `World`.
+    pub fn reset(&self) {
+        self.reset_global();
+        self.packets.write().clear();
+    }
+
+    /// Returns the replaced global limit.
+    pub fn global(&self) -> Option<(u16, u32)> {
+        *self.global.read()
+    }
+
+    /// Returns the replaced limits of the commands.
+    pub fn packets(&self) -> Vec<(u16, (u16, u32, DosPolicy))> {
+        let mut packets = self
+            .packets
+            .read()
+            .iter()
+            .map(|(cmd, limit)| (*cmd, *limit))
+            .collect::<Vec<_>>();
+        packets.sort_unstable_by_key(|(cmd, _)| *cmd);
+        packets
+    }
+
+    pub(crate) fn global_limit(&self, world: &impl World) -> (u16, u32) {
+        self.global().unwrap_or_else(|| world.global_limit())
+    }
+
+    pub(crate) fn packet_limit(&self, world: &impl World, cmd: u16) -> (u16, u32, DosPolicy) {
+        match self.packets.read().get(&cmd) {
+            Some(limit) => *limit,
+            None => world.get_packet_limit(cmd),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 struct PacketCounter {
     last_receive_time: i64,
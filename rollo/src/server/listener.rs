@@ -0,0 +1,186 @@
+use super::{
+    tls::load_config,
+    world_socket_mgr::{ListenerSecurity, WorldSocketConfiguration},
+};
+use crate::error::{Error, Result};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    net::{SocketAddr, ToSocketAddrs},
+    sync::Arc,
+};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::TlsAcceptor;
+
+#[cfg(unix)]
+use std::path::Path;
+#[cfg(unix)]
+use tokio::net::{UnixListener, UnixStream};
+
+/// Connections accepted before they are refused by the system.
+const BACKLOG: i32 = 1024;
+
+/// # Listener
+/// An address served by `WorldSocketMgr::start_listeners`, with its own security and configuration.
+///
+/// With `reuse_port` (SO_REUSEPORT), the same address can be served by several listeners.
+/// ## Usage
+/// ```rust, no_run
+/// use rollo::server::{Listener, ListenerSecurity, World, WorldSocketConfiguration, WorldSocketMgr};
+/// use std::path::Path;
+///
+/// # async fn test<W: World + 'static>(world: &'static W) {
+/// let mut server = WorldSocketMgr::new(world);
+///
+/// let security = ListenerSecurity::Tls(Path::new("cert.pem"), Path::new("key.pem"));
+/// server
+///     .start_listeners(&[
+///         Listener::tcp("0.0.0.0:6666").with_security(security),
+///         Listener::tcp("10.0.0.1:6667")
+///             .with_configuration(WorldSocketConfiguration::with_custom_configuration(true, 60)),
+///         Listener::unix(Path::new("/run/game/gateway.sock")),
+///     ])
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Listener<'a> {
+    address: ListenerAddress<'a>,
+    security: ListenerSecurity<'a>,
+    pub(crate) configuration: Option<WorldSocketConfiguration>,
+    reuse_port: bool,
+}
+
+/// Where a `Listener` accepts the connections.
+#[derive(Debug, Clone, Copy)]
+pub enum ListenerAddress<'a> {
+    Tcp(&'a str),
+    /// The sessions have no peer address (`SocketTools::peer_addr`), the bans don't apply.
+    #[cfg(unix)]
+    Unix(&'a Path),
+}
+
+impl<'a> Listener<'a> {
+    /// Listens on a TCP address.
+    pub const fn tcp(addr: &'a str) -> Self {
+        Self::new(ListenerAddress::Tcp(addr))
+    }
+
+    /// Listens on a Unix socket, the file must not exist.
+    #[cfg(unix)]
+    pub const fn unix(path: &'a Path) -> Self {
+        Self::new(ListenerAddress::Unix(path))
+    }
+
+    const fn new(address: ListenerAddress<'a>) -> Self {
+        Self {
+            address,
+            security: ListenerSecurity::Tcp,
+            configuration: None,
+            reuse_port: false,
+        }
+    }
+
+    /// Tcp or Tcp/Tls (Tcp by default).
+    pub const fn with_security(mut self, security: ListenerSecurity<'a>) -> Self {
+        self.security = security;
+        self
+    }
+
+    /// Configuration of the sessions of the listener (the one of the WorldSocketMgr by default).
+    pub const fn with_configuration(mut self, configuration: WorldSocketConfiguration) -> Self {
+        self.configuration = Some(configuration);
+        self
+    }
+
+    /// Sets SO_REUSEPORT on the TCP socket.
+    pub const fn with_reuse_port(mut self, reuse_port: bool) -> Self {
+        self.reuse_port = reuse_port;
+        self
+    }
+
+    pub(crate) fn bind(&self) -> Result<BoundListener> {
+        let tls_acceptor = match self.security {
+            ListenerSecurity::Tcp => None,
+            ListenerSecurity::Tls(certificate, key) => {
+                let config = load_config(certificate, key).map_err(|_| Error::TlsConfig)?;
+                Some(TlsAcceptor::from(Arc::new(config)))
+            }
+        };
+
+        let socket = match self.address {
+            ListenerAddress::Tcp(addr) => SocketListener::Tcp(self.bind_tcp(addr)?),
+            #[cfg(unix)]
+            ListenerAddress::Unix(path) => {
+                SocketListener::Unix(UnixListener::bind(path).map_err(|_| Error::Bind)?)
+            }
+        };
+
+        Ok(BoundListener {
+            socket,
+            tls_acceptor,
+        })
+    }
+
+    fn bind_tcp(&self, addr: &str) -> Result<TcpListener> {
+        let addr = addr
+            .to_socket_addrs()
+            .map_err(|_| Error::Bind)?
+            .next()
+            .ok_or(Error::Bind)?;
+
+        let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))
+            .map_err(|_| Error::Bind)?;
+        #[cfg(unix)]
+        {
+            socket.set_reuse_address(true).map_err(|_| Error::Bind)?;
+            socket
+                .set_reuse_port(self.reuse_port)
+                .map_err(|_| Error::Bind)?;
+        }
+        #[cfg(not(unix))]
+        if self.reuse_port {
+            return Err(Error::Bind);
+        }
+        socket.set_nonblocking(true).map_err(|_| Error::Bind)?;
+        socket.bind(&addr.into()).map_err(|_| Error::Bind)?;
+        socket.listen(BACKLOG).map_err(|_| Error::Bind)?;
+
+        TcpListener::from_std(socket.into()).map_err(|_| Error::Bind)
+    }
+}
+
+pub(crate) struct BoundListener {
+    pub(crate) socket: SocketListener,
+    pub(crate) tls_acceptor: Option<TlsAcceptor>,
+}
+
+pub(crate) enum SocketListener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+pub(crate) enum Connection {
+    Tcp(TcpStream),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl SocketListener {
+    pub(crate) async fn accept(&self) -> Option<(Connection, Option<SocketAddr>)> {
+        match self {
+            SocketListener::Tcp(listener) => listener
+                .accept()
+                .await
+                .ok()
+                .map(|(socket, addr)| (Connection::Tcp(socket), Some(addr))),
+            #[cfg(unix)]
+            SocketListener::Unix(listener) => listener
+                .accept()
+                .await
+                .ok()
+                .map(|(socket, _)| (Connection::Unix(socket), None)),
+        }
+    }
+}
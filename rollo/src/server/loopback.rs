@@ -0,0 +1,81 @@
+use crate::{
+    error::{Error, Result},
+    io::read::{Reader, MAX_SIZE},
+    packet::{to_bytes, Packet},
+};
+use tokio::io::{split, AsyncWriteExt, BufReader, DuplexStream, ReadHalf, WriteHalf};
+
+/// # Loopback Client
+/// An in-memory client connected to a session, opened by `WorldSocketMgr::open_loopback`.
+///
+/// Tests the `WorldSession` without socket (no port, in parallel).
+/// ## Usage
+/// ```rust, no_run
+/// # use rollo::server::{World, WorldSocketMgr};
+/// # async fn test<W: World + 'static>(world: &'static W) {
+/// let mut server = WorldSocketMgr::new(world);
+///
+/// for mut client in server.open_loopbacks(10) {
+///     client.send(10, None).await.unwrap();
+///     assert_eq!(client.recv().await.unwrap().cmd, 10);
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct LoopbackClient {
+    id: u64,
+    reader: BufReader<ReadHalf<DuplexStream>>,
+    writer: WriteHalf<DuplexStream>,
+}
+
+impl LoopbackClient {
+    pub(crate) fn new(id: u64, stream: DuplexStream) -> Self {
+        let (reader, writer) = split(stream);
+
+        Self {
+            id,
+            reader: BufReader::new(reader),
+            writer,
+        }
+    }
+
+    /// Id of the session (`SocketTools::id`).
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Sends a packet to the session.
+    pub async fn send(&mut self, cmd: u16, payload: Option<&[u8]>) -> Result<()> {
+        self.send_bytes(&to_bytes(cmd, payload)).await
+    }
+
+    /// Sends raw bytes to the session (header included).
+    pub async fn send_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.writer
+            .write_all(bytes)
+            .await
+            .map_err(|_| Error::Channel)
+    }
+
+    /// Waits for the next packet sent by the session, returns an error if the session is closed.
+    pub async fn recv(&mut self) -> Result<Packet> {
+        let mut reader = Reader::new(&mut self.reader);
+        let size = reader.read_size().await?;
+        let cmd = reader.read_cmd().await?;
+
+        if size >= MAX_SIZE {
+            return Err(Error::PacketSize);
+        }
+
+        if size == 0 {
+            Ok(Packet::new(cmd, None))
+        } else {
+            Ok(Packet::new(cmd, reader.read_payload(size).await?))
+        }
+    }
+
+    /// Closes the connection.
+    pub async fn close(mut self) {
+        let _ = self.writer.shutdown().await;
+    }
+}
@@ -1,15 +1,45 @@
+mod channel;
+pub use channel::Channel;
+
+mod admin;
+pub use admin::{AdminConsole, AdminListener};
+
+mod cluster;
+pub use cluster::{Cluster, ClusterConfig, NodeId};
+
 mod dos_protection;
-pub use dos_protection::DosPolicy;
+pub use dos_protection::{DosLimits, DosPolicy};
 
 pub(crate) mod world;
 pub use world::World;
 
 pub(crate) mod world_session;
-pub use world_session::{SocketTools, WorldSession};
+pub use world_session::{SocketTools, WorldSession, DEFAULT_REQUEST_TIMEOUT};
 
 mod world_socket_mgr;
-pub use world_socket_mgr::{ListenerSecurity, WorldSocketConfiguration, WorldSocketMgr};
+pub use world_socket_mgr::{
+    FlushPolicy, ListenerSecurity, WorldSocketConfiguration, WorldSocketMgr,
+};
 
 pub(crate) mod world_socket;
 
+mod listener;
+pub use listener::{Listener, ListenerAddress};
+
+mod loopback;
+pub use loopback::LoopbackClient;
+
+mod recorder;
+pub use recorder::{Direction, PacketRecorder, RecordedPacket};
+
+mod registry;
+
+mod replay;
+pub use replay::{Replay, ReplayTiming};
+
+mod rpc;
+
+pub(crate) mod stream;
+pub use stream::ByteStream;
+
 mod tls;
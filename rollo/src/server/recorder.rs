@@ -0,0 +1,346 @@
+use crate::{
+    game::Clock,
+    io::read::MAX_SIZE,
+    packet::{frames, Packet},
+};
+use parking_lot::Mutex;
+use std::{
+    convert::TryInto,
+    fmt,
+    fs::File,
+    io::{self, BufReader, BufWriter, ErrorKind, Read, Write},
+    mem,
+    path::Path,
+    sync::Arc,
+};
+
+const MAGIC: &[u8; 4] = b"RLRC";
+const VERSION: u8 = 1;
+const RECORD_HEADER_SIZE: usize = mem::size_of::<u8>()
+    + mem::size_of::<u64>()
+    + mem::size_of::<i64>()
+    + mem::size_of::<u16>()
+    + mem::size_of::<u32>();
+
+/// Direction of a recorded packet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Received from the session.
+    Inbound,
+    /// Sent to the session.
+    Outbound,
+}
+
+/// A packet of a recording.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedPacket {
+    pub direction: Direction,
+    pub session_id: u64,
+    /// Milliseconds since UNIX_EPOCH (clock of the `WorldSocketMgr`).
+    pub timestamp: i64,
+    pub cmd: u16,
+    pub payload: Vec<u8>,
+}
+
+impl RecordedPacket {
+    /// Reads a recording, a truncated last record (crash of the server) is ignored.
+    ///
+    /// A record bigger than the maximum size of a packet is rejected (`ErrorKind::InvalidData`).
+    pub fn read_all(reader: impl Read) -> io::Result<Vec<Self>> {
+        let mut reader = BufReader::new(reader);
+
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC || header[4] != VERSION {
+            return Err(io::Error::new(ErrorKind::InvalidData, "not a recording"));
+        }
+
+        let mut packets = Vec::new();
+        loop {
+            match Self::read(&mut reader) {
+                Ok(Some(packet)) => packets.push(packet),
+                Ok(None) => return Ok(packets),
+                Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(packets),
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    fn read(reader: &mut impl Read) -> io::Result<Option<Self>> {
+        let mut header = [0; RECORD_HEADER_SIZE];
+        if reader.read(&mut header[..1])? == 0 {
+            return Ok(None);
+        }
+        reader.read_exact(&mut header[1..])?;
+
+        let direction = match header[0] {
+            0 => Direction::Inbound,
+            1 => Direction::Outbound,
+            _ => return Err(io::Error::new(ErrorKind::InvalidData, "invalid direction")),
+        };
+        let session_id = u64::from_be_bytes(header[1..9].try_into().unwrap());
+        let timestamp = i64::from_be_bytes(header[9..17].try_into().unwrap());
+        let cmd = u16::from_be_bytes(header[17..19].try_into().unwrap());
+        let size = u32::from_be_bytes(header[19..23].try_into().unwrap()) as usize;
+
+        // The packets read and written by the sessions are below the maximum size.
+        if size >= MAX_SIZE {
+            return Err(io::Error::new(ErrorKind::InvalidData, "invalid size"));
+        }
+
+        let mut payload = vec![0; size];
+        reader.read_exact(&mut payload)?;
+
+        Ok(Some(Self {
+            direction,
+            session_id,
+            timestamp,
+            cmd,
+            payload,
+        }))
+    }
+}
+
+/// # Packet Recorder
+/// Writes the packets received and sent by the sessions (`WorldSocketMgr::with_recorder`).
+///
+/// A record is `[u8 direction][u64 session id][i64 timestamp][u16 cmd][u32 size][payload]`.
+/// ## Usage
+/// ```rust, no_run
+/// use rollo::server::{PacketRecorder, World, WorldSocketMgr};
+/// use std::sync::Arc;
+///
+/// # fn test<W: World + 'static>(world: &'static W) {
+/// let recorder = Arc::new(PacketRecorder::create("traffic.rec").unwrap());
+/// let server = WorldSocketMgr::new(world).with_recorder(Arc::clone(&recorder));
+///
+/// // Later
+/// recorder.flush().unwrap();
+/// # }
+/// ```
+pub struct PacketRecorder {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl PacketRecorder {
+    /// Creates the recording file.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+
+    /// Records to a writer.
+    pub fn new(mut writer: impl Write + Send + 'static) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+
+        Ok(Self {
+            writer: Mutex::new(Box::new(writer)),
+        })
+    }
+
+    /// Flushes the records.
+    pub fn flush(&self) -> io::Result<()> {
+        self.writer.lock().flush()
+    }
+
+    fn record(
+        &self,
+        direction: Direction,
+        session_id: u64,
+        timestamp: i64,
+        cmd: u16,
+        payload: &[u8],
+    ) {
+        if payload.len() >= MAX_SIZE {
+            log_error!(
+                "Packet {} too big to be recorded ({} bytes)",
+                cmd,
+                payload.len()
+            );
+            return;
+        }
+
+        let mut header = [0; RECORD_HEADER_SIZE];
+        header[0] = match direction {
+            Direction::Inbound => 0,
+            Direction::Outbound => 1,
+        };
+        header[1..9].copy_from_slice(&session_id.to_be_bytes());
+        header[9..17].copy_from_slice(&timestamp.to_be_bytes());
+        header[17..19].copy_from_slice(&cmd.to_be_bytes());
+        header[19..23].copy_from_slice(&(payload.len() as u32).to_be_bytes());
+
+        let mut writer = self.writer.lock();
+        if let Err(error) = writer
+            .write_all(&header)
+            .and_then(|_| writer.write_all(payload))
+        {
+            log_error!("Error when recording a packet {:?}", error);
+        }
+    }
+}
+
+impl Drop for PacketRecorder {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl fmt::Debug for PacketRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PacketRecorder").finish()
+    }
+}
+
+/// The recorder of a session.
+#[derive(Debug, Clone)]
+pub(crate) struct SessionRecorder {
+    recorder: Arc<PacketRecorder>,
+    clock: Arc<dyn Clock>,
+    session_id: u64,
+}
+
+impl SessionRecorder {
+    pub(crate) fn new(
+        recorder: Arc<PacketRecorder>,
+        clock: Arc<dyn Clock>,
+        session_id: u64,
+    ) -> Self {
+        Self {
+            recorder,
+            clock,
+            session_id,
+        }
+    }
+
+    pub(crate) fn inbound(&self, packet: &Packet) {
+        let payload = packet.payload.as_deref().map_or(&[][..], |p| p.as_slice());
+        self.recorder.record(
+            Direction::Inbound,
+            self.session_id,
+            self.now(),
+            packet.cmd,
+            payload,
+        );
+    }
+
+    /// Records the packets of the bytes written to the session.
+    pub(crate) fn outbound(&self, bytes: &[u8]) {
+        let timestamp = self.now();
+
+        for (cmd, payload) in frames(bytes) {
+            self.recorder.record(
+                Direction::Outbound,
+                self.session_id,
+                timestamp,
+                cmd,
+                payload,
+            );
+        }
+    }
+
+    fn now(&self) -> i64 {
+        self.clock.now().as_millis() as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{game::ManualClock, packet::to_bytes};
+    use std::time::Duration;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let buffer = SharedBuffer::default();
+        let recorder = Arc::new(PacketRecorder::new(buffer.clone()).unwrap());
+        let clock = Arc::new(ManualClock::starting_at(Duration::from_secs(10)));
+        let session = SessionRecorder::new(recorder, clock.clone(), 7);
+
+        let mut payload = to_bytes(0, None);
+        payload.clear();
+        payload.extend_from_slice(&[1, 2]);
+        session.inbound(&Packet::new(5, Some(payload)));
+
+        clock.advance(Duration::from_millis(20));
+        let mut bytes = to_bytes(6, Some(&[3])).to_vec();
+        bytes.extend_from_slice(&to_bytes(8, None));
+        session.outbound(&bytes);
+
+        let packets = RecordedPacket::read_all(&buffer.0.lock()[..]).unwrap();
+        assert_eq!(
+            packets,
+            [
+                RecordedPacket {
+                    direction: Direction::Inbound,
+                    session_id: 7,
+                    timestamp: 10_000,
+                    cmd: 5,
+                    payload: vec![1, 2],
+                },
+                RecordedPacket {
+                    direction: Direction::Outbound,
+                    session_id: 7,
+                    timestamp: 10_020,
+                    cmd: 6,
+                    payload: vec![3],
+                },
+                RecordedPacket {
+                    direction: Direction::Outbound,
+                    session_id: 7,
+                    timestamp: 10_020,
+                    cmd: 8,
+                    payload: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_truncated() {
+        let buffer = SharedBuffer::default();
+        let recorder = PacketRecorder::new(buffer.clone()).unwrap();
+        recorder.record(Direction::Inbound, 1, 0, 5, &[1, 2, 3]);
+        recorder.record(Direction::Inbound, 1, 0, 6, &[1, 2, 3]);
+
+        let bytes = buffer.0.lock().clone();
+        let packets = RecordedPacket::read_all(&bytes[..bytes.len() - 1]).unwrap();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].cmd, 5);
+
+        assert!(RecordedPacket::read_all(&b"ROLLO"[..]).is_err());
+    }
+
+    #[test]
+    fn test_max_size() {
+        let buffer = SharedBuffer::default();
+        let recorder = PacketRecorder::new(buffer.clone()).unwrap();
+        recorder.record(Direction::Inbound, 1, 0, 5, &vec![0; MAX_SIZE]);
+        recorder.record(Direction::Inbound, 1, 0, 6, &[1, 2, 3]);
+
+        let mut bytes = buffer.0.lock().clone();
+        let packets = RecordedPacket::read_all(&bytes[..]).unwrap();
+        assert_eq!(packets.len(), 1);
+        assert_eq!(packets[0].cmd, 6);
+
+        // Corrupted size of the record.
+        let size = bytes.len() - 3 - 4;
+        bytes[size..size + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+        let error = RecordedPacket::read_all(&bytes[..]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
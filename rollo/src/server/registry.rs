@@ -0,0 +1,74 @@
+use super::world::World;
+use parking_lot::RwLock;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    net::IpAddr,
+    sync::Arc,
+};
+
+/// The open sessions and the banned addresses of a `WorldSocketMgr`.
+pub(crate) struct SessionRegistry<W>
+where
+    W: World,
+{
+    sessions: RwLock<BTreeMap<u64, Arc<W::WorldSessionimplementer>>>,
+    bans: RwLock<BTreeSet<IpAddr>>,
+}
+
+impl<W> SessionRegistry<W>
+where
+    W: World,
+{
+    pub(crate) fn new() -> Self {
+        Self {
+            sessions: RwLock::new(BTreeMap::new()),
+            bans: RwLock::new(BTreeSet::new()),
+        }
+    }
+
+    pub(crate) fn insert(&self, id: u64, world_session: Arc<W::WorldSessionimplementer>) {
+        self.sessions.write().insert(id, world_session);
+    }
+
+    pub(crate) fn remove(&self, id: u64) {
+        self.sessions.write().remove(&id);
+    }
+
+    pub(crate) fn get(&self, id: u64) -> Option<Arc<W::WorldSessionimplementer>> {
+        self.sessions.read().get(&id).cloned()
+    }
+
+    /// The sessions sorted by id.
+    pub(crate) fn sessions(&self) -> Vec<Arc<W::WorldSessionimplementer>> {
+        self.sessions.read().values().cloned().collect()
+    }
+
+    pub(crate) fn ban(&self, ip: IpAddr) {
+        self.bans.write().insert(ip);
+    }
+
+    pub(crate) fn unban(&self, ip: IpAddr) -> bool {
+        self.bans.write().remove(&ip)
+    }
+
+    pub(crate) fn is_banned(&self, ip: IpAddr) -> bool {
+        self.bans.read().contains(&ip)
+    }
+
+    pub(crate) fn bans(&self) -> Vec<IpAddr> {
+        self.bans.read().iter().copied().collect()
+    }
+}
+
+impl<W> fmt::Debug for SessionRegistry<W>
+where
+    W: World,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionRegistry")
+            .field("sessions", &self.sessions.read().len())
+            .field("bans", &self.bans.read().len())
+            .finish()
+    }
+}
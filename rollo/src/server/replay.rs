@@ -0,0 +1,121 @@
+use super::{
+    recorder::{Direction, RecordedPacket},
+    world::World,
+    world_socket_mgr::{WorldSocketMgr, LOOPBACK_BUFFER_SIZE},
+};
+use crate::packet::to_bytes;
+use std::{collections::HashMap, fs::File, io, path::Path, time::Duration};
+use tokio::{
+    io::{duplex, sink, split, AsyncWriteExt, DuplexStream, WriteHalf},
+    task::JoinHandle,
+    time::sleep,
+};
+
+/// Timing of a replay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayTiming {
+    /// Waits between the packets like in the recording.
+    Original,
+    /// Sends the packets as fast as possible.
+    Fast,
+}
+
+/// # Replay
+/// Feeds a recording (`PacketRecorder`) back into a `World` through in-memory sessions.
+///
+/// A session is opened for every recorded session, the inbound packets are sent in order
+/// (the order is kept per session) and the outbound packets are ignored.
+/// ## Usage
+/// ```rust, no_run
+/// use rollo::server::{Replay, ReplayTiming, World, WorldSocketMgr};
+///
+/// # async fn test<W: World + 'static>(world: &'static W) {
+/// let mut server = WorldSocketMgr::new(world);
+/// let replay = Replay::open("traffic.rec").unwrap();
+/// replay.run(&mut server, ReplayTiming::Fast).await;
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Replay {
+    packets: Vec<RecordedPacket>,
+}
+
+impl Replay {
+    /// Opens a recording file.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::from_reader(File::open(path)?)
+    }
+
+    /// Reads a recording.
+    pub fn from_reader(reader: impl io::Read) -> io::Result<Self> {
+        Ok(Self {
+            packets: RecordedPacket::read_all(reader)?,
+        })
+    }
+
+    /// Packets of the recording.
+    pub fn packets(&self) -> &[RecordedPacket] {
+        &self.packets
+    }
+
+    /// Sends the recording to the World, returns when the sessions are closed.
+    pub async fn run<W>(&self, server: &mut WorldSocketMgr<W>, timing: ReplayTiming)
+    where
+        W: Send + Sync + 'static + World,
+    {
+        let mut sessions: HashMap<u64, (WriteHalf<DuplexStream>, JoinHandle<()>)> = HashMap::new();
+        let mut last_timestamp: Option<i64> = None;
+
+        for packet in self
+            .packets
+            .iter()
+            .filter(|packet| packet.direction == Direction::Inbound)
+        {
+            if timing == ReplayTiming::Original {
+                if let Some(last_timestamp) = last_timestamp {
+                    let wait = (packet.timestamp - last_timestamp).max(0) as u64;
+                    sleep(Duration::from_millis(wait)).await;
+                }
+                last_timestamp = Some(packet.timestamp);
+            }
+
+            let (writer, _) = sessions
+                .entry(packet.session_id)
+                .or_insert_with(|| Self::open_session(server));
+
+            let payload = if packet.payload.is_empty() {
+                None
+            } else {
+                Some(packet.payload.as_slice())
+            };
+            if writer
+                .write_all(&to_bytes(packet.cmd, payload))
+                .await
+                .is_err()
+            {
+                log_info!("Session {} closed during the replay.", packet.session_id);
+            }
+        }
+
+        for (_, (mut writer, drain)) in sessions {
+            let _ = writer.shutdown().await;
+            drop(writer);
+            let _ = drain.await;
+        }
+    }
+
+    fn open_session<W>(server: &mut WorldSocketMgr<W>) -> (WriteHalf<DuplexStream>, JoinHandle<()>)
+    where
+        W: Send + Sync + 'static + World,
+    {
+        let (client, stream) = duplex(LOOPBACK_BUFFER_SIZE);
+        server.open_stream(stream, None);
+
+        let (mut reader, writer) = split(client);
+        let drain = tokio::spawn(async move {
+            let _ = tokio::io::copy(&mut reader, &mut sink()).await;
+        });
+
+        (writer, drain)
+    }
+}
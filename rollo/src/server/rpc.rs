@@ -0,0 +1,77 @@
+use crate::packet::Packet;
+use parking_lot::Mutex;
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU32, Ordering},
+};
+use tokio::sync::oneshot::{channel, Receiver, Sender};
+
+/// Requests (RPC) sent to the session and waiting for a reply.
+#[derive(Debug, Default)]
+pub(crate) struct PendingRequests {
+    counter: AtomicU32,
+    requests: Mutex<HashMap<u32, Sender<Packet>>>,
+}
+
+impl PendingRequests {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a new request id and the receiver of its reply.
+    pub(crate) fn register(&self) -> (u32, Receiver<Packet>) {
+        let request_id = self.counter.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+        let (tx, rx) = channel();
+        self.requests.lock().insert(request_id, tx);
+
+        (request_id, rx)
+    }
+
+    /// Forgets a request (timeout).
+    pub(crate) fn remove(&self, request_id: u32) {
+        self.requests.lock().remove(&request_id);
+    }
+
+    /// Delivers the reply, returns false if the request is unknown.
+    pub(crate) fn complete(&self, request_id: u32, packet: Packet) -> bool {
+        let sender = self.requests.lock().remove(&request_id);
+
+        if let Some(sender) = sender {
+            sender.send(packet).is_ok()
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register() {
+        let pending_requests = PendingRequests::new();
+        let (first, _rx) = pending_requests.register();
+        let (second, _rx_second) = pending_requests.register();
+
+        assert_eq!(first, 1);
+        assert_eq!(second, 2);
+        assert_eq!(pending_requests.requests.lock().len(), 2);
+
+        pending_requests.remove(first);
+        assert_eq!(pending_requests.requests.lock().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_complete() {
+        let pending_requests = PendingRequests::new();
+        let (request_id, rx) = pending_requests.register();
+
+        assert!(!pending_requests.complete(request_id + 1, Packet::new(5, None)));
+        assert!(pending_requests.complete(request_id, Packet::new(5, None)));
+        assert!(!pending_requests.complete(request_id, Packet::new(5, None)));
+
+        assert_eq!(rx.await.unwrap().cmd, 5);
+        assert_eq!(pending_requests.requests.lock().len(), 0);
+    }
+}
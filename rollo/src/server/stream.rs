@@ -0,0 +1,448 @@
+use crate::error::{Error, Result};
+use crate::io::read::MAX_SIZE;
+use crate::packet::Packet;
+use easy_pool::{PoolObjectContainer, PoolSegQueue};
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+/// Size of the fragments of the streams sent by the server.
+pub(crate) const STREAM_FRAGMENT_SIZE: usize = 8 * 1024;
+
+/// Amount of fragments buffered for a `ByteStream` before the reading of the socket waits.
+const STREAM_CAPACITY: usize = 16;
+
+/// Memory counted for each stream being received, in addition to its data.
+const STREAM_OVERHEAD: usize = 1024;
+
+/// Memory counted for a `ByteStream`, the fragments buffered before the reading of the socket waits.
+const BYTE_STREAM_MEMORY: usize = STREAM_CAPACITY * MAX_SIZE;
+
+/// Maximum capacity of the reassembled buffers kept by the pool, the bigger ones are freed.
+const MAX_POOLED_CAPACITY: usize = 64 * 1024;
+
+/// Maximum amount of buffers kept by the pool of the streams, read when the pool is first used.
+pub(crate) static STREAM_POOL_SIZE: AtomicUsize = AtomicUsize::new(64);
+
+static POOL_VEC_STREAM: Lazy<Arc<PoolSegQueue<Vec<u8>>>> =
+    Lazy::new(|| Arc::new(PoolSegQueue::new(STREAM_POOL_SIZE.load(Ordering::Acquire))));
+
+// Keeps no buffer, the big reassembled buffers are freed when dropped.
+static POOL_VEC_UNPOOLED: Lazy<Arc<PoolSegQueue<Vec<u8>>>> =
+    Lazy::new(|| Arc::new(PoolSegQueue::new(0)));
+
+#[derive(Debug)]
+enum StreamItem {
+    Chunk(PoolObjectContainer<Vec<u8>>),
+    End,
+}
+
+/// A stream received from the session, fragment by fragment.
+///
+/// ```rust, no_run
+/// use rollo::server::ByteStream;
+///
+/// async fn on_stream(mut stream: ByteStream) {
+///     while let Some(chunk) = stream.next().await {
+///         // Process the chunk.
+///     }
+///
+///     if !stream.is_complete() {
+///         // The session was closed before the end of the stream.
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ByteStream {
+    cmd: u16,
+    rx: Receiver<StreamItem>,
+    complete: bool,
+}
+
+impl ByteStream {
+    fn new(cmd: u16) -> (Sender<StreamItem>, Self) {
+        let (tx, rx) = channel(STREAM_CAPACITY);
+
+        (
+            tx,
+            Self {
+                cmd,
+                rx,
+                complete: false,
+            },
+        )
+    }
+
+    /// Returns the command of the stream.
+    pub fn cmd(&self) -> u16 {
+        self.cmd
+    }
+
+    /// Returns the next chunk, or None at the end of the stream.
+    pub async fn next(&mut self) -> Option<PoolObjectContainer<Vec<u8>>> {
+        if self.complete {
+            return None;
+        }
+
+        match self.rx.recv().await {
+            Some(StreamItem::Chunk(chunk)) => Some(chunk),
+            Some(StreamItem::End) => {
+                self.complete = true;
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Returns true if the last fragment was received.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Reads the whole stream.
+    pub async fn read_to_end(mut self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+
+        while let Some(chunk) = self.next().await {
+            data.extend_from_slice(&chunk);
+        }
+
+        if self.complete {
+            Ok(data)
+        } else {
+            Err(Error::Channel)
+        }
+    }
+}
+
+#[derive(Debug)]
+enum IncomingStream {
+    Packet(u16, usize, Vec<u8>),
+    Stream(u16, Sender<StreamItem>),
+}
+
+impl IncomingStream {
+    fn cmd(&self) -> u16 {
+        match self {
+            IncomingStream::Packet(cmd, ..) | IncomingStream::Stream(cmd, _) => *cmd,
+        }
+    }
+
+    // Memory counted for the stream, without its data.
+    fn reserved(&self) -> usize {
+        match self {
+            IncomingStream::Packet(..) => STREAM_OVERHEAD,
+            IncomingStream::Stream(..) => STREAM_OVERHEAD + BYTE_STREAM_MEMORY,
+        }
+    }
+}
+
+/// Reassembles the streams received from a session.
+///
+/// Each open stream is counted against `max_memory`, the session is closed (`Error::StreamSize`)
+/// if a limit is exceeded. A Packet bigger than its `max_size` closes the session
+/// (`Error::PacketSize`).
+#[derive(Debug)]
+pub(crate) struct StreamAssembler {
+    max_memory: usize,
+    max_streams: usize,
+    memory: usize,
+    streams: HashMap<u32, IncomingStream>,
+}
+
+impl StreamAssembler {
+    pub(crate) fn new(max_memory: usize, max_streams: usize) -> Self {
+        Self {
+            max_memory,
+            max_streams,
+            memory: 0,
+            streams: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn contains(&self, stream_id: u32) -> bool {
+        self.streams.contains_key(&stream_id)
+    }
+
+    /// Starts a stream delivered as a complete Packet, smaller than `max_size`.
+    pub(crate) fn open_packet(&mut self, stream_id: u32, cmd: u16, max_size: usize) -> Result<()> {
+        self.open(stream_id, IncomingStream::Packet(cmd, max_size, Vec::new()))
+    }
+
+    /// Starts a stream delivered as a `ByteStream`.
+    pub(crate) fn open_stream(&mut self, stream_id: u32, cmd: u16) -> Result<ByteStream> {
+        let (tx, stream) = ByteStream::new(cmd);
+        self.open(stream_id, IncomingStream::Stream(cmd, tx))?;
+
+        Ok(stream)
+    }
+
+    fn open(&mut self, stream_id: u32, stream: IncomingStream) -> Result<()> {
+        let memory = self.memory + stream.reserved();
+
+        if self.streams.len() >= self.max_streams || memory > self.max_memory {
+            return Err(Error::StreamSize);
+        }
+
+        self.memory = memory;
+        self.streams.insert(stream_id, stream);
+
+        Ok(())
+    }
+
+    /// Adds a fragment, returns the Packet once the last fragment of a stream is received.
+    ///
+    /// The fragment must have the command of the stream.
+    pub(crate) async fn push(
+        &mut self,
+        stream_id: u32,
+        last: bool,
+        packet: Packet,
+    ) -> Result<Option<Packet>> {
+        let stream = self
+            .streams
+            .get_mut(&stream_id)
+            .ok_or(Error::PacketPayload)?;
+
+        if stream.cmd() != packet.cmd {
+            return Err(Error::PacketPayload);
+        }
+
+        match stream {
+            IncomingStream::Packet(_, max_size, data) => {
+                if let Some(chunk) = packet.payload {
+                    if data.len() + chunk.len() >= *max_size {
+                        return Err(Error::PacketSize);
+                    }
+
+                    self.memory += chunk.len();
+
+                    if self.memory > self.max_memory {
+                        return Err(Error::StreamSize);
+                    }
+
+                    data.extend_from_slice(&chunk);
+                }
+            }
+            IncomingStream::Stream(_, tx) => {
+                if let Some(chunk) = packet.payload {
+                    // The receiver may have been dropped, the fragments are ignored.
+                    let _ = tx.send(StreamItem::Chunk(chunk)).await;
+                }
+            }
+        }
+
+        if !last {
+            return Ok(None);
+        }
+
+        let stream = match self.streams.remove(&stream_id) {
+            Some(stream) => stream,
+            None => return Ok(None),
+        };
+        self.memory -= stream.reserved();
+
+        match stream {
+            IncomingStream::Packet(cmd, _, data) => {
+                self.memory -= data.len();
+
+                if data.is_empty() {
+                    Ok(Some(Packet::new(cmd, None)))
+                } else {
+                    Ok(Some(Packet::new(cmd, Some(into_payload(data)))))
+                }
+            }
+            IncomingStream::Stream(_, tx) => {
+                let _ = tx.send(StreamItem::End).await;
+                Ok(None)
+            }
+        }
+    }
+}
+
+// The small buffers are copied to the pool, the big ones are freed once dropped.
+fn into_payload(data: Vec<u8>) -> PoolObjectContainer<Vec<u8>> {
+    if data.len() > MAX_POOLED_CAPACITY {
+        POOL_VEC_UNPOOLED.create_with(|| data)
+    } else {
+        let mut payload = POOL_VEC_STREAM.create();
+        payload.extend_from_slice(&data);
+        payload
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(cmd: u16, data: &[u8]) -> Packet {
+        let mut payload = POOL_VEC_STREAM.create();
+        payload.extend_from_slice(data);
+        Packet::new(cmd, Some(payload))
+    }
+
+    #[tokio::test]
+    async fn test_packet() {
+        let mut assembler = StreamAssembler::new(2 * STREAM_OVERHEAD + 100, 2);
+        assembler.open_packet(1, 3, usize::MAX).unwrap();
+        assembler.open_packet(2, 4, usize::MAX).unwrap();
+
+        assert!(assembler
+            .push(1, false, chunk(3, &[1, 2]))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(assembler
+            .push(2, false, chunk(4, &[5]))
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(assembler.memory, 2 * STREAM_OVERHEAD + 3);
+
+        let packet = assembler
+            .push(1, true, chunk(3, &[3]))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(packet.cmd, 3);
+        assert_eq!(*packet.payload.unwrap(), [1, 2, 3]);
+        assert!(!assembler.contains(1));
+        assert!(assembler.contains(2));
+        assert_eq!(assembler.memory, STREAM_OVERHEAD + 1);
+    }
+
+    #[tokio::test]
+    async fn test_max_memory() {
+        let mut assembler = StreamAssembler::new(STREAM_OVERHEAD + 4, 2);
+        assembler.open_packet(1, 3, usize::MAX).unwrap();
+
+        assert!(assembler.push(1, false, chunk(3, &[1, 2, 3])).await.is_ok());
+        assert_eq!(
+            assembler
+                .push(1, false, chunk(3, &[4, 5]))
+                .await
+                .unwrap_err(),
+            Error::StreamSize
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_size() {
+        let mut assembler = StreamAssembler::new(usize::MAX, 2);
+        assembler.open_packet(1, 3, 5).unwrap();
+
+        assert!(assembler.push(1, false, chunk(3, &[1, 2, 3])).await.is_ok());
+        assert_eq!(
+            assembler
+                .push(1, true, chunk(3, &[4, 5]))
+                .await
+                .unwrap_err(),
+            Error::PacketSize
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cmd() {
+        let mut assembler = StreamAssembler::new(usize::MAX, 2);
+        assembler.open_packet(1, 3, usize::MAX).unwrap();
+        let _stream = assembler.open_stream(2, 4).unwrap();
+
+        assert_eq!(
+            assembler.push(1, true, chunk(4, &[1])).await.unwrap_err(),
+            Error::PacketPayload
+        );
+        assert_eq!(
+            assembler.push(2, true, chunk(3, &[1])).await.unwrap_err(),
+            Error::PacketPayload
+        );
+    }
+
+    #[test]
+    fn test_max_streams() {
+        let mut assembler = StreamAssembler::new(usize::MAX, 2);
+        assembler.open_packet(1, 3, usize::MAX).unwrap();
+        assembler.open_stream(2, 3).unwrap();
+
+        assert_eq!(
+            assembler.open_packet(3, 3, usize::MAX).unwrap_err(),
+            Error::StreamSize
+        );
+        assert_eq!(assembler.open_stream(3, 3).unwrap_err(), Error::StreamSize);
+        assert!(!assembler.contains(3));
+    }
+
+    #[test]
+    fn test_stream_memory() {
+        let mut assembler = StreamAssembler::new(STREAM_OVERHEAD + BYTE_STREAM_MEMORY, 16);
+        assembler.open_stream(1, 3).unwrap();
+
+        assert_eq!(
+            assembler.open_packet(2, 3, usize::MAX).unwrap_err(),
+            Error::StreamSize
+        );
+        assert_eq!(assembler.memory, STREAM_OVERHEAD + BYTE_STREAM_MEMORY);
+    }
+
+    #[test]
+    fn test_into_payload() {
+        let payload = into_payload(vec![1; 10]);
+        assert_eq!(*payload, [1; 10]);
+        assert!(payload.capacity() <= MAX_POOLED_CAPACITY);
+
+        let payload = into_payload(vec![1; MAX_POOLED_CAPACITY + 1]);
+        assert_eq!(payload.len(), MAX_POOLED_CAPACITY + 1);
+        drop(payload);
+        assert_eq!(POOL_VEC_UNPOOLED.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_stream() {
+        let mut assembler = StreamAssembler::new(4, 2);
+        assert!(assembler.push(1, true, chunk(3, &[1])).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_byte_stream() {
+        let mut assembler = StreamAssembler::new(STREAM_OVERHEAD + BYTE_STREAM_MEMORY, 2);
+        let stream = assembler.open_stream(1, 8).unwrap();
+        assert_eq!(stream.cmd(), 8);
+
+        assert!(assembler
+            .push(1, false, chunk(8, &[1, 2, 3]))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(assembler
+            .push(1, true, chunk(8, &[4, 5]))
+            .await
+            .unwrap()
+            .is_none());
+        assert!(!assembler.contains(1));
+        assert_eq!(assembler.memory, 0);
+
+        assert_eq!(stream.read_to_end().await.unwrap(), [1, 2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn test_byte_stream_incomplete() {
+        let mut assembler = StreamAssembler::new(STREAM_OVERHEAD + BYTE_STREAM_MEMORY, 2);
+        let mut stream = assembler.open_stream(1, 8).unwrap();
+
+        assert!(assembler
+            .push(1, false, chunk(8, &[1]))
+            .await
+            .unwrap()
+            .is_none());
+        drop(assembler);
+
+        assert_eq!(*stream.next().await.unwrap(), [1]);
+        assert!(stream.next().await.is_none());
+        assert!(!stream.is_complete());
+    }
+}
@@ -1,7 +1,11 @@
-use super::{dos_protection::DosPolicy, world_session::WorldSession};
-use crate::game::GameTime;
+use super::{cluster::NodeId, dos_protection::DosPolicy, world_session::WorldSession};
+use crate::{
+    game::{AoiGrid, GameTime, Scheduler},
+    packet::Packet,
+};
 use async_trait::async_trait;
 use crossbeam::atomic::AtomicCell;
+use parking_lot::Mutex;
 
 /// A trait defining the behavior of a game world.
 ///
@@ -28,6 +32,21 @@ pub trait World: Sized + Sync + Send {
         None
     }
 
+    /// Returns a reference to the scheduler run by the game loop.
+    ///
+    /// The game loop executes the tasks of the scheduler at each tick, after `update`.
+    fn scheduler(&'static self) -> Option<&'static Scheduler> {
+        None
+    }
+
+    /// Returns a reference to the area of interest grid of the world.
+    ///
+    /// The game loop removes the observers of the closed sessions at each tick, before `update`,
+    /// their `AoiEvent::Leave` are drained in `update`.
+    fn aoi_grid(&'static self) -> Option<&'static Mutex<AoiGrid>> {
+        None
+    }
+
     /// Called when the game time is updated.
     ///
     /// This method is called when the game time is updated. It takes a `diff` value of type `i64` and
@@ -53,4 +72,13 @@ pub trait World: Sized + Sync + Send {
         // Default global packet limit: 50 packets maximum per second and 5000 bytes maximum per second.
         (50, 5000)
     }
-}
\ No newline at end of file
+
+    /// Called when a node of the cluster is connected and authenticated.
+    fn on_node_up(&'static self, _node: NodeId) {}
+
+    /// Called when the link with a node of the cluster is lost.
+    fn on_node_down(&'static self, _node: NodeId) {}
+
+    /// Called when a node of the cluster sends a message to the world (`Cluster::send_to_world`).
+    fn on_node_message(&'static self, _node: NodeId, _packet: Packet) {}
+}
@@ -1,6 +1,13 @@
-use super::world_socket::WriterMessage;
+use super::{
+    channel::Channel,
+    rpc::PendingRequests,
+    stream::{ByteStream, STREAM_FRAGMENT_SIZE},
+    world_socket::WriterMessage,
+};
 use crate::error::{Error, Result};
-use crate::packet::{to_bytes, Packet};
+use crate::packet::{
+    is_reserved, to_bytes, to_rpc_bytes, to_stream_bytes, Packet, RPC_REQUEST_CMD, RPC_RESPONSE_CMD,
+};
 use crate::server::world_socket::ContainerBytes;
 use async_trait::async_trait;
 use crossbeam::atomic::AtomicCell;
@@ -9,12 +16,18 @@ use std::{
     fmt::Debug,
     net::SocketAddr,
     sync::{
-        atomic::{AtomicI64, Ordering},
+        atomic::{AtomicI64, AtomicU32, Ordering},
         Arc,
     },
     time::Duration,
 };
-use tokio::sync::mpsc::UnboundedSender;
+use tokio::{
+    sync::mpsc::{error::SendError, UnboundedSender},
+    time::timeout,
+};
+
+/// Default delay to wait for the reply of a request (RPC).
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// A trait for defining events for a WorldSession.
 #[async_trait]
@@ -31,6 +44,34 @@ where
     /// Called when a message is received.
     async fn on_message(world_session: &Arc<Self>, world: &'static T, packet: Packet);
 
+    /// Called when a request (RPC) is received.
+    ///
+    /// The returned payload is sent back as the reply with the same command.
+    async fn on_request(
+        _world_session: &Arc<Self>,
+        _world: &'static T,
+        _packet: Packet,
+    ) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Returns true if the streams of a command are received with `on_stream`
+    /// instead of a complete Packet with `on_message`.
+    fn is_streamed(&self, _cmd: u16) -> bool {
+        false
+    }
+
+    /// Returns true if the streams of a command are reassembled and received as a complete Packet
+    /// with `on_message`, smaller than the size limit of the command (`World::get_packet_limit`).
+    ///
+    /// The session is closed if it sends a stream on a command neither streamed nor reassembled.
+    fn is_reassembled(&self, _cmd: u16) -> bool {
+        false
+    }
+
+    /// Called in a new task when a stream starts.
+    async fn on_stream(_world_session: Arc<Self>, _world: &'static T, _stream: ByteStream) {}
+
     /// Called when the connection is closed.
     async fn on_close(world_session: &Arc<Self>, world: &'static T);
 
@@ -41,9 +82,12 @@ where
 /// A struct for sending packets, measuring latency, and managing the SocketTools object.
 #[derive(Debug)]
 pub struct SocketTools {
-    /// The socket address.
+    /// The socket address, 127.0.0.1:0 for the sessions without network address (`peer_addr`).
     pub socket_addr: SocketAddr,
 
+    /// The address of the peer, None for a Unix socket, a loopback or a replay.
+    peer_addr: Option<SocketAddr>,
+
     /// The sender for the writer message.
     pub(crate) tx: UnboundedSender<WriterMessage>,
 
@@ -55,56 +99,173 @@ pub struct SocketTools {
 
     /// Indicates whether the connection is closed.
     closed: AtomicCell<bool>,
+
+    /// The requests waiting for a reply.
+    pub(crate) requests: Arc<PendingRequests>,
+
+    /// The counter of the streams sent to the session.
+    streams: Arc<AtomicU32>,
 }
 
 impl SocketTools {
     /// Creates a new SocketTools object.
     pub(crate) fn new(
-        socket_addr: SocketAddr,
+        peer_addr: Option<SocketAddr>,
         tx: UnboundedSender<WriterMessage>,
         id: u64,
     ) -> Self {
         Self {
-            socket_addr,
+            socket_addr: peer_addr.unwrap_or_else(|| SocketAddr::from(([127, 0, 0, 1], 0))),
+            peer_addr,
             tx,
             id,
             latency: AtomicI64::new(0),
             closed: AtomicCell::new(false),
+            requests: Arc::new(PendingRequests::new()),
+            streams: Arc::new(AtomicU32::new(0)),
         }
     }
 
     /// Sends a packet to the session.
+    ///
+    /// The packets on a reserved command (`packet::RESERVED_CMD`) are rejected.
     pub fn send(&self, cmd: u16, payload: Option<&[u8]>) {
-        if !self.is_closed() {
+        if !self.is_closed() && !Self::reject(cmd) {
             let bytes = to_bytes(cmd, payload);
-            if self
-                .tx
-                .send(WriterMessage::Send(bytes.into(), true))
-                .is_err()
-            {
-                log::error!("Can't send the data to the channel.");
+            if self.push(WriterMessage::Send(bytes.into(), true)).is_err() {
+                log_error!("Can't send the data to the channel.");
+            }
+        }
+    }
+
+    /// Sends a request (RPC) to the session and waits for the reply.
+    ///
+    /// Fails with `Error::RequestTimeout` after `DEFAULT_REQUEST_TIMEOUT`.
+    pub async fn request(&self, cmd: u16, payload: Option<&[u8]>) -> Result<Packet> {
+        self.request_with_timeout(cmd, payload, DEFAULT_REQUEST_TIMEOUT)
+            .await
+    }
+
+    /// Sends a request (RPC) to the session and waits for the reply with a custom timeout.
+    pub async fn request_with_timeout(
+        &self,
+        cmd: u16,
+        payload: Option<&[u8]>,
+        delay: Duration,
+    ) -> Result<Packet> {
+        if self.is_closed() {
+            return Err(Error::Channel);
+        }
+
+        if Self::reject(cmd) {
+            return Err(Error::ReservedCmd);
+        }
+
+        let (request_id, rx) = self.requests.register();
+        let bytes = to_rpc_bytes(RPC_REQUEST_CMD, request_id, cmd, payload);
+
+        if self.push(WriterMessage::Send(bytes.into(), true)).is_err() {
+            self.requests.remove(request_id);
+            return Err(Error::Channel);
+        }
+
+        match timeout(delay, rx).await {
+            Ok(result) => result.map_err(|_| Error::Channel),
+            Err(_) => {
+                self.requests.remove(request_id);
+                Err(Error::RequestTimeout)
+            }
+        }
+    }
+
+    /// Sends the reply of a request (RPC) to the session.
+    pub(crate) fn send_response(&self, request_id: u32, cmd: u16, payload: Option<&[u8]>) {
+        if !self.is_closed() {
+            let bytes = to_rpc_bytes(RPC_RESPONSE_CMD, request_id, cmd, payload);
+            if self.push(WriterMessage::Send(bytes.into(), true)).is_err() {
+                log_error!("Can't send the data to the channel.");
             }
         }
     }
 
     /// Sends bytes (Packet) to the session.
     pub fn send_data(&self, bytes: ContainerBytes) {
-        if !self.is_closed() && self.tx.send(WriterMessage::Send(bytes, true)).is_err() {
-            log::error!("Can't send the data to the channel.");
+        if !self.is_closed() && self.push(WriterMessage::Send(bytes, true)).is_err() {
+            log_error!("Can't send the data to the channel.");
+        }
+    }
+
+    /// Sends a packet to the session on a channel.
+    ///
+    /// ```rust, no_run
+    /// use rollo::server::{Channel, SocketTools};
+    ///
+    /// const CHAT: Channel = Channel::new(2, 1, 1);
+    ///
+    /// fn send_history(socket_tools: &SocketTools, history: &[u8]) {
+    ///     socket_tools.send_on(CHAT, 20, Some(history));
+    /// }
+    /// ```
+    pub fn send_on(&self, channel: Channel, cmd: u16, payload: Option<&[u8]>) {
+        if !self.is_closed() && !Self::reject(cmd) {
+            self.send_data_on(channel, to_bytes(cmd, payload).into());
+        }
+    }
+
+    /// Sends bytes (Packet) to the session on a channel.
+    pub fn send_data_on(&self, channel: Channel, bytes: ContainerBytes) {
+        let message = if channel.id() == Channel::DEFAULT.id() {
+            WriterMessage::Send(bytes, true)
+        } else {
+            WriterMessage::SendOn(channel, bytes)
+        };
+
+        if !self.is_closed() && self.push(message).is_err() {
+            log_error!("Can't send the data to the channel.");
+        }
+    }
+
+    /// Sends a payload of any size to the session on a channel, fragment by fragment.
+    ///
+    /// ```rust, no_run
+    /// use rollo::server::{Channel, SocketTools};
+    ///
+    /// const MAP: Channel = Channel::new(3, 0, 1);
+    ///
+    /// fn send_map(socket_tools: &SocketTools, map: &[u8]) {
+    ///     socket_tools.send_stream(MAP, 40, map);
+    /// }
+    /// ```
+    pub fn send_stream(&self, channel: Channel, cmd: u16, payload: &[u8]) {
+        if self.is_closed() || Self::reject(cmd) {
+            return;
+        }
+
+        let stream_id = self.streams.fetch_add(1, Ordering::Relaxed).wrapping_add(1);
+
+        if payload.is_empty() {
+            self.send_data_on(channel, to_stream_bytes(stream_id, cmd, true, &[]).into());
+            return;
+        }
+
+        let mut chunks = payload.chunks(STREAM_FRAGMENT_SIZE).peekable();
+        while let Some(chunk) = chunks.next() {
+            let last = chunks.peek().is_none();
+            self.send_data_on(channel, to_stream_bytes(stream_id, cmd, last, chunk).into());
         }
     }
 
     /// Writes bytes (Packet) to the session.
     pub fn write_data(&self, bytes: ContainerBytes) {
-        if !self.is_closed() && self.tx.send(WriterMessage::Send(bytes, false)).is_err() {
-            log::error!("Can't send the data to the channel.");
+        if !self.is_closed() && self.push(WriterMessage::Send(bytes, false)).is_err() {
+            log_error!("Can't send the data to the channel.");
         }
     }
 
     /// Flushes the session.
     pub fn flush(&self) {
-        if !self.is_closed() && self.tx.send(WriterMessage::Flush).is_err() {
-            log::error!("Can't send the data to the channel.");
+        if !self.is_closed() && self.push(WriterMessage::Flush).is_err() {
+            log_error!("Can't send the data to the channel.");
         }
     }
 
@@ -116,22 +277,43 @@ impl SocketTools {
     /// Closes the session.
     pub fn close(&self) -> Result<()> {
         self.closed.store(true);
-        self.tx
-            .send(WriterMessage::Close)
-            .map_err(|_| Error::Channel)
+        self.push(WriterMessage::Close).map_err(|_| Error::Channel)
     }
 
     /// Closes the session with a delay.
     pub fn close_with_delay(&self, delay: Duration) -> Result<()> {
-        self.tx
-            .send(WriterMessage::CloseDelayed(delay))
+        self.push(WriterMessage::CloseDelayed(delay))
             .map_err(|_| Error::Channel)
     }
 
+    /// Returns the address of the peer, None if the session has no network address
+    /// (Unix socket, loopback, replay).
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.peer_addr
+    }
+
     /// Returns true if the connection is closed.
     pub fn is_closed(&self) -> bool {
         self.tx.is_closed() || self.closed.load()
     }
+
+    // Returns true if the command is reserved by the protocol.
+    fn reject(cmd: u16) -> bool {
+        if is_reserved(cmd) {
+            log_error!("The command {} is reserved.", cmd);
+        }
+
+        is_reserved(cmd)
+    }
+
+    fn push(&self, message: WriterMessage) -> std::result::Result<(), SendError<WriterMessage>> {
+        self.tx.send(message)?;
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::METRICS.enqueued();
+
+        Ok(())
+    }
 }
 
 impl Clone for SocketTools {
@@ -141,11 +323,26 @@ impl Clone for SocketTools {
             tx: self.tx.clone(),
             id: self.id,
             socket_addr: self.socket_addr,
+            peer_addr: self.peer_addr,
             closed: AtomicCell::new(self.closed.load()),
+            requests: Arc::clone(&self.requests),
+            streams: Arc::clone(&self.streams),
         }
     }
 }
 
+#[cfg(test)]
+impl SocketTools {
+    /// SocketTools of a session without socket, the messages are received by the receiver.
+    pub(crate) fn for_test(id: u64) -> (Self, tokio::sync::mpsc::UnboundedReceiver<WriterMessage>) {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        (
+            Self::new(Some("127.0.0.1:6666".parse().unwrap()), tx, id),
+            rx,
+        )
+    }
+}
+
 impl PartialEq for SocketTools {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id
@@ -164,4 +361,4 @@ impl From<Arc<PoolObjectContainer<Vec<u8>>>> for ContainerBytes {
     fn from(b: Arc<PoolObjectContainer<Vec<u8>>>) -> Self {
         ContainerBytes::Arc(b)
     }
-}
\ No newline at end of file
+}
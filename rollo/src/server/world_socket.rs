@@ -1,11 +1,14 @@
-use super::dos_protection::{DosPolicy, DosProtection};
+use super::channel::{Channel, ChannelScheduler};
+use super::dos_protection::{DosLimits, DosPolicy, DosProtection};
+use super::recorder::SessionRecorder;
+use super::stream::StreamAssembler;
 use super::world::World;
 use super::world_session::WorldSession;
+use super::world_socket_mgr::{FlushPolicy, WorldSocketConfiguration};
 use crate::error::{Error, Result};
-use crate::game::GameTime;
+use crate::game::Clock;
 use crate::io::read::{Reader, MAX_SIZE};
-use crate::packet::Packet;
-use crossbeam::atomic::AtomicCell;
+use crate::packet::{is_reserved, Packet, RPC_REQUEST_CMD, RPC_RESPONSE_CMD, STREAM_CMD};
 use easy_pool::PoolObjectContainer;
 use std::convert::TryInto;
 use std::marker::PhantomData;
@@ -13,10 +16,13 @@ use std::sync::{atomic::Ordering, Arc};
 use std::time::Duration;
 use tokio::io::{AsyncWriteExt, BufWriter};
 use tokio::task::yield_now;
-use tokio::time::{sleep, timeout};
+use tokio::time::{sleep, sleep_until, timeout, Instant};
 use tokio::{
     io::{AsyncRead, AsyncWrite, BufReader, ReadHalf, WriteHalf},
-    sync::mpsc::UnboundedReceiver,
+    sync::{
+        mpsc::{error::TryRecvError, UnboundedReceiver},
+        watch::Receiver,
+    },
 };
 use tokio::{select, task};
 
@@ -29,6 +35,9 @@ where
     world_session: Arc<T>,
     world: &'static W,
     dos_protection: DosProtection,
+    limits: Arc<DosLimits>,
+    streams: StreamAssembler,
+    recorder: Option<SessionRecorder>,
     phantom: PhantomData<S>,
 }
 
@@ -38,27 +47,35 @@ where
     S: AsyncRead + AsyncWrite,
     W: 'static + Send + Sync + World,
 {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) async fn handle<'a>(
         &'a mut self,
         rx: UnboundedReceiver<WriterMessage>,
         mut reader: BufReader<ReadHalf<S>>,
         writer: WriteHalf<S>,
-        game_time: &'static AtomicCell<GameTime>,
-        timeout_read: u64,
+        clock: Arc<dyn Clock>,
+        configuration: WorldSocketConfiguration,
+        ticks: Receiver<u64>,
         world_session: &'a Arc<T>,
     ) where
         S: AsyncWrite + AsyncRead,
     {
+        let timeout_read = configuration.timeout;
+        let writer = SocketWriter::new(writer, rx, configuration, ticks)
+            .with_recorder(self.recorder.clone());
+
         select! {
-            _ = self.read(&mut reader, game_time, timeout_read, world_session, self.world) => {}
-            _ = Self::write(writer, rx) => {}
+            _ = self.read(&mut reader, &*clock, timeout_read, world_session, self.world) => {}
+            _ = writer.run() => {}
         }
     }
 
     fn handle_ping(&self, packet: Packet) -> Result<()> {
         if let Some(content) = packet.payload {
+            // The reply is flushed whatever the flush policy, a delay would be added to the latency.
             self.world_session.socket_tools().send(0, Some(&content));
-            let latency = parse_ping(&*content)?;
+            self.world_session.socket_tools().flush();
+            let latency = parse_ping(&content)?;
             self.world_session
                 .socket_tools()
                 .latency
@@ -69,19 +86,104 @@ where
         }
     }
 
-    pub(crate) fn new(world_session: Arc<T>, world: &'static W) -> Self {
+    fn handle_response(&self, packet: Packet) -> Result<()> {
+        let (request_id, packet) = packet.into_rpc()?;
+
+        if !self
+            .world_session
+            .socket_tools()
+            .requests
+            .complete(request_id, packet)
+        {
+            log_info!("Reply received for an unknown request {}.", request_id);
+        }
+
+        Ok(())
+    }
+
+    async fn handle_request(
+        &mut self,
+        packet: Packet,
+        world_session: &Arc<T>,
+        world: &'static W,
+        clock: &dyn Clock,
+    ) -> Result<()> {
+        let (request_id, packet) = packet.into_rpc()?;
+        self.check_inner_packet(&packet, clock).await?;
+        let cmd = packet.cmd;
+        let payload = T::on_request(world_session, world, packet).await;
+
+        world_session
+            .socket_tools()
+            .send_response(request_id, cmd, payload.as_deref());
+
+        Ok(())
+    }
+
+    async fn handle_stream(
+        &mut self,
+        packet: Packet,
+        world_session: &Arc<T>,
+        world: &'static W,
+        clock: &dyn Clock,
+    ) -> Result<()> {
+        let (stream_id, last, packet) = packet.into_stream()?;
+        let size_limit = self.check_inner_packet(&packet, clock).await?;
+
+        if !self.streams.contains(stream_id) {
+            if world_session.is_streamed(packet.cmd) {
+                let stream = self.streams.open_stream(stream_id, packet.cmd)?;
+                let world_session = Arc::clone(world_session);
+                task::spawn(instrument!(
+                    tracing::debug_span!("on_stream", cmd = packet.cmd),
+                    async move {
+                        T::on_stream(world_session, world, stream).await;
+                    }
+                ));
+            } else if world_session.is_reassembled(packet.cmd) {
+                self.streams
+                    .open_packet(stream_id, packet.cmd, size_limit as usize)?;
+            } else {
+                return Err(Error::PacketPayload);
+            }
+        }
+
+        if let Some(packet) = self.streams.push(stream_id, last, packet).await? {
+            T::on_message(world_session, world, packet).await;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn new(
+        world_session: Arc<T>,
+        world: &'static W,
+        configuration: WorldSocketConfiguration,
+        limits: Arc<DosLimits>,
+    ) -> Self {
         Self {
             world_session,
             dos_protection: DosProtection::new(),
+            limits,
+            streams: StreamAssembler::new(
+                configuration.max_stream_memory,
+                configuration.max_streams,
+            ),
+            recorder: None,
             phantom: PhantomData,
             world,
         }
     }
 
+    pub(crate) fn with_recorder(mut self, recorder: Option<SessionRecorder>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
     async fn process_packet<'a>(
         &'a mut self,
         reader: &'a mut Reader<'_, BufReader<ReadHalf<S>>>,
-        game_time: &'static AtomicCell<GameTime>,
+        clock: &'a dyn Clock,
         timeout_read: u64,
         world_session: &'a Arc<T>,
         world: &'static W,
@@ -89,15 +191,31 @@ where
         let result = {
             if let Ok(result) = timeout(
                 Duration::from_secs(timeout_read),
-                self.read_packet(reader, game_time),
+                self.read_packet(reader, clock),
             )
             .await
             {
+                if let (Ok(packet), Some(recorder)) = (&result, &self.recorder) {
+                    recorder.inbound(packet);
+                }
+
+                #[cfg(feature = "metrics")]
+                if let Ok(packet) = &result {
+                    let size = packet.payload.as_ref().map_or(0, |payload| payload.len());
+                    crate::metrics::METRICS.received(packet.cmd, size);
+                }
+
                 match result {
-                    Ok(packet) if packet.cmd == 0 => self.handle_ping(packet),
                     Ok(packet) => {
-                        T::on_message(world_session, world, packet).await;
-                        Ok(())
+                        instrument!(
+                            tracing::debug_span!(
+                                "packet",
+                                cmd = packet.cmd,
+                                size = packet.payload.as_ref().map_or(0, |payload| payload.len())
+                            ),
+                            self.dispatch(packet, world_session, world, clock)
+                        )
+                        .await
                     }
                     Err(error) => Err(error),
                 }
@@ -109,10 +227,40 @@ where
         result
     }
 
+    async fn dispatch(
+        &mut self,
+        packet: Packet,
+        world_session: &Arc<T>,
+        world: &'static W,
+        clock: &dyn Clock,
+    ) -> Result<()> {
+        match packet.cmd {
+            0 => self.handle_ping(packet),
+            RPC_RESPONSE_CMD => self.handle_response(packet),
+            RPC_REQUEST_CMD => {
+                self.handle_request(packet, world_session, world, clock)
+                    .await
+            }
+            STREAM_CMD => {
+                self.handle_stream(packet, world_session, world, clock)
+                    .await
+            }
+            cmd if is_reserved(cmd) => Err(Error::ReservedCmd),
+            _ => {
+                instrument!(
+                    tracing::debug_span!("on_message"),
+                    T::on_message(world_session, world, packet)
+                )
+                .await;
+                Ok(())
+            }
+        }
+    }
+
     async fn read<'a>(
         &'a mut self,
         buffer: &'a mut BufReader<ReadHalf<S>>,
-        game_time: &'static AtomicCell<GameTime>,
+        clock: &'a dyn Clock,
         timeout_read: u64,
         world_session: &'a Arc<T>,
         world: &'static W,
@@ -120,10 +268,14 @@ where
         let mut reader = Reader::new(buffer);
         loop {
             let result = self
-                .process_packet(&mut reader, game_time, timeout_read, world_session, world)
+                .process_packet(&mut reader, clock, timeout_read, world_session, world)
                 .await;
 
-            if result.is_err() {
+            if let Err(error) = result {
+                #[cfg(feature = "tracing")]
+                tracing::Span::current().record("error", tracing::field::debug(&error));
+
+                log_debug!("Session closed: {:?}.", error);
                 break;
             }
 
@@ -134,19 +286,20 @@ where
     async fn read_packet<'a>(
         &'a mut self,
         reader: &'a mut Reader<'_, BufReader<ReadHalf<S>>>,
-        game_time: &'static AtomicCell<GameTime>,
+        clock: &'a dyn Clock,
     ) -> Result<Packet> {
         let size = reader.read_size().await?;
         let cmd = reader.read_cmd().await?;
 
-        let (global_amount_limit, global_size_limit) = self.world.global_limit();
-        let (packet_amount_limit, packet_size_limit, policy) = self.world.get_packet_limit(cmd);
+        let (global_amount_limit, global_size_limit) = self.limits.global_limit(self.world);
+        let (packet_amount_limit, packet_size_limit, policy) =
+            self.limits.packet_limit(self.world, cmd);
 
         if size >= MAX_SIZE || (size as u32) >= packet_size_limit {
             return Err(Error::PacketSize);
         }
 
-        let time = game_time.load().timestamp;
+        let time = clock.now().as_millis() as i64;
 
         let global_result = self.dos_protection.evaluate_global_limit(
             time,
@@ -160,21 +313,13 @@ where
                 .dos_protection
                 .evaluate_cmd(cmd, packet_amount_limit, time)
         {
-            WorldSession::on_dos_attack(&self.world_session, self.world, cmd).await;
-
-            if !global_result {
-                return Err(self.close_dos());
-            }
-
-            match policy {
-                DosPolicy::Close => {
-                    return Err(self.close_dos());
-                }
-                DosPolicy::Log => {
-                    log::info!("Possible DOS attack detected for command {}.", cmd);
-                }
-                DosPolicy::None => {}
-            }
+            // The global limit always closes the session.
+            let policy = if global_result {
+                policy
+            } else {
+                DosPolicy::Close
+            };
+            self.handle_dos(cmd, policy).await?;
         }
 
         if size == 0 {
@@ -186,22 +331,144 @@ where
         }
     }
 
+    // Applies the limits of the command of a stream fragment or a request, returns its size limit.
+    async fn check_inner_packet(&mut self, packet: &Packet, clock: &dyn Clock) -> Result<u32> {
+        if packet.cmd == 0 || is_reserved(packet.cmd) {
+            return Err(Error::ReservedCmd);
+        }
+
+        let size = packet.payload.as_ref().map_or(0, |payload| payload.len());
+        let (amount_limit, size_limit, policy) = self.limits.packet_limit(self.world, packet.cmd);
+
+        if (size as u32) >= size_limit {
+            return Err(Error::PacketSize);
+        }
+
+        let time = clock.now().as_millis() as i64;
+
+        if !self
+            .dos_protection
+            .evaluate_cmd(packet.cmd, amount_limit, time)
+        {
+            self.handle_dos(packet.cmd, policy).await?;
+        }
+
+        Ok(size_limit)
+    }
+
+    // Called when a limit is exceeded, returns an error if the session must be closed.
+    async fn handle_dos(&self, cmd: u16, policy: DosPolicy) -> Result<()> {
+        WorldSession::on_dos_attack(&self.world_session, self.world, cmd).await;
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::METRICS.dos_triggered(&policy);
+
+        match policy {
+            DosPolicy::Close => Err(self.close_dos()),
+            DosPolicy::Log => {
+                log_info!("Possible DOS attack detected for command {}.", cmd);
+                Ok(())
+            }
+            DosPolicy::None => Ok(()),
+        }
+    }
+
     fn close_dos(&self) -> Error {
         if self.world_session.socket_tools().close().is_err() {
-            log::error!("Error when closing the channel.");
+            log_error!("Error when closing the channel.");
         }
         Error::DosProtection
     }
+}
+
+/// Writes the messages of a session according to the channels and the flush policy.
+struct SocketWriter<S> {
+    writer: BufWriter<WriteHalf<S>>,
+    rx: UnboundedReceiver<WriterMessage>,
+    scheduler: ChannelScheduler,
+    flush_policy: FlushPolicy,
+    ticks: Receiver<u64>,
+    ticking: bool,
+    pending: bool,
+    deadline: Option<Instant>,
+    recorder: Option<SessionRecorder>,
+}
 
-    async fn write(writer: WriteHalf<S>, mut rx: UnboundedReceiver<WriterMessage>) {
-        let mut writer = BufWriter::new(writer);
+impl<S> SocketWriter<S>
+where
+    S: AsyncWrite,
+{
+    fn new(
+        writer: WriteHalf<S>,
+        rx: UnboundedReceiver<WriterMessage>,
+        configuration: WorldSocketConfiguration,
+        ticks: Receiver<u64>,
+    ) -> Self {
+        Self {
+            writer: BufWriter::new(writer),
+            rx,
+            scheduler: ChannelScheduler::new(configuration.fragment_size),
+            flush_policy: configuration.flush_policy,
+            ticking: configuration.flush_policy == FlushPolicy::Tick,
+            ticks,
+            pending: false,
+            deadline: None,
+            recorder: None,
+        }
+    }
+
+    fn with_recorder(mut self, recorder: Option<SessionRecorder>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
+    async fn run(mut self) {
+        loop {
+            let message = if self.scheduler.is_empty() {
+                select! {
+                    message = self.rx.recv() => match message {
+                        Some(message) => message,
+                        None => break,
+                    },
+                    _ = sleep_until(self.deadline.unwrap_or_else(Instant::now)), if self.deadline.is_some() => {
+                        self.flush().await;
+                        continue;
+                    }
+                    result = self.ticks.changed(), if self.ticking => {
+                        if result.is_err() {
+                            self.ticking = false;
+                        } else if self.pending {
+                            self.flush().await;
+                        }
+                        continue;
+                    }
+                }
+            } else {
+                match self.rx.try_recv() {
+                    Ok(message) => message,
+                    Err(TryRecvError::Empty) => {
+                        if self.write_fragment().await.is_err() {
+                            return;
+                        }
+
+                        self.flush_if_due().await;
+                        yield_now().await;
+                        continue;
+                    }
+                    Err(TryRecvError::Disconnected) => break,
+                }
+            };
+
+            #[cfg(feature = "metrics")]
+            crate::metrics::METRICS.dequeued();
 
-        while let Some(message) = rx.recv().await {
             match message {
                 WriterMessage::Close => break,
                 WriterMessage::CloseDelayed(duration) => {
+                    self.write_channels().await;
+                    self.flush().await;
                     sleep(duration).await;
-                    break;
+                    return;
                 }
                 WriterMessage::Send(data, flush) => {
                     if data.is_empty() {
@@ -209,25 +476,107 @@ where
                         continue;
                     }
 
-                    if writer.write_all(data.bytes()).await.is_err() {
-                        break;
+                    self.on_write(data.bytes());
+
+                    if self.writer.write_all(data.bytes()).await.is_err() {
+                        return;
                     }
 
                     if flush {
-                        if let Err(error) = writer.flush().await {
-                            log::error!("Error when flushing {:?}", error);
-                        }
+                        self.request_flush().await;
                     }
                 }
+                WriterMessage::SendOn(channel, data) => {
+                    self.scheduler.push(channel, data);
+                }
                 WriterMessage::Flush => {
-                    if let Err(error) = writer.flush().await {
-                        log::error!("Error when flushing {:?}", error);
-                    }
+                    self.flush().await;
                 }
             }
 
             yield_now().await;
         }
+
+        self.write_channels().await;
+        self.flush().await;
+    }
+
+    // Records the bytes written to the session.
+    fn on_write(&self, bytes: &[u8]) {
+        if let Some(recorder) = &self.recorder {
+            recorder.outbound(bytes);
+        }
+
+        #[cfg(feature = "metrics")]
+        for (cmd, payload) in crate::packet::frames(bytes) {
+            crate::metrics::METRICS.sent(cmd, payload.len());
+        }
+    }
+
+    async fn write_fragment(&mut self) -> std::io::Result<()> {
+        if let Some(fragment) = self.scheduler.next_fragment() {
+            self.on_write(fragment.bytes());
+
+            self.writer.write_all(fragment.bytes()).await?;
+            self.request_flush().await;
+        }
+
+        Ok(())
+    }
+
+    async fn write_channels(&mut self) {
+        while !self.scheduler.is_empty() {
+            if self.write_fragment().await.is_err() {
+                break;
+            }
+        }
+    }
+
+    async fn request_flush(&mut self) {
+        match self.flush_policy {
+            FlushPolicy::Immediate => self.flush().await,
+            FlushPolicy::Delayed(delay) => {
+                self.pending = true;
+                self.deadline.get_or_insert_with(|| Instant::now() + delay);
+            }
+            FlushPolicy::Tick => self.pending = true,
+        }
+    }
+
+    /// Flushes without waiting if the delay expired or a tick ended.
+    async fn flush_if_due(&mut self) {
+        let expired = self
+            .deadline
+            .is_some_and(|deadline| deadline <= Instant::now());
+        let ticked = self.ticking && self.ticks.has_changed().unwrap_or(false);
+
+        if ticked {
+            self.ticks.borrow_and_update();
+        }
+
+        if self.pending && (expired || ticked) {
+            self.flush().await;
+        }
+    }
+
+    async fn flush(&mut self) {
+        if let Err(error) = self.writer.flush().await {
+            log_error!("Error when flushing {:?}", error);
+        }
+
+        self.pending = false;
+        self.deadline = None;
+    }
+}
+
+// The messages left in the queue are removed from the metrics.
+#[cfg(feature = "metrics")]
+impl<S> Drop for SocketWriter<S> {
+    fn drop(&mut self) {
+        self.rx.close();
+        while self.rx.try_recv().is_ok() {
+            crate::metrics::METRICS.dequeued();
+        }
     }
 }
 
@@ -252,8 +601,8 @@ pub enum ContainerBytes {
 impl ContainerBytes {
     pub fn bytes(&self) -> &[u8] {
         match self {
-            ContainerBytes::Raw(b) => &*b,
-            ContainerBytes::Arc(b) => &*b,
+            ContainerBytes::Raw(b) => b,
+            ContainerBytes::Arc(b) => b,
         }
     }
 
@@ -270,6 +619,7 @@ pub(crate) enum WriterMessage {
     Flush,
     CloseDelayed(Duration),
     Send(ContainerBytes, bool),
+    SendOn(Channel, ContainerBytes),
 }
 
 #[cfg(test)]
@@ -277,6 +627,103 @@ mod tests {
     use bytes::{BufMut, BytesMut};
 
     use super::*;
+    use crate::packet::to_bytes;
+    use tokio::{
+        io::{duplex, split, AsyncReadExt, DuplexStream},
+        sync::{mpsc::unbounded_channel, watch},
+    };
+
+    fn spawn_writer(
+        flush_policy: FlushPolicy,
+    ) -> (
+        DuplexStream,
+        tokio::sync::mpsc::UnboundedSender<WriterMessage>,
+        watch::Sender<u64>,
+    ) {
+        let (client, server) = duplex(64 * 1024);
+        let (_, writer) = split(server);
+        let (ticks_tx, ticks) = watch::channel(0);
+        let (tx, rx) = unbounded_channel();
+        let configuration = WorldSocketConfiguration::new().with_flush_policy(flush_policy);
+        tokio::spawn(SocketWriter::new(writer, rx, configuration, ticks).run());
+
+        (client, tx, ticks_tx)
+    }
+
+    fn send(tx: &tokio::sync::mpsc::UnboundedSender<WriterMessage>, cmd: u16) {
+        let message = WriterMessage::Send(to_bytes(cmd, Some(&[1])).into(), true);
+        assert!(tx.send(message).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_flush_immediate() {
+        let (mut client, tx, _ticks) = spawn_writer(FlushPolicy::Immediate);
+        send(&tx, 1);
+
+        let mut buffer = [0; 7];
+        timeout(Duration::from_millis(500), client.read_exact(&mut buffer))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(buffer[5], 1);
+    }
+
+    #[tokio::test]
+    async fn test_flush_delayed() {
+        let (mut client, tx, _ticks) =
+            spawn_writer(FlushPolicy::Delayed(Duration::from_millis(150)));
+        send(&tx, 1);
+        send(&tx, 2);
+
+        let mut buffer = [0; 14];
+        assert!(
+            timeout(Duration::from_millis(50), client.read_exact(&mut buffer))
+                .await
+                .is_err()
+        );
+
+        timeout(Duration::from_millis(500), client.read_exact(&mut buffer))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(buffer[5], 1);
+        assert_eq!(buffer[12], 2);
+    }
+
+    #[tokio::test]
+    async fn test_flush_tick() {
+        let (mut client, tx, ticks) = spawn_writer(FlushPolicy::Tick);
+        send(&tx, 1);
+
+        let mut buffer = [0; 7];
+        assert!(
+            timeout(Duration::from_millis(100), client.read_exact(&mut buffer))
+                .await
+                .is_err()
+        );
+
+        ticks.send_modify(|tick| *tick += 1);
+
+        timeout(Duration::from_millis(500), client.read_exact(&mut buffer))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(buffer[5], 1);
+    }
+
+    #[tokio::test]
+    async fn test_flush_on_close() {
+        let (mut client, tx, _ticks) = spawn_writer(FlushPolicy::Tick);
+        send(&tx, 1);
+        assert!(tx.send(WriterMessage::Close).is_ok());
+
+        let mut buffer = [0; 7];
+        timeout(Duration::from_millis(500), client.read_exact(&mut buffer))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(buffer[5], 1);
+    }
 
     #[test]
     fn test_parse_ping() {
@@ -286,6 +733,6 @@ mod tests {
         bytes.put_u16(100); // Ping Date
         bytes.put_i64(75); // Latency
 
-        assert_eq!(parse_ping(&bytes.to_vec()).unwrap(), 75);
+        assert_eq!(parse_ping(&bytes).unwrap(), 75);
     }
 }
@@ -1,35 +1,93 @@
 use super::{
-    tls::load_config,
+    admin::AdminConsole,
+    cluster::{self, Cluster, ClusterConfig},
+    dos_protection::DosLimits,
+    listener::{BoundListener, Connection, Listener},
+    loopback::LoopbackClient,
+    recorder::{PacketRecorder, SessionRecorder},
+    registry::SessionRegistry,
     world::World,
     world_session::{SocketTools, WorldSession},
     world_socket::WorldSocket,
 };
-use crate::game::game_loop::GameLoop;
+use crate::game::game_loop::{GameLoop, GameLoopControl};
 use crate::{
     error::{Error, Result},
-    game::GameTime,
+    game::{Clock, GameTime, SystemClock},
 };
 use crossbeam::atomic::AtomicCell;
-use std::{net::SocketAddr, path::Path, sync::Arc, time::Duration};
+use std::{
+    net::SocketAddr,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use tokio::{
-    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, ReadHalf, WriteHalf},
-    net::{TcpListener, TcpStream},
-    sync::mpsc::unbounded_channel,
+    io::{
+        duplex, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, ReadHalf, WriteHalf,
+    },
+    net::TcpStream,
+    sync::{
+        mpsc::unbounded_channel,
+        watch::{self, Receiver, Sender},
+    },
     task,
     time::timeout,
 };
 use tokio_rustls::{TlsAcceptor, TlsStream};
 
 /// World Socket Manager
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct WorldSocketMgr<W>
 where
     W: Send + Sync + 'static + World,
 {
     world: &'static W,
-    counter: u64,
-    configuration: WorldSocketConfiguration,
+    counter: Arc<AtomicU64>,
+    configuration: Arc<AtomicCell<WorldSocketConfiguration>>,
     game_time: &'static AtomicCell<GameTime>,
+    ticks: Arc<Sender<u64>>,
+    clock: Arc<dyn Clock>,
+    control: GameLoopControl,
+    recorder: Option<Arc<PacketRecorder>>,
+    limits: Arc<DosLimits>,
+    registry: Arc<SessionRegistry<W>>,
+}
+
+impl<W> Clone for WorldSocketMgr<W>
+where
+    W: Send + Sync + 'static + World,
+{
+    fn clone(&self) -> Self {
+        Self {
+            world: self.world,
+            counter: Arc::clone(&self.counter),
+            configuration: Arc::clone(&self.configuration),
+            game_time: self.game_time,
+            ticks: Arc::clone(&self.ticks),
+            clock: Arc::clone(&self.clock),
+            control: self.control.clone(),
+            recorder: self.recorder.clone(),
+            limits: Arc::clone(&self.limits),
+            registry: Arc::clone(&self.registry),
+        }
+    }
+}
+
+/// What the sessions of a WorldSocketMgr share.
+struct SessionContext<W>
+where
+    W: Send + Sync + 'static + World,
+{
+    world: &'static W,
+    configuration: WorldSocketConfiguration,
+    clock: Arc<dyn Clock>,
+    recorder: Option<Arc<PacketRecorder>>,
+    limits: Arc<DosLimits>,
+    registry: Arc<SessionRegistry<W>>,
 }
 
 impl<W> WorldSocketMgr<W>
@@ -38,136 +96,352 @@ where
 {
     /// Create WorldSocketMgr with default configuration.
     pub fn new(world: &'static W) -> Self {
-        Self {
-            world,
-            counter: 0,
-            configuration: WorldSocketConfiguration::default(),
-            game_time: world
-                .game_time()
-                .get_or_insert(Box::leak(Box::new(AtomicCell::new(GameTime::new())))),
-        }
+        Self::with_configuration(world, WorldSocketConfiguration::default())
     }
 
     /// Create WorldSocketMgr with custom configuration.
     pub fn with_configuration(world: &'static W, configuration: WorldSocketConfiguration) -> Self {
         Self {
             world,
-            counter: 0,
-            configuration,
+            counter: Arc::new(AtomicU64::new(0)),
+            configuration: Arc::new(AtomicCell::new(configuration)),
             game_time: world
                 .game_time()
                 .get_or_insert(Box::leak(Box::new(AtomicCell::new(GameTime::new())))),
+            ticks: Arc::new(watch::channel(0).0),
+            clock: Arc::new(SystemClock),
+            control: GameLoopControl::new(),
+            recorder: None,
+            limits: Arc::new(DosLimits::default()),
+            registry: Arc::new(SessionRegistry::new()),
         }
     }
 
+    /// Drives the GameLoop (and the DoS protection) with a clock (`ManualClock` in the tests).
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.game_time.store(GameTime::with_clock(&*clock));
+        self.clock = clock;
+        self
+    }
+
+    /// Records the packets received and sent by the sessions (`Replay` to feed them back).
+    pub fn with_recorder(mut self, recorder: Arc<PacketRecorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Replaces the configuration of the new sessions.
+    pub fn set_configuration(&self, configuration: WorldSocketConfiguration) {
+        self.configuration.store(configuration);
+    }
+
+    #[cfg(feature = "config")]
+    pub(crate) fn configuration_handle(&self) -> Arc<AtomicCell<WorldSocketConfiguration>> {
+        Arc::clone(&self.configuration)
+    }
+
+    /// Returns the limits of the DoS protection, they can be replaced at runtime.
+    pub fn dos_limits(&self) -> Arc<DosLimits> {
+        Arc::clone(&self.limits)
+    }
+
+    /// Returns the admin console of the server (sessions, bans, DoS limits, ticks).
+    pub fn admin_console(&self) -> AdminConsole<W> {
+        AdminConsole::new(
+            Arc::clone(&self.registry),
+            Arc::clone(&self.limits),
+            self.control.clone(),
+            self.game_time,
+        )
+    }
+
+    /// Connects this node to the other nodes of a cluster, the messages are routed to the sessions
+    /// and the world.
+    ///
+    /// Returns an error if the address of the node can't be bound.
+    pub async fn start_cluster(&self, config: ClusterConfig) -> Result<Cluster> {
+        cluster::start(self.world, Arc::clone(&self.registry), config).await
+    }
+
+    /// Returns the runtime control (pause, step, speed) of the GameLoop.
+    pub fn game_loop_control(&self) -> GameLoopControl {
+        self.control.clone()
+    }
+
     /// Start the GameLoop with an interval.
     pub fn start_game_loop(&mut self, interval: Duration) -> &mut Self {
         let world = self.world;
         let game_time = self.game_time;
+        let ticks = Arc::clone(&self.ticks);
+        let clock = Arc::clone(&self.clock);
+        let control = self.control.clone();
         tokio::spawn(async move {
-            let mut game_loop = GameLoop::new(interval);
+            let mut game_loop = GameLoop::new(interval)
+                .with_clock(clock)
+                .with_control(control)
+                .with_ticks(ticks);
             game_loop.start(world, Some(game_time)).await;
         });
 
         self
     }
 
+    /// Opens a session on a stream (`tokio::io::duplex`, ...), returns the id of the session.
+    ///
+    /// The stream is handled like a TCP connection accepted by `start_network`.
+    pub fn accept_stream<S>(&mut self, stream: S, addr: SocketAddr) -> u64
+    where
+        S: AsyncRead + AsyncWrite + Send + Sync + Unpin + 'static,
+    {
+        self.open_stream(stream, Some(addr))
+    }
+
+    /// Opens a session on a stream, without peer address for a loopback or a replay.
+    pub(crate) fn open_stream<S>(&mut self, stream: S, addr: Option<SocketAddr>) -> u64
+    where
+        S: AsyncRead + AsyncWrite + Send + Sync + Unpin + 'static,
+    {
+        let id = self.next_id();
+        let (reader, writer) = Self::split_socket(stream);
+
+        tokio::spawn(Self::create_socket(
+            self.context(),
+            addr,
+            false,
+            id,
+            reader,
+            writer,
+            self.ticks.subscribe(),
+        ));
+
+        id
+    }
+
+    /// Opens a session with an in-memory client, without socket.
+    ///```rust, no_run
+    /// # use rollo::server::{World, WorldSocketMgr};
+    /// # async fn test<W: World + 'static>(world: &'static W) {
+    /// let mut server = WorldSocketMgr::new(world);
+    /// let mut client = server.open_loopback();
+    ///
+    /// client.send(10, Some(&[1, 2, 3])).await.unwrap();
+    /// let packet = client.recv().await.unwrap();
+    /// # }
+    /// ```
+    pub fn open_loopback(&mut self) -> LoopbackClient {
+        let (client, server) = duplex(LOOPBACK_BUFFER_SIZE);
+        let id = self.open_stream(server, None);
+
+        LoopbackClient::new(id, client)
+    }
+
+    /// Opens sessions with in-memory clients.
+    pub fn open_loopbacks(&mut self, amount: usize) -> Vec<LoopbackClient> {
+        (0..amount).map(|_| self.open_loopback()).collect()
+    }
+
     /// Start TCP Server
-    pub async fn start_network<'a>(
+    pub async fn start_network(
         &mut self,
         addr: impl AsRef<str>,
         security: ListenerSecurity<'_>,
     ) -> Result<()> {
-        let address = addr.as_ref().parse::<String>().unwrap();
-        let listener = TcpListener::bind(&address).await.unwrap();
-
-        let mut tls_acceptor: Option<TlsAcceptor> = None;
+        self.start_listeners(&[Listener::tcp(addr.as_ref()).with_security(security)])
+            .await
+    }
 
-        if let ListenerSecurity::Tls(certificate, key) = security {
-            let config = load_config(certificate, key).unwrap();
-            tls_acceptor = Some(TlsAcceptor::from(Arc::new(config)));
+    /// Starts several listeners, their sessions share the ids and the world.
+    ///
+    /// Returns an error if a listener can't be bound.
+    pub async fn start_listeners(&mut self, listeners: &[Listener<'_>]) -> Result<()> {
+        let mut bound = Vec::with_capacity(listeners.len());
+        for listener in listeners {
+            bound.push((listener.bind()?, listener.configuration));
         }
 
-        let no_delay = self.configuration.no_delay;
-
         W::on_start(self.game_time).await;
 
-        self.listen(listener, tls_acceptor, no_delay).await
+        let mut tasks = Vec::with_capacity(bound.len());
+        for (listener, configuration) in bound {
+            let server = self.clone();
+            tasks.push(tokio::spawn(async move {
+                server.listen(listener, configuration).await
+            }));
+        }
+        for task in tasks {
+            let _ = task.await;
+        }
+
+        Ok(())
     }
 
     async fn listen(
-        &mut self,
-        listener: TcpListener,
-        tls_acceptor: Option<TlsAcceptor>,
-        no_delay: bool,
-    ) -> ! {
-        let timeout_read = self.configuration.timeout;
+        &self,
+        listener: BoundListener,
+        configuration: Option<WorldSocketConfiguration>,
+    ) {
         loop {
-            if let Ok((mut socket, addr)) = listener.accept().await {
-                self.counter += 1;
-                let id = self.counter;
-                let tls_acceptor = tls_acceptor.clone();
-
-                let world = self.world;
-                let game_time = self.game_time;
-                tokio::spawn(async move {
-                    if Self::set_up_socket(&mut socket, no_delay).is_ok() {
-                        if let Some(tls_acceptor) = tls_acceptor {
-                            if let Ok((reader, writer)) = Self::try_tls(socket, tls_acceptor).await
-                            {
-                                Self::create_socket(
-                                    addr,
-                                    world,
-                                    id,
-                                    reader,
-                                    writer,
-                                    game_time,
-                                    timeout_read,
-                                )
-                                .await;
-                            }
-                        } else {
-                            let (reader, writer) = Self::split_socket(socket);
-                            Self::create_socket(
+            if let Some((connection, addr)) = listener.socket.accept().await {
+                #[cfg(feature = "metrics")]
+                crate::metrics::METRICS.accepted();
+
+                if let Some(addr) = addr.filter(|addr| self.registry.is_banned(addr.ip())) {
+                    log_info!("Connection of a banned address {}.", addr.ip());
+                    continue;
+                }
+
+                let id = self.next_id();
+                let tls_acceptor = listener.tls_acceptor.clone();
+
+                let mut context = self.context();
+                if let Some(configuration) = configuration {
+                    context.configuration = configuration;
+                }
+                let ticks = self.ticks.subscribe();
+
+                match connection {
+                    Connection::Tcp(mut socket) => {
+                        if Self::set_up_socket(&mut socket, context.configuration.no_delay).is_ok()
+                        {
+                            tokio::spawn(Self::accept(
+                                context,
+                                socket,
                                 addr,
-                                world,
                                 id,
-                                reader,
-                                writer,
-                                game_time,
-                                timeout_read,
-                            )
-                            .await;
+                                tls_acceptor,
+                                ticks,
+                            ));
                         }
                     }
-                });
+                    #[cfg(unix)]
+                    Connection::Unix(socket) => {
+                        tokio::spawn(Self::accept(context, socket, addr, id, tls_acceptor, ticks));
+                    }
+                }
             }
 
             task::yield_now().await;
         }
     }
 
+    async fn accept<S>(
+        context: SessionContext<W>,
+        socket: S,
+        addr: Option<SocketAddr>,
+        id: u64,
+        tls_acceptor: Option<TlsAcceptor>,
+        ticks: Receiver<u64>,
+    ) where
+        S: AsyncRead + AsyncWrite + Send + Sync + Unpin + 'static,
+    {
+        if let Some(tls_acceptor) = tls_acceptor {
+            if let Ok((reader, writer)) = Self::try_tls(socket, tls_acceptor).await {
+                Self::create_socket(context, addr, true, id, reader, writer, ticks).await;
+            }
+        } else {
+            let (reader, writer) = Self::split_socket(socket);
+            Self::create_socket(context, addr, false, id, reader, writer, ticks).await;
+        }
+    }
+
+    fn next_id(&self) -> u64 {
+        self.counter.fetch_add(1, Ordering::AcqRel) + 1
+    }
+
+    fn context(&self) -> SessionContext<W> {
+        SessionContext {
+            world: self.world,
+            configuration: self.configuration.load(),
+            clock: Arc::clone(&self.clock),
+            recorder: self.recorder.clone(),
+            limits: Arc::clone(&self.limits),
+            registry: Arc::clone(&self.registry),
+        }
+    }
+
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
     async fn create_socket<S>(
-        socket_addr: SocketAddr,
-        world: &'static W,
+        context: SessionContext<W>,
+        peer_addr: Option<SocketAddr>,
+        tls: bool,
         id: u64,
         reader: BufReader<ReadHalf<S>>,
         writer: WriteHalf<S>,
-        game_time: &'static AtomicCell<GameTime>,
-        timeout_read: u64,
+        ticks: Receiver<u64>,
     ) where
         S: AsyncRead + AsyncWrite,
     {
+        #[cfg(feature = "metrics")]
+        crate::metrics::METRICS.opened();
+
         let (tx, rx) = unbounded_channel();
-        let socket_tools = SocketTools::new(socket_addr, tx, id);
+        let socket_tools = SocketTools::new(peer_addr, tx, id);
+        let socket_addr = socket_tools.socket_addr;
+
+        let SessionContext {
+            world,
+            configuration,
+            clock,
+            recorder,
+            limits,
+            registry,
+        } = context;
+
+        let session = async move {
+            let world_session = match instrument!(
+                tracing::debug_span!("on_open"),
+                W::WorldSessionimplementer::on_open(socket_tools, world)
+            )
+            .await
+            {
+                Ok(world_session) => world_session,
+                Err(error) => {
+                    log_info!("Session refused: {:?}.", error);
+                    return;
+                }
+            };
 
-        if let Ok(world_session) = W::WorldSessionimplementer::on_open(socket_tools, world).await {
-            let mut world_socket = WorldSocket::new(Arc::clone(&world_session), world);
+            let recorder =
+                recorder.map(|recorder| SessionRecorder::new(recorder, Arc::clone(&clock), id));
+            let mut world_socket =
+                WorldSocket::new(Arc::clone(&world_session), world, configuration, limits)
+                    .with_recorder(recorder);
+
+            registry.insert(id, Arc::clone(&world_session));
             world_socket
-                .handle(rx, reader, writer, game_time, timeout_read, &world_session)
+                .handle(
+                    rx,
+                    reader,
+                    writer,
+                    clock,
+                    configuration,
+                    ticks,
+                    &world_session,
+                )
                 .await;
-            W::WorldSessionimplementer::on_close(&world_session, world).await;
-        }
+            registry.remove(id);
+
+            instrument!(
+                tracing::debug_span!("on_close"),
+                W::WorldSessionimplementer::on_close(&world_session, world)
+            )
+            .await;
+        };
+
+        instrument!(
+            tracing::info_span!(
+                "session",
+                id,
+                addr = %socket_addr,
+                tls,
+                error = tracing::field::Empty
+            ),
+            session
+        )
+        .await;
+
+        #[cfg(feature = "metrics")]
+        crate::metrics::METRICS.closed();
     }
 
     fn set_up_socket(socket: &mut TcpStream, no_delay: bool) -> Result<()> {
@@ -187,13 +461,20 @@ where
     where
         S: AsyncRead + AsyncWrite + Unpin,
     {
-        let socket = timeout(
+        let result = timeout(
             Duration::from_secs(Self::TIMEOUT_TLS),
             tls_acceptor.accept(socket),
         )
         .await
-        .map_err(|_| Error::TlsAcceptTimeout)?
-        .map_err(|_| Error::TlsAccept)?;
+        .map_err(|_| Error::TlsAcceptTimeout)
+        .and_then(|result| result.map_err(|_| Error::TlsAccept));
+
+        #[cfg(feature = "metrics")]
+        if result.is_err() {
+            crate::metrics::METRICS.tls_failed();
+        }
+
+        let socket = result?;
 
         Ok(Self::split_socket(tokio_rustls::TlsStream::Server(socket)))
     }
@@ -209,18 +490,38 @@ where
     }
 }
 
+pub(crate) const LOOPBACK_BUFFER_SIZE: usize = 64 * 1024;
+
 /// Tcp or Tcp/Tls
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum ListenerSecurity<'a> {
     Tcp,
     Tls(&'a Path, &'a Path),
 }
 
+/// When the data sent to the sessions is flushed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlushPolicy {
+    /// Flush after each message.
+    #[default]
+    Immediate,
+    /// Coalesce the messages and flush at most after the delay.
+    Delayed(Duration),
+    /// Flush once at the end of every tick of the GameLoop.
+    ///
+    /// Without `start_game_loop`, the data is only flushed when the buffer is full or with `flush`.
+    Tick,
+}
+
 /// Socket Configuration
 #[derive(Debug, Clone, Copy)]
 pub struct WorldSocketConfiguration {
-    no_delay: bool,
-    timeout: u64,
+    pub(crate) no_delay: bool,
+    pub(crate) timeout: u64,
+    pub(crate) fragment_size: usize,
+    pub(crate) max_stream_memory: usize,
+    pub(crate) max_streams: usize,
+    pub(crate) flush_policy: FlushPolicy,
 }
 
 impl WorldSocketConfiguration {
@@ -229,15 +530,82 @@ impl WorldSocketConfiguration {
     /// let conf = WorldSocketConfiguration::with_custom_configuration(true, 20);
     /// ```
     pub const fn with_custom_configuration(no_delay: bool, timeout: u64) -> Self {
-        Self { no_delay, timeout }
+        Self {
+            no_delay,
+            timeout,
+            fragment_size: Self::FRAGMENT_SIZE,
+            max_stream_memory: Self::MAX_STREAM_MEMORY,
+            max_streams: Self::MAX_STREAMS,
+            flush_policy: FlushPolicy::Immediate,
+        }
     }
 
     pub const fn new() -> Self {
         Self {
             no_delay: true,
             timeout: 20,
+            fragment_size: Self::FRAGMENT_SIZE,
+            max_stream_memory: Self::MAX_STREAM_MEMORY,
+            max_streams: Self::MAX_STREAMS,
+            flush_policy: FlushPolicy::Immediate,
         }
     }
+
+    const FRAGMENT_SIZE: usize = 1024;
+
+    const MAX_STREAM_MEMORY: usize = 16 * 1024 * 1024;
+
+    const MAX_STREAMS: usize = 16;
+
+    /// Maximum size of the fragments of the messages sent on a `Channel`.
+    ///
+    /// Clamped to the biggest fragment a reader accepts (below the maximum packet size).
+    ///```rust, no_run
+    /// use rollo::server::WorldSocketConfiguration;
+    /// let conf = WorldSocketConfiguration::new().with_fragment_size(512);
+    /// ```
+    pub const fn with_fragment_size(mut self, fragment_size: usize) -> Self {
+        self.fragment_size = fragment_size;
+        self
+    }
+
+    /// Maximum memory used by the streams being received from a session, each open stream is
+    /// counted (a `ByteStream` counts the fragments it can buffer).
+    ///
+    /// The session is closed if the limit is exceeded.
+    ///```rust, no_run
+    /// use rollo::server::WorldSocketConfiguration;
+    /// let conf = WorldSocketConfiguration::new().with_max_stream_memory(4 * 1024 * 1024);
+    /// ```
+    pub const fn with_max_stream_memory(mut self, max_stream_memory: usize) -> Self {
+        self.max_stream_memory = max_stream_memory;
+        self
+    }
+
+    /// Maximum amount of streams received at the same time from a session.
+    ///
+    /// The session is closed if the limit is exceeded.
+    ///```rust, no_run
+    /// use rollo::server::WorldSocketConfiguration;
+    /// let conf = WorldSocketConfiguration::new().with_max_streams(4);
+    /// ```
+    pub const fn with_max_streams(mut self, max_streams: usize) -> Self {
+        self.max_streams = max_streams;
+        self
+    }
+
+    /// When the data sent to the sessions is flushed.
+    ///```rust, no_run
+    /// use rollo::server::{FlushPolicy, WorldSocketConfiguration};
+    /// use std::time::Duration;
+    ///
+    /// let conf = WorldSocketConfiguration::new()
+    ///     .with_flush_policy(FlushPolicy::Delayed(Duration::from_millis(5)));
+    /// ```
+    pub const fn with_flush_policy(mut self, flush_policy: FlushPolicy) -> Self {
+        self.flush_policy = flush_policy;
+        self
+    }
 }
 
 impl Default for WorldSocketConfiguration {
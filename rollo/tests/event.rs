@@ -1,5 +1,5 @@
 #![cfg(feature = "full")]
-use rollo::game::{Event, EventProcessor};
+use rollo::game::{Event, EventAction, EventProcessor};
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
@@ -19,20 +19,20 @@ fn test_event_processor() {
     event_processor.add_event(second_event.clone(), Duration::from_secs(15));
 
     // First Event
-    event_processor.update(9000);
+    event_processor.update(9000, &mut ());
     assert!(!first_event.executed.load(Ordering::SeqCst));
 
-    event_processor.update(10000);
+    event_processor.update(10000, &mut ());
     assert!(first_event.executed.load(Ordering::SeqCst));
 
-    event_processor.update(14000);
+    event_processor.update(14000, &mut ());
     assert!(!second_event.executed.load(Ordering::SeqCst));
 
-    event_processor.update(14999);
+    event_processor.update(14999, &mut ());
     assert!(!second_event.executed.load(Ordering::SeqCst));
     assert!(!event_processor.is_empty());
 
-    event_processor.update(15001);
+    event_processor.update(15001, &mut ());
     assert!(second_event.executed.load(Ordering::SeqCst));
     assert!(event_processor.is_empty());
 
@@ -47,7 +47,8 @@ struct MyEvent {
 }
 
 impl Event for MyEvent {
-    fn on_execute(&self, _diff: i64) {
+    fn on_execute(&mut self, _ctx: &mut (), _diff: i64) -> EventAction<Self> {
         self.executed.store(true, Ordering::SeqCst);
+        EventAction::Next
     }
 }
@@ -0,0 +1,166 @@
+#![cfg(feature = "full")]
+use async_trait::async_trait;
+use rollo::{
+    error::Error,
+    packet::{to_bytes, Packet},
+    server::{AdminListener, ListenerSecurity, SocketTools, World, WorldSession, WorldSocketMgr},
+};
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    time::{sleep, timeout},
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_admin() {
+    let world = Box::leak(Box::new(MyWorld {}));
+    let mut server = WorldSocketMgr::new(world);
+    let console = server.admin_console();
+
+    let mut clients = server.open_loopbacks(2);
+    for client in clients.iter_mut() {
+        client.send(5, None).await.unwrap();
+        assert_eq!(client.recv().await.unwrap().cmd, 5);
+    }
+
+    let sessions = console.execute("sessions");
+    assert!(sessions.starts_with("1 127.0.0.1:0 "), "{}", sessions);
+    assert!(sessions.contains("\n2 127.0.0.1:0 "), "{}", sessions);
+    assert!(sessions.ends_with("OK\n"));
+
+    // Kick
+    assert_eq!(console.execute("kick 1"), "OK\n");
+    assert!(timeout(Duration::from_secs(5), clients[0].recv())
+        .await
+        .unwrap()
+        .is_err());
+    assert_eq!(console.execute("kick 1"), "ERR unknown session: 1\n");
+
+    // DoS limits
+    assert_eq!(console.execute("dos"), "global world\nOK\n");
+    assert_eq!(console.execute("dos global 10 100"), "OK\n");
+    assert_eq!(console.execute("dos cmd 5 1 10 close"), "OK\n");
+    assert_eq!(
+        console.execute("dos"),
+        "global 10 100\ncmd 5 1 10 Close\nOK\n"
+    );
+    assert_eq!(
+        console.execute("dos cmd 5 1 10 kill"),
+        "ERR invalid policy: kill\n"
+    );
+    assert_eq!(console.execute("dos reset 5"), "OK\n");
+    assert_eq!(console.execute("dos"), "global 10 100\nOK\n");
+    assert_eq!(console.execute("dos reset"), "OK\n");
+
+    // Ban, the loopback sessions have no address.
+    assert_eq!(console.execute("ban 2"), "ERR session without address: 2\n");
+    assert_eq!(console.execute("bans"), "OK\n");
+    assert_eq!(console.execute("ban 127.0.0.1"), "banned 127.0.0.1\nOK\n");
+    clients[1].send(5, None).await.unwrap();
+    assert_eq!(clients[1].recv().await.unwrap().cmd, 5);
+    assert_eq!(console.execute("bans"), "127.0.0.1\nOK\n");
+    assert!(console.execute("unknown").starts_with("ERR"));
+
+    tokio::spawn(async move {
+        server
+            .start_game_loop(Duration::from_millis(10))
+            .start_network("127.0.0.1:6666", ListenerSecurity::Tcp)
+            .await
+            .unwrap();
+    });
+    tokio::spawn(console.clone().serve(AdminListener::Tcp("127.0.0.1:6667")));
+    sleep(Duration::from_millis(500)).await;
+
+    // The banned address is refused.
+    let mut socket = TcpStream::connect("127.0.0.1:6666").await.unwrap();
+    let _ = socket.write_all(&to_bytes(5, None)).await;
+    let mut buffer = [0; 6];
+    assert!(!matches!(socket.read(&mut buffer).await, Ok(6)));
+
+    assert!(console
+        .serve(AdminListener::Tcp("0.0.0.0:6668"))
+        .await
+        .is_err());
+
+    // TCP console
+    let socket = TcpStream::connect("127.0.0.1:6667").await.unwrap();
+    let (reader, mut writer) = socket.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    writer.write_all(b"unban 127.0.0.1\ntick\n").await.unwrap();
+    assert_eq!(lines.next_line().await.unwrap().unwrap(), "OK");
+    let mut tick = Vec::new();
+    loop {
+        let line = lines.next_line().await.unwrap().unwrap();
+        if line == "OK" {
+            break;
+        }
+        tick.push(line);
+    }
+    assert_eq!(tick.len(), 7);
+    assert!(tick.iter().any(|line| line == "paused false"));
+    assert!(!tick.iter().any(|line| line == "ticks 0"));
+
+    let mut socket = TcpStream::connect("127.0.0.1:6666").await.unwrap();
+    socket.write_all(&to_bytes(5, None)).await.unwrap();
+    socket.read_exact(&mut buffer).await.unwrap();
+
+    writer.write_all(b"sessions\nban 3\nquit\n").await.unwrap();
+    let mut sessions = Vec::new();
+    loop {
+        let line = lines.next_line().await.unwrap().unwrap();
+        if line == "OK" {
+            break;
+        }
+        sessions.push(line);
+    }
+    assert!(
+        sessions.iter().any(|line| line.starts_with("3 127.0.0.1:")),
+        "{:?}",
+        sessions
+    );
+    assert_eq!(
+        lines.next_line().await.unwrap().unwrap(),
+        "banned 127.0.0.1"
+    );
+    assert_eq!(lines.next_line().await.unwrap().unwrap(), "OK");
+    assert!(lines.next_line().await.unwrap().is_none());
+
+    // The TCP session of the banned address is closed.
+    assert!(matches!(socket.read(&mut buffer).await, Ok(0) | Err(_)));
+}
+
+struct MyWorldSession {
+    socket_tools: SocketTools,
+}
+
+#[async_trait]
+impl WorldSession<MyWorld> for MyWorldSession {
+    async fn on_open(
+        tools: SocketTools,
+        _world: &'static MyWorld,
+    ) -> Result<std::sync::Arc<Self>, Error> {
+        Ok(Arc::new(Self {
+            socket_tools: tools,
+        }))
+    }
+
+    fn socket_tools(&self) -> &SocketTools {
+        &self.socket_tools
+    }
+
+    async fn on_message(world_session: &Arc<Self>, _world: &'static MyWorld, packet: Packet) {
+        world_session
+            .socket_tools
+            .send(packet.cmd, packet.payload.as_deref().map(|p| p.as_slice()));
+    }
+
+    async fn on_close(_world_session: &Arc<Self>, _world: &'static MyWorld) {}
+}
+
+struct MyWorld {}
+
+impl World for MyWorld {
+    type WorldSessionimplementer = MyWorldSession;
+}
@@ -0,0 +1,126 @@
+#![cfg(feature = "full")]
+use async_trait::async_trait;
+use bytes::{BufMut, BytesMut};
+use rollo::{
+    error::Error,
+    packet::{Packet, FRAGMENT_CMD},
+    server::{
+        Channel, ListenerSecurity, SocketTools, World, WorldSession, WorldSocketConfiguration,
+        WorldSocketMgr,
+    },
+};
+use std::{convert::TryInto, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    task::JoinHandle,
+    time::sleep,
+};
+
+const MOVEMENT: Channel = Channel::new(1, 10, 1);
+const CHAT: Channel = Channel::new(2, 1, 1);
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_channel() {
+    setup(6666).await;
+    sleep(Duration::from_secs(1)).await;
+
+    let mut connect = TcpStream::connect("127.0.0.1:6666").await.unwrap();
+    connect.set_nodelay(true).unwrap();
+
+    connect.write_all(&packet()).await.unwrap();
+
+    let mut chat = Vec::new();
+    let mut movement_received = false;
+
+    loop {
+        let size = connect.read_u32().await.unwrap() as usize;
+        let cmd = connect.read_u16().await.unwrap();
+        let mut payload = vec![0; size];
+        connect.read_exact(&mut payload).await.unwrap();
+
+        if cmd == FRAGMENT_CMD {
+            assert!(size <= 258);
+            assert_eq!(payload[0], CHAT.id());
+            chat.extend_from_slice(&payload[2..]);
+
+            if payload[1] == 1 {
+                break;
+            }
+        } else {
+            assert_eq!(cmd, 2);
+            assert_eq!(payload, [7]);
+            movement_received = true;
+        }
+    }
+
+    // The movement is not blocked by the chat history.
+    assert!(movement_received);
+
+    let size = u32::from_be_bytes(chat[..4].try_into().unwrap()) as usize;
+    let cmd = u16::from_be_bytes(chat[4..6].try_into().unwrap());
+    assert_eq!(size, 4000);
+    assert_eq!(cmd, 3);
+    assert!(chat[6..].iter().all(|b| *b == 9));
+}
+
+fn packet() -> BytesMut {
+    let mut bytes = BytesMut::new();
+    bytes.put_u32(0);
+    bytes.put_u16(1);
+
+    bytes
+}
+
+async fn setup(port: u32) -> JoinHandle<()> {
+    let world = Box::new(MyWorld {});
+    let world = Box::leak(world);
+
+    let mut server = WorldSocketMgr::with_configuration(
+        world,
+        WorldSocketConfiguration::new().with_fragment_size(256),
+    );
+
+    tokio::spawn(async move {
+        server
+            .start_network(format!("127.0.0.1:{}", port), ListenerSecurity::Tcp)
+            .await
+            .unwrap();
+    })
+}
+struct MyWorldSession {
+    socket_tools: SocketTools,
+}
+
+#[async_trait]
+impl WorldSession<MyWorld> for MyWorldSession {
+    async fn on_open(
+        tools: SocketTools,
+        _world: &'static MyWorld,
+    ) -> Result<std::sync::Arc<Self>, Error> {
+        Ok(Arc::new(Self {
+            socket_tools: tools,
+        }))
+    }
+
+    fn socket_tools(&self) -> &SocketTools {
+        &self.socket_tools
+    }
+
+    async fn on_message(world_session: &Arc<Self>, _world: &'static MyWorld, packet: Packet) {
+        assert_eq!(packet.cmd, 1);
+
+        world_session
+            .socket_tools
+            .send_on(CHAT, 3, Some(&[9; 4000]));
+        world_session.socket_tools.send_on(MOVEMENT, 2, Some(&[7]));
+    }
+
+    async fn on_close(_world_session: &Arc<Self>, _world: &'static MyWorld) {}
+}
+
+struct MyWorld {}
+
+impl World for MyWorld {
+    type WorldSessionimplementer = MyWorldSession;
+}
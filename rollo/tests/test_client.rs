@@ -0,0 +1,204 @@
+#![cfg(feature = "full")]
+use async_trait::async_trait;
+use rollo::{
+    client::{ClientConfiguration, ClientEvent, ClientSecurity, RolloClient},
+    error::Error,
+    packet::Packet,
+    server::{Channel, ListenerSecurity, SocketTools, World, WorldSession, WorldSocketMgr},
+};
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    sync::mpsc::UnboundedReceiver,
+    time::{sleep, timeout},
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_client() {
+    let world = Box::leak(Box::new(MyWorld {}));
+    let mut server = WorldSocketMgr::new(world);
+    tokio::spawn(async move {
+        server
+            .start_game_loop(Duration::from_millis(20))
+            .start_network("127.0.0.1:6666", ListenerSecurity::Tcp)
+            .await
+            .unwrap();
+    });
+    sleep(Duration::from_millis(500)).await;
+
+    let configuration = ClientConfiguration::new()
+        .with_ping_interval(Duration::from_millis(50))
+        .with_backoff(Duration::from_millis(10), Duration::from_millis(100));
+    let (client, mut events) =
+        RolloClient::connect("127.0.0.1:6666", ClientSecurity::Tcp, configuration)
+            .await
+            .unwrap();
+    assert!(client.is_connected());
+
+    // Echo
+    client.send(5, Some(&[1, 2, 3])).unwrap();
+    match next(&mut events).await {
+        ClientEvent::Packet(packet) => {
+            assert_eq!(packet.cmd, 5);
+            assert_eq!(packet.payload.as_deref(), Some(&vec![1, 2, 3]));
+        }
+        event => panic!("unexpected event {:?}", event),
+    }
+
+    // The server closes the session, the client reconnects.
+    client.send(6, None).unwrap();
+    assert!(matches!(next(&mut events).await, ClientEvent::Disconnected));
+    assert!(matches!(next(&mut events).await, ClientEvent::Reconnected));
+    assert!(client.is_connected());
+
+    client.send(5, None).unwrap();
+    assert!(matches!(next(&mut events).await, ClientEvent::Packet(packet) if packet.cmd == 5));
+    assert!(client.latency() >= 0);
+
+    client.close();
+    sleep(Duration::from_millis(100)).await;
+    assert!(!client.is_connected());
+    assert_eq!(client.send(5, None), Err(Error::Channel));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_client_reassembly() {
+    let world = Box::leak(Box::new(MyWorld {}));
+    let mut server = WorldSocketMgr::new(world);
+    tokio::spawn(async move {
+        server
+            .start_game_loop(Duration::from_millis(20))
+            .start_network("127.0.0.1:6667", ListenerSecurity::Tcp)
+            .await
+            .unwrap();
+    });
+    sleep(Duration::from_millis(500)).await;
+
+    let (client, mut events) = RolloClient::connect(
+        "127.0.0.1:6667",
+        ClientSecurity::Tcp,
+        ClientConfiguration::new(),
+    )
+    .await
+    .unwrap();
+
+    // A message of a channel, split in fragments by the server.
+    client.send(7, Some(&[3])).unwrap();
+    match next(&mut events).await {
+        ClientEvent::Packet(packet) => {
+            assert_eq!(packet.cmd, 7);
+            assert_eq!(packet.payload.as_deref(), Some(&payload(CHANNEL_SIZE)));
+        }
+        event => panic!("unexpected event {:?}", event),
+    }
+
+    // A stream bigger than the maximum size of a packet.
+    client.send(8, None).unwrap();
+    match next(&mut events).await {
+        ClientEvent::Packet(packet) => {
+            assert_eq!(packet.cmd, 8);
+            assert_eq!(packet.payload.as_deref(), Some(&payload(STREAM_SIZE)));
+        }
+        event => panic!("unexpected event {:?}", event),
+    }
+
+    // A request of the server, its reply is sent back with the cmd 10.
+    client.send(9, None).unwrap();
+    match next(&mut events).await {
+        ClientEvent::Request(request_id, packet) => {
+            assert_eq!(packet.cmd, 9);
+            assert_eq!(packet.payload.as_deref(), Some(&vec![1, 2]));
+            client
+                .respond(request_id, packet.cmd, Some(&[3, 4]))
+                .unwrap();
+        }
+        event => panic!("unexpected event {:?}", event),
+    }
+    match next(&mut events).await {
+        ClientEvent::Packet(packet) => {
+            assert_eq!(packet.cmd, 10);
+            assert_eq!(packet.payload.as_deref(), Some(&vec![3, 4]));
+        }
+        event => panic!("unexpected event {:?}", event),
+    }
+
+    assert!(client.is_connected());
+    client.close();
+}
+
+const CHANNEL: Channel = Channel::new(2, 1, 1);
+const CHANNEL_SIZE: usize = 4000;
+const STREAM_SIZE: usize = 40000;
+
+fn payload(size: usize) -> Vec<u8> {
+    (0..size).map(|i| i as u8).collect()
+}
+
+// Next event, the latencies are checked and skipped.
+async fn next(events: &mut UnboundedReceiver<ClientEvent>) -> ClientEvent {
+    loop {
+        match timeout(Duration::from_secs(5), events.recv())
+            .await
+            .unwrap()
+            .unwrap()
+        {
+            ClientEvent::Latency(latency) => assert!(latency >= 0),
+            event => return event,
+        }
+    }
+}
+
+struct MyWorldSession {
+    socket_tools: SocketTools,
+}
+
+#[async_trait]
+impl WorldSession<MyWorld> for MyWorldSession {
+    async fn on_open(
+        tools: SocketTools,
+        _world: &'static MyWorld,
+    ) -> Result<std::sync::Arc<Self>, Error> {
+        Ok(Arc::new(Self {
+            socket_tools: tools,
+        }))
+    }
+
+    fn socket_tools(&self) -> &SocketTools {
+        &self.socket_tools
+    }
+
+    async fn on_message(world_session: &Arc<Self>, _world: &'static MyWorld, packet: Packet) {
+        match packet.cmd {
+            6 => world_session.socket_tools.close().unwrap(),
+            7 => world_session
+                .socket_tools
+                .send_on(CHANNEL, 7, Some(&payload(CHANNEL_SIZE))),
+            8 => world_session
+                .socket_tools
+                .send_stream(CHANNEL, 8, &payload(STREAM_SIZE)),
+            9 => {
+                let world_session = Arc::clone(world_session);
+                tokio::spawn(async move {
+                    let reply = world_session
+                        .socket_tools
+                        .request(9, Some(&[1, 2]))
+                        .await
+                        .unwrap();
+                    world_session
+                        .socket_tools
+                        .send(10, reply.payload.as_deref().map(|p| p.as_slice()));
+                });
+            }
+            _ => world_session
+                .socket_tools
+                .send(packet.cmd, packet.payload.as_deref().map(|p| p.as_slice())),
+        }
+    }
+
+    async fn on_close(_world_session: &Arc<Self>, _world: &'static MyWorld) {}
+}
+
+struct MyWorld {}
+
+impl World for MyWorld {
+    type WorldSessionimplementer = MyWorldSession;
+}
@@ -0,0 +1,145 @@
+#![cfg(feature = "full")]
+use async_trait::async_trait;
+use bytes::{BufMut, BytesMut};
+use rollo::{
+    error::Error,
+    game::ManualClock,
+    packet::Packet,
+    server::{DosPolicy, ListenerSecurity, SocketTools, World, WorldSession, WorldSocketMgr},
+};
+use std::{
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    io::AsyncWriteExt,
+    net::TcpStream,
+    sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    time::{sleep, timeout},
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_manual_clock() {
+    let (sender, mut rx) = unbounded_channel();
+    let world = Box::leak(Box::new(MyWorld {
+        sender,
+        elapsed: AtomicI64::new(0),
+    }));
+    let clock = Arc::new(ManualClock::new());
+
+    let mut server = WorldSocketMgr::new(world).with_clock(clock.clone());
+    tokio::spawn(async move {
+        server
+            .start_game_loop(Duration::from_millis(50))
+            .start_network("127.0.0.1:6666", ListenerSecurity::Tcp)
+            .await
+            .unwrap();
+    });
+    sleep(Duration::from_millis(500)).await;
+
+    // The time does not move without the clock.
+    assert_eq!(world.elapsed.load(Ordering::Acquire), 0);
+
+    let mut connect = TcpStream::connect("127.0.0.1:6666").await.unwrap();
+    connect.set_nodelay(true).unwrap();
+
+    for _ in 0..3 {
+        connect.write_all(&packet()).await.unwrap();
+    }
+    assert_eq!(next(&mut rx).await, Received::Message);
+    assert_eq!(next(&mut rx).await, Received::Message);
+    assert_eq!(next(&mut rx).await, Received::DosAttack);
+    // Logged only.
+    assert_eq!(next(&mut rx).await, Received::Message);
+
+    // New DoS window.
+    clock.advance(Duration::from_millis(1001));
+    timeout(Duration::from_secs(5), async {
+        while world.elapsed.load(Ordering::Acquire) < 1001 {
+            sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(world.elapsed.load(Ordering::Acquire), 1001);
+
+    for _ in 0..3 {
+        connect.write_all(&packet()).await.unwrap();
+    }
+    assert_eq!(next(&mut rx).await, Received::Message);
+    assert_eq!(next(&mut rx).await, Received::Message);
+    assert_eq!(next(&mut rx).await, Received::DosAttack);
+    // Logged only.
+    assert_eq!(next(&mut rx).await, Received::Message);
+}
+
+async fn next(rx: &mut UnboundedReceiver<Received>) -> Received {
+    timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+fn packet() -> BytesMut {
+    let mut bytes = BytesMut::new();
+    bytes.put_u32(0);
+    bytes.put_u16(5);
+
+    bytes
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Received {
+    Message,
+    DosAttack,
+}
+
+struct MyWorldSession {
+    socket_tools: SocketTools,
+}
+
+#[async_trait]
+impl WorldSession<MyWorld> for MyWorldSession {
+    async fn on_open(
+        tools: SocketTools,
+        _world: &'static MyWorld,
+    ) -> Result<std::sync::Arc<Self>, Error> {
+        Ok(Arc::new(Self {
+            socket_tools: tools,
+        }))
+    }
+
+    async fn on_dos_attack(_world_session: &Arc<Self>, world: &'static MyWorld, _cmd: u16) {
+        world.sender.send(Received::DosAttack).unwrap();
+    }
+
+    fn socket_tools(&self) -> &SocketTools {
+        &self.socket_tools
+    }
+
+    async fn on_message(_world_session: &Arc<Self>, world: &'static MyWorld, _packet: Packet) {
+        world.sender.send(Received::Message).unwrap();
+    }
+
+    async fn on_close(_world_session: &Arc<Self>, _world: &'static MyWorld) {}
+}
+
+struct MyWorld {
+    sender: UnboundedSender<Received>,
+    elapsed: AtomicI64,
+}
+
+impl World for MyWorld {
+    type WorldSessionimplementer = MyWorldSession;
+
+    fn update(&'static self, diff: i64, _game_time: rollo::game::GameTime) {
+        self.elapsed.fetch_add(diff, Ordering::AcqRel);
+    }
+
+    fn get_packet_limit(&self, _cmd: u16) -> (u16, u32, DosPolicy) {
+        (2, 500, DosPolicy::Log)
+    }
+}
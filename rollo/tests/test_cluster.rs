@@ -0,0 +1,221 @@
+#![cfg(feature = "full")]
+use async_trait::async_trait;
+use bytes::{BufMut, BytesMut};
+use rollo::{
+    error::Error,
+    packet::Packet,
+    server::{ClusterConfig, NodeId, SocketTools, World, WorldSession, WorldSocketMgr},
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::{sleep, timeout},
+};
+
+const LINK_HELLO: u16 = 1;
+const LINK_AUTH: u16 = 2;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_cluster() {
+    let world_1 = Box::leak(Box::new(MyWorld::default()));
+    let world_2 = Box::leak(Box::new(MyWorld::default()));
+    let server_1 = WorldSocketMgr::new(world_1);
+    let mut server_2 = WorldSocketMgr::new(world_2);
+
+    let cluster_2 = server_2
+        .start_cluster(
+            ClusterConfig::new(2, "secret")
+                .with_listen("127.0.0.1:6667")
+                .with_peer(0, "127.0.0.1:6665")
+                .with_peer(1, "127.0.0.1:6666")
+                .with_heartbeat(Duration::from_millis(100)),
+        )
+        .await
+        .unwrap();
+    let cluster_1 = server_1
+        .start_cluster(
+            ClusterConfig::new(1, "secret")
+                .with_listen("127.0.0.1:6666")
+                .with_peer(2, "127.0.0.1:6667")
+                .with_heartbeat(Duration::from_millis(100)),
+        )
+        .await
+        .unwrap();
+
+    // Wrong secret
+    let world_0 = Box::leak(Box::new(MyWorld::default()));
+    let cluster_0 = WorldSocketMgr::new(world_0)
+        .start_cluster(ClusterConfig::new(0, "wrong").with_peer(2, "127.0.0.1:6667"))
+        .await
+        .unwrap();
+
+    sleep(Duration::from_millis(500)).await;
+    assert_eq!(cluster_1.nodes(), [2]);
+    assert_eq!(cluster_2.nodes(), [1]);
+    assert!(cluster_0.nodes().is_empty());
+    assert_eq!(*world_1.events.lock().unwrap(), ["up 2"]);
+    assert_eq!(*world_2.events.lock().unwrap(), ["up 1"]);
+
+    // Session of the node 2
+    let mut client = server_2.open_loopback();
+    client.send(5, None).await.unwrap();
+    assert_eq!(client.recv().await.unwrap().cmd, 5);
+
+    cluster_1
+        .send_to_session(2, client.id(), 7, Some(&[1, 2, 3]))
+        .unwrap();
+    let packet = timeout(Duration::from_secs(5), client.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(packet.cmd, 7);
+    assert_eq!(*packet.payload.unwrap(), [1, 2, 3]);
+
+    // World of the node 1
+    cluster_2.send_to_world(1, 9, Some(&[4])).unwrap();
+    sleep(Duration::from_millis(100)).await;
+    assert_eq!(
+        *world_1.events.lock().unwrap(),
+        ["up 2", "message 2 9 Some([4])"]
+    );
+
+    assert_eq!(
+        cluster_2.send_to_world(3, 9, None),
+        Err(Error::NodeUnavailable)
+    );
+
+    // Node down
+    cluster_1.shutdown();
+    sleep(Duration::from_millis(500)).await;
+    assert!(cluster_2.nodes().is_empty());
+    assert_eq!(*world_2.events.lock().unwrap(), ["up 1", "down 1"]);
+    assert_eq!(
+        cluster_1.send_to_world(2, 9, None),
+        Err(Error::NodeUnavailable)
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_relay() {
+    let world_1 = Box::leak(Box::new(MyWorld::default()));
+    let world_2 = Box::leak(Box::new(MyWorld::default()));
+    let _cluster_1 = WorldSocketMgr::new(world_1)
+        .start_cluster(
+            ClusterConfig::new(1, "secret")
+                .with_listen("127.0.0.1:6668")
+                .with_peer(2, "127.0.0.1:6665"),
+        )
+        .await
+        .unwrap();
+    let cluster_2 = WorldSocketMgr::new(world_2)
+        .start_cluster(
+            ClusterConfig::new(2, "secret")
+                .with_listen("127.0.0.1:6669")
+                .with_peer(1, "127.0.0.1:6668"),
+        )
+        .await
+        .unwrap();
+    sleep(Duration::from_millis(200)).await;
+
+    // Connects to the node 2 as the node 1.
+    let mut to_2 = TcpStream::connect("127.0.0.1:6669").await.unwrap();
+    write_packet(&mut to_2, LINK_HELLO, &hello(1, [7; 32])).await;
+    let (cmd, hello_2) = read_packet(&mut to_2).await.unwrap();
+    assert_eq!(cmd, LINK_HELLO);
+
+    // Relays the challenge of the node 2 to the node 1, as the node 2.
+    let mut to_1 = TcpStream::connect("127.0.0.1:6668").await.unwrap();
+    write_packet(&mut to_1, LINK_HELLO, &hello_2).await;
+    let (cmd, _) = read_packet(&mut to_1).await.unwrap();
+    assert_eq!(cmd, LINK_HELLO);
+
+    // The node 1 signs nothing before the node 2 is authenticated.
+    assert!(timeout(Duration::from_millis(500), read_packet(&mut to_1))
+        .await
+        .is_err());
+
+    write_packet(&mut to_2, LINK_AUTH, &[0; 32]).await;
+    assert!(read_packet(&mut to_2).await.is_none());
+    assert!(cluster_2.nodes().is_empty());
+    assert!(world_2.events.lock().unwrap().is_empty());
+}
+
+fn hello(node: NodeId, nonce: [u8; 32]) -> Vec<u8> {
+    let mut hello = node.to_be_bytes().to_vec();
+    hello.extend_from_slice(&nonce);
+    hello
+}
+
+async fn write_packet(stream: &mut TcpStream, cmd: u16, payload: &[u8]) {
+    let mut bytes = BytesMut::new();
+    bytes.put_u32(payload.len() as u32);
+    bytes.put_u16(cmd);
+    bytes.put_slice(payload);
+    stream.write_all(&bytes).await.unwrap();
+}
+
+async fn read_packet(stream: &mut TcpStream) -> Option<(u16, Vec<u8>)> {
+    let size = stream.read_u32().await.ok()?;
+    let cmd = stream.read_u16().await.ok()?;
+    let mut payload = vec![0; size as usize];
+    stream.read_exact(&mut payload).await.ok()?;
+    Some((cmd, payload))
+}
+
+#[derive(Default)]
+struct MyWorld {
+    events: Mutex<Vec<String>>,
+}
+
+impl World for MyWorld {
+    type WorldSessionimplementer = MyWorldSession;
+
+    fn on_node_up(&'static self, node: NodeId) {
+        self.events.lock().unwrap().push(format!("up {}", node));
+    }
+
+    fn on_node_down(&'static self, node: NodeId) {
+        self.events.lock().unwrap().push(format!("down {}", node));
+    }
+
+    fn on_node_message(&'static self, node: NodeId, packet: Packet) {
+        self.events.lock().unwrap().push(format!(
+            "message {} {} {:?}",
+            node,
+            packet.cmd,
+            packet.payload.as_deref()
+        ));
+    }
+}
+
+struct MyWorldSession {
+    socket_tools: SocketTools,
+}
+
+#[async_trait]
+impl WorldSession<MyWorld> for MyWorldSession {
+    async fn on_open(
+        tools: SocketTools,
+        _world: &'static MyWorld,
+    ) -> Result<std::sync::Arc<Self>, Error> {
+        Ok(Arc::new(Self {
+            socket_tools: tools,
+        }))
+    }
+
+    fn socket_tools(&self) -> &SocketTools {
+        &self.socket_tools
+    }
+
+    async fn on_message(world_session: &Arc<Self>, _world: &'static MyWorld, packet: Packet) {
+        world_session
+            .socket_tools
+            .send(packet.cmd, packet.payload.as_deref().map(|p| p.as_slice()));
+    }
+
+    async fn on_close(_world_session: &Arc<Self>, _world: &'static MyWorld) {}
+}
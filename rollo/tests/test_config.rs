@@ -0,0 +1,87 @@
+#![cfg(feature = "full")]
+use async_trait::async_trait;
+use rollo::{
+    config::ServerConfig,
+    error::Error,
+    packet::Packet,
+    server::{DosPolicy, SocketTools, World, WorldSession, WorldSocketMgr},
+};
+use std::{fs, sync::Arc, time::Duration};
+use tokio::time::sleep;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_watch_config() {
+    let path = std::env::temp_dir().join(format!("rollo-test-config-{}.toml", std::process::id()));
+    fs::write(&path, "[dos]\nglobal = { amount = 10, size = 100 }\n").unwrap();
+
+    let config = ServerConfig::load(&path).unwrap();
+    let world = Box::leak(Box::new(MyWorld {}));
+    let mut server = WorldSocketMgr::from_config(world, &config);
+    let limits = server.dos_limits();
+    assert_eq!(limits.global(), Some((10, 100)));
+
+    let watcher = server.watch_config(&path, Duration::from_millis(20));
+    server.start_game_loop(config.game_loop_interval());
+    let control = server.game_loop_control();
+
+    fs::write(
+        &path,
+        r#"
+        [network]
+        bind = "127.0.0.1:7000"
+
+        [dos]
+        packets = [{ cmd = 5, amount = 1, size = 10, policy = "log" }]
+
+        [game_loop]
+        interval = 5
+        "#,
+    )
+    .unwrap();
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(limits.global(), None);
+    assert_eq!(limits.packets(), [(5, (1, 10, DosPolicy::Log))]);
+
+    // The ticks are faster.
+    let ticks = control.ticks();
+    sleep(Duration::from_millis(500)).await;
+    assert!(control.ticks() - ticks > 30, "{}", control.ticks() - ticks);
+
+    // An invalid file is ignored.
+    fs::write(&path, "[dos]\nglobal = 10\n").unwrap();
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(limits.packets(), [(5, (1, 10, DosPolicy::Log))]);
+
+    watcher.abort();
+    fs::remove_file(&path).unwrap();
+}
+
+struct MyWorldSession {
+    socket_tools: SocketTools,
+}
+
+#[async_trait]
+impl WorldSession<MyWorld> for MyWorldSession {
+    async fn on_open(
+        tools: SocketTools,
+        _world: &'static MyWorld,
+    ) -> Result<std::sync::Arc<Self>, Error> {
+        Ok(Arc::new(Self {
+            socket_tools: tools,
+        }))
+    }
+
+    fn socket_tools(&self) -> &SocketTools {
+        &self.socket_tools
+    }
+
+    async fn on_message(_world_session: &Arc<Self>, _world: &'static MyWorld, _packet: Packet) {}
+
+    async fn on_close(_world_session: &Arc<Self>, _world: &'static MyWorld) {}
+}
+
+struct MyWorld {}
+
+impl World for MyWorld {
+    type WorldSessionimplementer = MyWorldSession;
+}
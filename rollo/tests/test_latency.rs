@@ -4,14 +4,17 @@ use bytes::{BufMut, BytesMut};
 use rollo::{
     error::Error,
     packet::Packet,
-    server::{ListenerSecurity, SocketTools, World, WorldSession, WorldSocketMgr},
+    server::{
+        FlushPolicy, ListenerSecurity, SocketTools, World, WorldSession, WorldSocketConfiguration,
+        WorldSocketMgr,
+    },
 };
 use std::{sync::Arc, time::Duration};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     net::TcpStream,
     task::JoinHandle,
-    time::sleep,
+    time::{sleep, timeout},
 };
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
@@ -39,6 +42,26 @@ async fn test_latency() {
     connect.read_u32().await.unwrap();
 }
 
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_latency_flush_policy() {
+    let world = Box::leak(Box::new(MyWorld {}));
+    let mut server = WorldSocketMgr::with_configuration(
+        world,
+        WorldSocketConfiguration::new().with_flush_policy(FlushPolicy::Tick),
+    );
+    let mut client = server.open_loopback();
+
+    // Without GameLoop, the ticks never flush, the reply of the ping is flushed right away.
+    client.send_bytes(&latency_packet()).await.unwrap();
+    let packet = timeout(Duration::from_millis(500), client.recv())
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(packet.cmd, 0);
+    assert_eq!(packet.payload.unwrap().len(), 16);
+}
+
 fn latency_packet() -> BytesMut {
     let mut bytes = BytesMut::new();
     bytes.put_u32(16);
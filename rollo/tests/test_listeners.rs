@@ -0,0 +1,107 @@
+#![cfg(all(feature = "full", unix))]
+use async_trait::async_trait;
+use rollo::{
+    error::Error,
+    packet::{to_bytes, Packet},
+    server::{Listener, SocketTools, World, WorldSession, WorldSocketMgr},
+};
+use std::{collections::HashSet, convert::TryInto, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpStream, UnixStream},
+    time::sleep,
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_listeners() {
+    let path = std::env::temp_dir().join(format!("rollo-test-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let world = Box::leak(Box::new(MyWorld {}));
+    let mut server = WorldSocketMgr::new(world);
+    let mut other = server.clone();
+    let unix_path = path.clone();
+    tokio::spawn(async move {
+        server
+            .start_listeners(&[
+                Listener::tcp("127.0.0.1:6666").with_reuse_port(true),
+                Listener::tcp("127.0.0.1:6666").with_reuse_port(true),
+                Listener::tcp("127.0.0.1:6667"),
+                Listener::unix(&unix_path),
+            ])
+            .await
+            .unwrap();
+    });
+    sleep(Duration::from_millis(500)).await;
+
+    // The address is used without SO_REUSEPORT.
+    assert_eq!(
+        other
+            .start_listeners(&[Listener::tcp("127.0.0.1:6667")])
+            .await,
+        Err(Error::Bind)
+    );
+
+    let mut ids = HashSet::new();
+    for _ in 0..4 {
+        ids.insert(echo(TcpStream::connect("127.0.0.1:6666").await.unwrap()).await);
+    }
+    ids.insert(echo(TcpStream::connect("127.0.0.1:6667").await.unwrap()).await);
+    ids.insert(echo(UnixStream::connect(&path).await.unwrap()).await);
+    assert_eq!(
+        ids,
+        (1..=5)
+            .map(|id| (id, true))
+            .chain(Some((6, false)))
+            .collect()
+    );
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+// Returns the id of the session and whether it has a peer address.
+async fn echo<S: AsyncRead + AsyncWrite + Unpin>(mut socket: S) -> (u64, bool) {
+    socket.write_all(&to_bytes(5, None)).await.unwrap();
+    let mut buffer = [0; 15];
+    socket.read_exact(&mut buffer).await.unwrap();
+    assert_eq!(buffer[..6], [0, 0, 0, 9, 0, 5]);
+    (
+        u64::from_be_bytes(buffer[6..14].try_into().unwrap()),
+        buffer[14] == 1,
+    )
+}
+
+struct MyWorldSession {
+    socket_tools: SocketTools,
+}
+
+#[async_trait]
+impl WorldSession<MyWorld> for MyWorldSession {
+    async fn on_open(
+        tools: SocketTools,
+        _world: &'static MyWorld,
+    ) -> Result<std::sync::Arc<Self>, Error> {
+        Ok(Arc::new(Self {
+            socket_tools: tools,
+        }))
+    }
+
+    fn socket_tools(&self) -> &SocketTools {
+        &self.socket_tools
+    }
+
+    async fn on_message(world_session: &Arc<Self>, _world: &'static MyWorld, packet: Packet) {
+        let tools = &world_session.socket_tools;
+        let mut payload = tools.id.to_be_bytes().to_vec();
+        payload.push(tools.peer_addr().is_some() as u8);
+        tools.send(packet.cmd, Some(&payload));
+    }
+
+    async fn on_close(_world_session: &Arc<Self>, _world: &'static MyWorld) {}
+}
+
+struct MyWorld {}
+
+impl World for MyWorld {
+    type WorldSessionimplementer = MyWorldSession;
+}
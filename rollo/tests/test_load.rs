@@ -0,0 +1,88 @@
+#![cfg(feature = "full")]
+use async_trait::async_trait;
+use rollo::{
+    client::{LoadTest, Scenario},
+    error::Error,
+    packet::Packet,
+    server::{DosPolicy, ListenerSecurity, SocketTools, World, WorldSession, WorldSocketMgr},
+};
+use std::{sync::Arc, time::Duration};
+use tokio::time::sleep;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_load() {
+    let world = Box::leak(Box::new(MyWorld {}));
+    let mut server = WorldSocketMgr::new(world);
+    tokio::spawn(async move {
+        server
+            .start_network("127.0.0.1:6666", ListenerSecurity::Tcp)
+            .await
+            .unwrap();
+    });
+    sleep(Duration::from_millis(500)).await;
+
+    let scenario = Scenario::new(6, vec![1, 2, 3])
+        .with_login(1, b"bot".to_vec(), Some(2))
+        .with_rate(20)
+        .with_duration(Duration::from_millis(600))
+        .with_reply(6, |packet| {
+            packet.payload.as_deref() == Some(&vec![1, 2, 3])
+        });
+
+    let report = LoadTest::new("127.0.0.1:6666", 50, scenario)
+        .with_ramp_up(Duration::from_millis(100))
+        .run()
+        .await;
+
+    assert_eq!(report.connected, 50);
+    assert_eq!(report.connect_success(), 1.0);
+    assert_eq!(report.login_failures, 0);
+    assert_eq!(report.disconnections, 0);
+    assert!(report.sent >= 50 * 10);
+    assert_eq!(report.replies, report.sent);
+    assert_eq!(report.invalid_replies, 0);
+    assert!(report.rtt_percentile(99.0).is_some());
+    assert!(report.sent_throughput() > 0.0);
+}
+
+struct MyWorldSession {
+    socket_tools: SocketTools,
+}
+
+#[async_trait]
+impl WorldSession<MyWorld> for MyWorldSession {
+    async fn on_open(
+        tools: SocketTools,
+        _world: &'static MyWorld,
+    ) -> Result<std::sync::Arc<Self>, Error> {
+        Ok(Arc::new(Self {
+            socket_tools: tools,
+        }))
+    }
+
+    fn socket_tools(&self) -> &SocketTools {
+        &self.socket_tools
+    }
+
+    async fn on_message(world_session: &Arc<Self>, _world: &'static MyWorld, packet: Packet) {
+        if packet.cmd == 1 {
+            world_session.socket_tools.send(2, None);
+        } else {
+            world_session
+                .socket_tools
+                .send(packet.cmd, packet.payload.as_deref().map(|p| p.as_slice()));
+        }
+    }
+
+    async fn on_close(_world_session: &Arc<Self>, _world: &'static MyWorld) {}
+}
+
+struct MyWorld {}
+
+impl World for MyWorld {
+    type WorldSessionimplementer = MyWorldSession;
+
+    fn get_packet_limit(&self, _cmd: u16) -> (u16, u32, DosPolicy) {
+        (1000, 12000, DosPolicy::None)
+    }
+}
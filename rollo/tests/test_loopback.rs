@@ -0,0 +1,100 @@
+#![cfg(feature = "full")]
+use async_trait::async_trait;
+use rollo::{
+    error::Error,
+    packet::Packet,
+    server::{SocketTools, World, WorldSession, WorldSocketMgr},
+};
+use std::{
+    convert::TryInto,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::time::timeout;
+
+#[tokio::test]
+async fn test_loopback_echo() {
+    let world = Box::leak(Box::new(MyWorld {
+        sessions: AtomicU64::new(0),
+    }));
+    let mut server = WorldSocketMgr::new(world);
+
+    let mut clients = server.open_loopbacks(20);
+    for client in clients.iter_mut() {
+        client
+            .send(5, Some(&client.id().to_be_bytes()))
+            .await
+            .unwrap();
+    }
+
+    for client in clients.iter_mut() {
+        let packet = timeout(Duration::from_secs(5), client.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(packet.cmd, 5);
+        let id = u64::from_be_bytes(packet.payload.unwrap()[..].try_into().unwrap());
+        assert_eq!(id, client.id());
+    }
+
+    assert_eq!(world.sessions.load(Ordering::Acquire), 20);
+}
+
+#[tokio::test]
+async fn test_loopback_close() {
+    let world = Box::leak(Box::new(MyWorld {
+        sessions: AtomicU64::new(0),
+    }));
+    let mut server = WorldSocketMgr::new(world);
+
+    let mut client = server.open_loopback();
+    assert_eq!(client.id(), 1);
+    client.send(6, None).await.unwrap();
+
+    let result = timeout(Duration::from_secs(5), client.recv())
+        .await
+        .unwrap();
+    assert_eq!(result.err(), Some(Error::ReadingPacket));
+}
+
+struct MyWorldSession {
+    socket_tools: SocketTools,
+}
+
+#[async_trait]
+impl WorldSession<MyWorld> for MyWorldSession {
+    async fn on_open(tools: SocketTools, world: &'static MyWorld) -> Result<Arc<Self>, Error> {
+        world.sessions.fetch_add(1, Ordering::AcqRel);
+
+        Ok(Arc::new(Self {
+            socket_tools: tools,
+        }))
+    }
+
+    fn socket_tools(&self) -> &SocketTools {
+        &self.socket_tools
+    }
+
+    async fn on_message(world_session: &Arc<Self>, _world: &'static MyWorld, packet: Packet) {
+        if packet.cmd == 6 {
+            world_session.socket_tools.close().unwrap();
+        } else {
+            world_session
+                .socket_tools
+                .send(packet.cmd, packet.payload.as_deref().map(|p| p.as_slice()));
+        }
+    }
+
+    async fn on_close(_world_session: &Arc<Self>, _world: &'static MyWorld) {}
+}
+
+struct MyWorld {
+    sessions: AtomicU64,
+}
+
+impl World for MyWorld {
+    type WorldSessionimplementer = MyWorldSession;
+}
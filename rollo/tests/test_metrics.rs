@@ -0,0 +1,112 @@
+#![cfg(feature = "full")]
+use async_trait::async_trait;
+use rollo::{
+    error::Error,
+    packet::{to_bytes, Packet},
+    server::{ListenerSecurity, SocketTools, World, WorldSession, WorldSocketMgr},
+};
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    time::sleep,
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_metrics() {
+    let world = Box::leak(Box::new(MyWorld {}));
+    let mut server = WorldSocketMgr::new(world);
+    tokio::spawn(async move {
+        server
+            .start_game_loop(Duration::from_millis(10))
+            .start_network("127.0.0.1:6666", ListenerSecurity::Tcp)
+            .await
+            .unwrap();
+    });
+    tokio::spawn(rollo::metrics::serve("127.0.0.1:6667"));
+    sleep(Duration::from_millis(500)).await;
+
+    let mut socket = TcpStream::connect("127.0.0.1:6666").await.unwrap();
+    socket
+        .write_all(&to_bytes(5, Some(&[1, 2, 3])))
+        .await
+        .unwrap();
+    let mut buffer = [0; 9];
+    socket.read_exact(&mut buffer).await.unwrap();
+
+    let text = rollo::metrics::scrape();
+    for line in [
+        "rollo_connections 1",
+        "rollo_accepts_total 1",
+        "rollo_packets_received_total{cmd=\"5\"} 1",
+        "rollo_bytes_received_total{cmd=\"5\"} 9",
+        "rollo_packets_sent_total{cmd=\"5\"} 1",
+        "rollo_bytes_sent_total{cmd=\"5\"} 9",
+        "rollo_writer_queue_depth 0",
+    ]
+    .iter()
+    {
+        assert!(text.lines().any(|l| l == *line), "{} in\n{}", line, text);
+    }
+    assert!(!text.contains("rollo_tick_duration_seconds_count 0"));
+
+    // HTTP endpoint
+    let response = get("/metrics").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK"));
+    assert!(response.contains("application/openmetrics-text"));
+    assert!(response.contains("rollo_accepts_total 1"));
+    assert!(response.ends_with("# EOF\n"));
+
+    assert!(get("/").await.starts_with("HTTP/1.1 404"));
+
+    drop(socket);
+    sleep(Duration::from_millis(100)).await;
+    assert!(rollo::metrics::scrape()
+        .lines()
+        .any(|l| l == "rollo_connections 0"));
+}
+
+async fn get(path: &str) -> String {
+    let mut socket = TcpStream::connect("127.0.0.1:6667").await.unwrap();
+    socket
+        .write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).as_bytes())
+        .await
+        .unwrap();
+    let mut response = String::new();
+    socket.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+struct MyWorldSession {
+    socket_tools: SocketTools,
+}
+
+#[async_trait]
+impl WorldSession<MyWorld> for MyWorldSession {
+    async fn on_open(
+        tools: SocketTools,
+        _world: &'static MyWorld,
+    ) -> Result<std::sync::Arc<Self>, Error> {
+        Ok(Arc::new(Self {
+            socket_tools: tools,
+        }))
+    }
+
+    fn socket_tools(&self) -> &SocketTools {
+        &self.socket_tools
+    }
+
+    async fn on_message(world_session: &Arc<Self>, _world: &'static MyWorld, packet: Packet) {
+        world_session
+            .socket_tools
+            .send(packet.cmd, packet.payload.as_deref().map(|p| p.as_slice()));
+    }
+
+    async fn on_close(_world_session: &Arc<Self>, _world: &'static MyWorld) {}
+}
+
+struct MyWorld {}
+
+impl World for MyWorld {
+    type WorldSessionimplementer = MyWorldSession;
+}
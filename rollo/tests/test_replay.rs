@@ -0,0 +1,117 @@
+#![cfg(feature = "full")]
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use rollo::{
+    error::Error,
+    packet::Packet,
+    server::{
+        Direction, PacketRecorder, Replay, ReplayTiming, SocketTools, World, WorldSession,
+        WorldSocketMgr,
+    },
+};
+use std::{sync::Arc, time::Duration};
+use tokio::time::{sleep, timeout, Instant};
+
+#[tokio::test]
+async fn test_record_replay() {
+    let path = std::env::temp_dir().join(format!("rollo_replay_{}.rec", std::process::id()));
+
+    // Record
+    let world = Box::leak(Box::new(MyWorld::default()));
+    let recorder = Arc::new(PacketRecorder::create(&path).unwrap());
+    let mut server = WorldSocketMgr::new(world).with_recorder(Arc::clone(&recorder));
+
+    let mut clients = server.open_loopbacks(2);
+    for (index, client) in clients.iter_mut().enumerate() {
+        client.send(5, Some(&[index as u8])).await.unwrap();
+        sleep(Duration::from_millis(50)).await;
+        client.send(6, None).await.unwrap();
+    }
+    for client in clients.iter_mut() {
+        for _ in 0..2 {
+            timeout(Duration::from_secs(5), client.recv())
+                .await
+                .unwrap()
+                .unwrap();
+        }
+    }
+    recorder.flush().unwrap();
+
+    let replay = Replay::open(&path).unwrap();
+    let inbound = replay
+        .packets()
+        .iter()
+        .filter(|packet| packet.direction == Direction::Inbound)
+        .count();
+    let outbound = replay
+        .packets()
+        .iter()
+        .filter(|packet| packet.direction == Direction::Outbound)
+        .count();
+    assert_eq!(inbound, 4);
+    assert_eq!(outbound, 4);
+    assert_eq!(replay.packets()[0].payload, vec![0]);
+
+    // Replay as fast as possible
+    let replay_world = Box::leak(Box::new(MyWorld::default()));
+    let mut replay_server = WorldSocketMgr::new(replay_world);
+    timeout(
+        Duration::from_secs(5),
+        replay.run(&mut replay_server, ReplayTiming::Fast),
+    )
+    .await
+    .unwrap();
+    // The sessions are concurrent, the order is kept per session.
+    let mut received = replay_world.received.lock().clone();
+    let mut expected = world.received.lock().clone();
+    received.sort();
+    expected.sort();
+    assert_eq!(received, expected);
+
+    // Replay with the original timing
+    let replay_world = Box::leak(Box::new(MyWorld::default()));
+    let mut replay_server = WorldSocketMgr::new(replay_world);
+    let start = Instant::now();
+    replay.run(&mut replay_server, ReplayTiming::Original).await;
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    assert_eq!(replay_world.received.lock().len(), 4);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+struct MyWorldSession {
+    socket_tools: SocketTools,
+}
+
+#[async_trait]
+impl WorldSession<MyWorld> for MyWorldSession {
+    async fn on_open(
+        tools: SocketTools,
+        _world: &'static MyWorld,
+    ) -> Result<std::sync::Arc<Self>, Error> {
+        Ok(Arc::new(Self {
+            socket_tools: tools,
+        }))
+    }
+
+    fn socket_tools(&self) -> &SocketTools {
+        &self.socket_tools
+    }
+
+    async fn on_message(world_session: &Arc<Self>, world: &'static MyWorld, packet: Packet) {
+        let payload = packet.payload.as_deref().cloned().unwrap_or_default();
+        world.received.lock().push((packet.cmd, payload.clone()));
+        world_session.socket_tools.send(packet.cmd, Some(&payload));
+    }
+
+    async fn on_close(_world_session: &Arc<Self>, _world: &'static MyWorld) {}
+}
+
+#[derive(Default)]
+struct MyWorld {
+    received: Mutex<Vec<(u16, Vec<u8>)>>,
+}
+
+impl World for MyWorld {
+    type WorldSessionimplementer = MyWorldSession;
+}
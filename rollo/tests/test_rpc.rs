@@ -0,0 +1,235 @@
+#![cfg(feature = "full")]
+use async_trait::async_trait;
+use bytes::{BufMut, BytesMut};
+use rollo::{
+    error::Error,
+    packet::{Packet, FRAGMENT_CMD, RPC_REQUEST_CMD, RPC_RESPONSE_CMD},
+    server::{DosPolicy, ListenerSecurity, SocketTools, World, WorldSession, WorldSocketMgr},
+};
+use std::{convert::TryInto, io::ErrorKind, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    task::JoinHandle,
+    time::sleep,
+};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_rpc() {
+    setup(6666).await;
+    sleep(Duration::from_secs(1)).await;
+
+    let mut connect = TcpStream::connect("127.0.0.1:6666").await.unwrap();
+    connect.set_nodelay(true).unwrap();
+
+    // Client -> Server
+    connect
+        .write_all(&rpc_packet(RPC_REQUEST_CMD, 42, 10, 21))
+        .await
+        .unwrap();
+
+    assert_eq!(connect.read_u32().await.unwrap(), 10);
+    assert_eq!(connect.read_u16().await.unwrap(), RPC_RESPONSE_CMD);
+    assert_eq!(connect.read_u32().await.unwrap(), 42);
+    assert_eq!(connect.read_u16().await.unwrap(), 10);
+    assert_eq!(connect.read_u32().await.unwrap(), 42);
+
+    // Server -> Client
+    connect.write_all(&message_packet(11)).await.unwrap();
+
+    assert_eq!(connect.read_u32().await.unwrap(), 10);
+    assert_eq!(connect.read_u16().await.unwrap(), RPC_REQUEST_CMD);
+    let request_id = connect.read_u32().await.unwrap();
+    assert_eq!(connect.read_u16().await.unwrap(), 12);
+    assert_eq!(connect.read_u32().await.unwrap(), 7);
+
+    connect
+        .write_all(&rpc_packet(RPC_RESPONSE_CMD, request_id, 12, 8))
+        .await
+        .unwrap();
+
+    assert_eq!(connect.read_u32().await.unwrap(), 4);
+    assert_eq!(connect.read_u16().await.unwrap(), 13);
+    assert_eq!(connect.read_u32().await.unwrap(), 8);
+
+    // Timeout
+    connect.write_all(&message_packet(14)).await.unwrap();
+
+    assert_eq!(connect.read_u32().await.unwrap(), 10);
+    assert_eq!(connect.read_u16().await.unwrap(), RPC_REQUEST_CMD);
+    connect.read_u32().await.unwrap();
+    assert_eq!(connect.read_u16().await.unwrap(), 12);
+    connect.read_u32().await.unwrap();
+
+    assert_eq!(connect.read_u32().await.unwrap(), 0);
+    assert_eq!(connect.read_u16().await.unwrap(), 15);
+
+    // Reserved commands
+    connect.write_all(&message_packet(16)).await.unwrap();
+
+    assert_eq!(connect.read_u32().await.unwrap(), 0);
+    assert_eq!(connect.read_u16().await.unwrap(), 17);
+
+    connect
+        .write_all(&message_packet(FRAGMENT_CMD))
+        .await
+        .unwrap();
+    assert_eq!(
+        connect.read_u8().await.unwrap_err().kind(),
+        ErrorKind::UnexpectedEof
+    );
+
+    // Reserved commands of the requests
+    for cmd in [0, RPC_REQUEST_CMD, FRAGMENT_CMD] {
+        assert_closed(&[rpc_packet(RPC_REQUEST_CMD, 1, cmd, 21)]).await;
+    }
+
+    // Limits of the command of the requests
+    assert_closed(&[rpc_packet(RPC_REQUEST_CMD, 1, 30, 21)]).await;
+    assert_closed(&[
+        rpc_packet(RPC_REQUEST_CMD, 1, 31, 21),
+        rpc_packet(RPC_REQUEST_CMD, 2, 31, 21),
+    ])
+    .await;
+}
+
+// Sends the packets then a packet echoed by the session, the session must be closed before.
+async fn assert_closed(packets: &[BytesMut]) {
+    let mut connect = TcpStream::connect("127.0.0.1:6666").await.unwrap();
+    connect.set_nodelay(true).unwrap();
+
+    for packet in packets {
+        let _ = connect.write_all(packet).await;
+    }
+    let _ = connect.write_all(&message_packet(18)).await;
+
+    loop {
+        match connect.read_u32().await {
+            Ok(size) => {
+                let cmd = connect.read_u16().await.unwrap();
+                assert_ne!(cmd, 19);
+                let mut payload = vec![0; size as usize];
+                connect.read_exact(&mut payload).await.unwrap();
+            }
+            Err(error) => {
+                assert_eq!(error.kind(), ErrorKind::UnexpectedEof);
+                break;
+            }
+        }
+    }
+}
+
+fn rpc_packet(rpc_cmd: u16, request_id: u32, cmd: u16, value: u32) -> BytesMut {
+    let mut bytes = BytesMut::new();
+    bytes.put_u32(10);
+    bytes.put_u16(rpc_cmd);
+    bytes.put_u32(request_id);
+    bytes.put_u16(cmd);
+    bytes.put_u32(value);
+
+    bytes
+}
+
+fn message_packet(cmd: u16) -> BytesMut {
+    let mut bytes = BytesMut::new();
+    bytes.put_u32(0);
+    bytes.put_u16(cmd);
+
+    bytes
+}
+
+async fn setup(port: u32) -> JoinHandle<()> {
+    let world = Box::new(MyWorld {});
+    let world = Box::leak(world);
+
+    let mut server = WorldSocketMgr::new(world);
+
+    tokio::spawn(async move {
+        server
+            .start_network(format!("127.0.0.1:{}", port), ListenerSecurity::Tcp)
+            .await
+            .unwrap();
+    })
+}
+struct MyWorldSession {
+    socket_tools: SocketTools,
+}
+
+#[async_trait]
+impl WorldSession<MyWorld> for MyWorldSession {
+    async fn on_open(
+        tools: SocketTools,
+        _world: &'static MyWorld,
+    ) -> Result<std::sync::Arc<Self>, Error> {
+        Ok(Arc::new(Self {
+            socket_tools: tools,
+        }))
+    }
+
+    fn socket_tools(&self) -> &SocketTools {
+        &self.socket_tools
+    }
+
+    async fn on_message(world_session: &Arc<Self>, _world: &'static MyWorld, packet: Packet) {
+        let socket_tools = world_session.socket_tools.clone();
+
+        // Waiting for the reply must not block the reading task.
+        tokio::spawn(async move {
+            if packet.cmd == 11 {
+                let reply = socket_tools.request(12, Some(&7u32.to_be_bytes())).await;
+                let reply = reply.unwrap();
+                assert_eq!(reply.cmd, 12);
+                socket_tools.send(13, reply.payload.as_deref().map(Vec::as_slice));
+            } else if packet.cmd == 14 {
+                let reply = socket_tools
+                    .request_with_timeout(12, Some(&7u32.to_be_bytes()), Duration::from_millis(200))
+                    .await;
+                assert_eq!(reply.unwrap_err(), Error::RequestTimeout);
+                socket_tools.send(15, None);
+            } else if packet.cmd == 18 {
+                socket_tools.send(19, None);
+            } else if packet.cmd == 16 {
+                socket_tools.send(RPC_RESPONSE_CMD, None);
+                assert_eq!(
+                    socket_tools
+                        .request(RPC_REQUEST_CMD, None)
+                        .await
+                        .unwrap_err(),
+                    Error::ReservedCmd
+                );
+                socket_tools.send(17, None);
+            }
+        });
+    }
+
+    async fn on_request(
+        _world_session: &Arc<Self>,
+        _world: &'static MyWorld,
+        packet: Packet,
+    ) -> Option<Vec<u8>> {
+        if packet.cmd != 10 {
+            return None;
+        }
+
+        let value =
+            u32::from_be_bytes(packet.payload.as_deref().unwrap()[0..4].try_into().unwrap());
+
+        Some((value * 2).to_be_bytes().to_vec())
+    }
+
+    async fn on_close(_world_session: &Arc<Self>, _world: &'static MyWorld) {}
+}
+
+struct MyWorld {}
+
+impl World for MyWorld {
+    type WorldSessionimplementer = MyWorldSession;
+
+    fn get_packet_limit(&self, cmd: u16) -> (u16, u32, DosPolicy) {
+        match cmd {
+            30 => (15, 4, DosPolicy::Log),
+            31 => (1, 10 * 1024, DosPolicy::Close),
+            _ => (15, 10 * 1024, DosPolicy::Log),
+        }
+    }
+}
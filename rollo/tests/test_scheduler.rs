@@ -0,0 +1,86 @@
+#![cfg(feature = "full")]
+use async_trait::async_trait;
+use rollo::{
+    error::Error,
+    game::Scheduler,
+    packet::Packet,
+    server::{SocketTools, World, WorldSession, WorldSocketMgr},
+};
+use std::sync::{
+    atomic::{AtomicU16, Ordering},
+    Arc,
+};
+use tokio::time::{sleep, Duration};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_scheduler() {
+    let world = Box::leak(Box::new(MyWorld {
+        counter: AtomicU16::new(0),
+        scheduler: Scheduler::new(),
+    }));
+
+    let mut server = WorldSocketMgr::new(world);
+    server.start_game_loop(Duration::from_millis(20));
+
+    let counter = &world.counter;
+    world
+        .scheduler
+        .schedule(Duration::from_millis(100), move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+    world
+        .scheduler
+        .schedule_future(Duration::from_millis(50), async move {
+            counter.fetch_add(10, Ordering::Relaxed);
+        });
+    let handle = world
+        .scheduler
+        .schedule(Duration::from_millis(100), move || {
+            counter.fetch_add(100, Ordering::Relaxed);
+        });
+    handle.cancel();
+
+    sleep(Duration::from_millis(80)).await;
+    assert_eq!(world.counter.load(Ordering::Relaxed), 10);
+
+    sleep(Duration::from_millis(200)).await;
+    assert_eq!(world.counter.load(Ordering::Relaxed), 11);
+    assert!(world.scheduler.is_empty());
+}
+
+struct MyWorldSession {
+    socket_tools: SocketTools,
+}
+
+#[async_trait]
+impl WorldSession<MyWorld> for MyWorldSession {
+    async fn on_open(
+        tools: SocketTools,
+        _world: &'static MyWorld,
+    ) -> Result<std::sync::Arc<Self>, Error> {
+        Ok(Arc::new(Self {
+            socket_tools: tools,
+        }))
+    }
+
+    fn socket_tools(&self) -> &SocketTools {
+        &self.socket_tools
+    }
+
+    async fn on_message(_world_session: &Arc<Self>, _world: &'static MyWorld, _packet: Packet) {}
+
+    async fn on_close(_world_session: &Arc<Self>, _world: &'static MyWorld) {}
+}
+
+struct MyWorld {
+    counter: AtomicU16,
+    scheduler: Scheduler,
+}
+
+impl World for MyWorld {
+    type WorldSessionimplementer = MyWorldSession;
+
+    fn scheduler(&'static self) -> Option<&'static Scheduler> {
+        Some(&self.scheduler)
+    }
+}
@@ -0,0 +1,230 @@
+#![cfg(feature = "full")]
+use async_trait::async_trait;
+use bytes::{BufMut, BytesMut};
+use rollo::{
+    error::Error,
+    packet::{Packet, RPC_REQUEST_CMD, STREAM_CMD},
+    server::{
+        ByteStream, Channel, DosPolicy, ListenerSecurity, SocketTools, World, WorldSession,
+        WorldSocketConfiguration, WorldSocketMgr,
+    },
+};
+use std::{convert::TryInto, sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+    task::JoinHandle,
+    time::sleep,
+};
+
+const SIZE: usize = 100 * 1024;
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_stream() {
+    setup(6666).await;
+    sleep(Duration::from_secs(1)).await;
+
+    let mut connect = TcpStream::connect("127.0.0.1:6666").await.unwrap();
+    connect.set_nodelay(true).unwrap();
+
+    // Complete Packet
+    let data: Vec<u8> = (0..SIZE).map(|i| i as u8).collect();
+    write_stream(&mut connect, 1, 20, &data).await;
+
+    let mut received = Vec::new();
+    loop {
+        let size = connect.read_u32().await.unwrap() as usize;
+        assert_eq!(connect.read_u16().await.unwrap(), STREAM_CMD);
+        connect.read_u32().await.unwrap();
+        assert_eq!(connect.read_u16().await.unwrap(), 21);
+        let last = connect.read_u8().await.unwrap();
+        let mut chunk = vec![0; size - 7];
+        connect.read_exact(&mut chunk).await.unwrap();
+        received.extend_from_slice(&chunk);
+
+        if last == 1 {
+            break;
+        }
+    }
+
+    assert_eq!(received, data);
+
+    // Byte stream
+    write_stream(&mut connect, 2, 22, &data).await;
+
+    assert_eq!(connect.read_u32().await.unwrap(), 4);
+    assert_eq!(connect.read_u16().await.unwrap(), 23);
+    assert_eq!(connect.read_u32().await.unwrap() as usize, SIZE);
+
+    // Memory limit
+    write_stream(&mut connect, 3, 20, &vec![0; SIZE * 5]).await;
+
+    assert!(connect.read_u32().await.is_err());
+
+    // Streams limit
+    let mut connect = TcpStream::connect("127.0.0.1:6666").await.unwrap();
+    connect.set_nodelay(true).unwrap();
+
+    for stream_id in 0..5 {
+        let mut bytes = BytesMut::new();
+        bytes.put_u32(8);
+        bytes.put_u16(STREAM_CMD);
+        bytes.put_u32(stream_id);
+        bytes.put_u16(20);
+        bytes.put_u8(0);
+        bytes.put_u8(1);
+
+        if connect.write_all(&bytes).await.is_err() {
+            break;
+        }
+    }
+
+    assert!(connect.read_u32().await.is_err());
+
+    // Reserved commands
+    for cmd in [0, RPC_REQUEST_CMD] {
+        assert_closed(1, cmd, &[1; 10]).await;
+    }
+
+    // Command neither streamed nor reassembled
+    assert_closed(1, 24, &[1; 10]).await;
+
+    // Size limit of the command, per fragment
+    assert_closed(1, 25, &[1; 4000]).await;
+
+    // Size limit of the command, reassembled
+    assert_closed(1, 27, &[1; 3 * 4000]).await;
+
+    // Amount limit of the command, per fragment
+    assert_closed(1, 26, &[1; 3 * 4000]).await;
+}
+
+// Sends a stream then a packet echoed by the session, the session must be closed before.
+async fn assert_closed(stream_id: u32, cmd: u16, data: &[u8]) {
+    let mut connect = TcpStream::connect("127.0.0.1:6666").await.unwrap();
+    connect.set_nodelay(true).unwrap();
+    write_stream(&mut connect, stream_id, cmd, data).await;
+
+    let mut bytes = BytesMut::new();
+    bytes.put_u32(0);
+    bytes.put_u16(28);
+    let _ = connect.write_all(&bytes).await;
+
+    assert!(connect.read_u32().await.is_err(), "cmd {}", cmd);
+}
+
+async fn write_stream(connect: &mut TcpStream, stream_id: u32, cmd: u16, data: &[u8]) {
+    let mut chunks = data.chunks(4000).peekable();
+    while let Some(chunk) = chunks.next() {
+        let mut bytes = BytesMut::new();
+        bytes.put_u32(7 + chunk.len() as u32);
+        bytes.put_u16(STREAM_CMD);
+        bytes.put_u32(stream_id);
+        bytes.put_u16(cmd);
+        bytes.put_u8(chunks.peek().is_none() as u8);
+        bytes.put_slice(chunk);
+
+        if connect.write_all(&bytes).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn setup(port: u32) -> JoinHandle<()> {
+    let world = Box::new(MyWorld {});
+    let world = Box::leak(world);
+
+    let mut server = WorldSocketMgr::with_configuration(
+        world,
+        WorldSocketConfiguration::new()
+            .with_max_stream_memory(4 * SIZE)
+            .with_max_streams(4),
+    );
+
+    tokio::spawn(async move {
+        server
+            .start_network(format!("127.0.0.1:{}", port), ListenerSecurity::Tcp)
+            .await
+            .unwrap();
+    })
+}
+struct MyWorldSession {
+    socket_tools: SocketTools,
+}
+
+#[async_trait]
+impl WorldSession<MyWorld> for MyWorldSession {
+    async fn on_open(
+        tools: SocketTools,
+        _world: &'static MyWorld,
+    ) -> Result<std::sync::Arc<Self>, Error> {
+        Ok(Arc::new(Self {
+            socket_tools: tools,
+        }))
+    }
+
+    fn socket_tools(&self) -> &SocketTools {
+        &self.socket_tools
+    }
+
+    async fn on_message(world_session: &Arc<Self>, _world: &'static MyWorld, packet: Packet) {
+        if packet.cmd == 28 {
+            world_session.socket_tools.send(29, None);
+            return;
+        }
+
+        if packet.cmd != 20 {
+            return;
+        }
+
+        let payload = packet.payload.unwrap();
+        assert_eq!(payload.len(), SIZE);
+        assert_eq!(payload[SIZE - 1], (SIZE - 1) as u8);
+
+        world_session
+            .socket_tools
+            .send_stream(Channel::DEFAULT, 21, &payload);
+    }
+
+    fn is_streamed(&self, cmd: u16) -> bool {
+        cmd == 22 || cmd == 25 || cmd == 26
+    }
+
+    fn is_reassembled(&self, cmd: u16) -> bool {
+        cmd == 20 || cmd == 27 || cmd == 0 || cmd == RPC_REQUEST_CMD
+    }
+
+    async fn on_stream(world_session: Arc<Self>, _world: &'static MyWorld, stream: ByteStream) {
+        if stream.cmd() != 22 {
+            return;
+        }
+
+        let data = stream.read_to_end().await.unwrap();
+        let size: u32 = data.len().try_into().unwrap();
+        world_session
+            .socket_tools
+            .send(23, Some(&size.to_be_bytes()));
+    }
+
+    async fn on_close(_world_session: &Arc<Self>, _world: &'static MyWorld) {}
+}
+
+struct MyWorld {}
+
+impl World for MyWorld {
+    type WorldSessionimplementer = MyWorldSession;
+
+    fn global_limit(&self) -> (u16, u32) {
+        (10000, 10 * 1024 * 1024)
+    }
+
+    fn get_packet_limit(&self, cmd: u16) -> (u16, u32, DosPolicy) {
+        match cmd {
+            20 | 22 => (1000, 10 * SIZE as u32, DosPolicy::None),
+            25 => (1000, 3000, DosPolicy::None),
+            27 => (1000, 10000, DosPolicy::None),
+            26 => (2, 5000, DosPolicy::Close),
+            _ => (1000, 5000, DosPolicy::None),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+#![cfg(feature = "full")]
+use async_trait::async_trait;
+use rollo::{
+    error::Error,
+    packet::Packet,
+    server::{SocketTools, World, WorldSession, WorldSocketMgr},
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{Debug, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tokio::time::{sleep, timeout};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Event, Metadata, Subscriber,
+};
+use tracing_core::span::Current;
+
+#[tokio::test]
+async fn test_tracing() {
+    tracing::subscriber::set_global_default(SpanRecorder::default()).unwrap();
+
+    let world = Box::leak(Box::new(MyWorld {}));
+    let mut server = WorldSocketMgr::new(world);
+    let mut client = server.open_loopback();
+
+    client.send(5, Some(&[1, 2, 3])).await.unwrap();
+    let packet = timeout(Duration::from_secs(5), client.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(packet.cmd, 5);
+
+    // Ping without payload, the session is closed.
+    client.send(0, None).await.unwrap();
+    assert!(timeout(Duration::from_secs(5), client.recv())
+        .await
+        .unwrap()
+        .is_err());
+    sleep(Duration::from_millis(100)).await;
+
+    let spans = SPANS.lock().unwrap().clone();
+    let find = |name: &str, fields: &str| {
+        spans
+            .iter()
+            .any(|(span, recorded)| span == name && recorded.contains(fields))
+    };
+    assert!(
+        find("session", "id=1 addr=127.0.0.1:0 tls=false"),
+        "{:?}",
+        spans
+    );
+    assert!(find("packet", "cmd=5 size=3"), "{:?}", spans);
+    assert!(find("packet", "cmd=0 size=0"), "{:?}", spans);
+    assert!(find("on_open", ""), "{:?}", spans);
+    assert!(find("on_message", ""), "{:?}", spans);
+    assert!(find("on_close", ""), "{:?}", spans);
+    assert!(find("session", "error=PacketPayload"), "{:?}", spans);
+}
+
+// (name, fields) of the spans, the recorded fields are added to their span.
+static SPANS: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+thread_local! {
+    static STACK: RefCell<Vec<Id>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Default)]
+struct SpanRecorder {
+    next: AtomicU64,
+    spans: Mutex<HashMap<u64, (usize, &'static Metadata<'static>)>>,
+}
+
+struct Fields(String);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        let _ = write!(self.0, "{}={:?} ", field.name(), value);
+    }
+}
+
+impl Subscriber for SpanRecorder {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut fields = Fields(String::new());
+        span.record(&mut fields);
+
+        let mut spans = SPANS.lock().unwrap();
+        spans.push((span.metadata().name().to_string(), fields.0));
+
+        let id = self.next.fetch_add(1, Ordering::Relaxed) + 1;
+        self.spans
+            .lock()
+            .unwrap()
+            .insert(id, (spans.len() - 1, span.metadata()));
+        Id::from_u64(id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let mut fields = Fields(String::new());
+        values.record(&mut fields);
+
+        if let Some((index, _)) = self.spans.lock().unwrap().get(&span.into_u64()) {
+            SPANS.lock().unwrap()[*index].1.push_str(&fields.0);
+        }
+    }
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, _event: &Event<'_>) {}
+
+    fn enter(&self, span: &Id) {
+        STACK.with(|stack| stack.borrow_mut().push(span.clone()));
+    }
+
+    fn exit(&self, _span: &Id) {
+        STACK.with(|stack| stack.borrow_mut().pop());
+    }
+
+    fn current_span(&self) -> Current {
+        STACK.with(|stack| match stack.borrow().last() {
+            Some(id) => match self.spans.lock().unwrap().get(&id.into_u64()) {
+                Some((_, metadata)) => Current::new(id.clone(), metadata),
+                None => Current::none(),
+            },
+            None => Current::none(),
+        })
+    }
+}
+
+struct MyWorldSession {
+    socket_tools: SocketTools,
+}
+
+#[async_trait]
+impl WorldSession<MyWorld> for MyWorldSession {
+    async fn on_open(
+        tools: SocketTools,
+        _world: &'static MyWorld,
+    ) -> Result<std::sync::Arc<Self>, Error> {
+        Ok(Arc::new(Self {
+            socket_tools: tools,
+        }))
+    }
+
+    fn socket_tools(&self) -> &SocketTools {
+        &self.socket_tools
+    }
+
+    async fn on_message(world_session: &Arc<Self>, _world: &'static MyWorld, packet: Packet) {
+        world_session
+            .socket_tools
+            .send(packet.cmd, packet.payload.as_deref().map(|p| p.as_slice()));
+    }
+
+    async fn on_close(_world_session: &Arc<Self>, _world: &'static MyWorld) {}
+}
+
+struct MyWorld {}
+
+impl World for MyWorld {
+    type WorldSessionimplementer = MyWorldSession;
+}